@@ -38,6 +38,10 @@ android {
     buildFeatures {
         compose = true
     }
+    testOptions {
+        // Lets code that logs through android.util.Log run in JVM tests
+        unitTests.isReturnDefaultValues = true
+    }
 
     lint {
         disable += setOf("ProtectedPermissions")
@@ -59,6 +63,7 @@ dependencies {
     implementation(libs.androidx.security.crypto)
     implementation(libs.androidx.appcompat)
     testImplementation(libs.junit)
+    testImplementation(libs.json)
     androidTestImplementation(libs.androidx.junit)
     androidTestImplementation(libs.androidx.espresso.core)
     androidTestImplementation(platform(libs.androidx.compose.bom))
@@ -69,6 +74,7 @@ dependencies {
     implementation(libs.kotlinx.serialization.json)
     implementation(libs.androidx.navigation.compose)
     implementation(libs.okhttp)
+    implementation(project(":mmcp"))
     
     // CameraX dependencies
     implementation("androidx.camera:camera-camera2:1.3.1")
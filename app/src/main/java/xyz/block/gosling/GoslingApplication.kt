@@ -1,9 +1,16 @@
 package xyz.block.gosling
 
 import android.app.Application
+import xyz.block.gosling.features.agent.McpCatalog
 import xyz.block.gosling.features.overlay.OverlayService
 
 class GoslingApplication : Application() {
+    override fun onCreate() {
+        super.onCreate()
+        // Keep the catalogue of app extensions current so agent turns don't wait for discovery
+        McpCatalog.start(this)
+    }
+
     companion object {
         var isMainActivityRunning = false
         var isLauncherActivityRunning = false
@@ -11,6 +11,7 @@ import android.view.accessibility.AccessibilityEvent
 import androidx.core.app.NotificationCompat
 import xyz.block.gosling.R
 import xyz.block.gosling.features.agent.Agent
+import xyz.block.gosling.features.agent.ScreenWaiter
 import xyz.block.gosling.features.app.MainActivity
 import xyz.block.gosling.features.settings.SettingsStore
 
@@ -27,10 +28,13 @@ class GoslingAccessibilityService : AccessibilityService() {
         super.onServiceConnected()
         instance = this
 
-        // Set up service info with notification monitoring only
+        // Notifications for the agent to handle, window changes so tools can wait for the screen
         val info = AccessibilityServiceInfo()
         info.apply {
-            eventTypes = AccessibilityEvent.TYPE_NOTIFICATION_STATE_CHANGED
+            eventTypes = AccessibilityEvent.TYPE_NOTIFICATION_STATE_CHANGED or
+                    AccessibilityEvent.TYPE_WINDOW_STATE_CHANGED or
+                    AccessibilityEvent.TYPE_WINDOW_CONTENT_CHANGED or
+                    AccessibilityEvent.TYPE_WINDOWS_CHANGED
             feedbackType = AccessibilityServiceInfo.FEEDBACK_GENERIC
             flags =
                 AccessibilityServiceInfo.FLAG_INCLUDE_NOT_IMPORTANT_VIEWS or AccessibilityServiceInfo.FLAG_REPORT_VIEW_IDS or AccessibilityServiceInfo.FLAG_REQUEST_TOUCH_EXPLORATION_MODE or AccessibilityServiceInfo.FLAG_RETRIEVE_INTERACTIVE_WINDOWS
@@ -69,6 +73,8 @@ class GoslingAccessibilityService : AccessibilityService() {
     }
 
     override fun onAccessibilityEvent(event: AccessibilityEvent) {
+        ScreenWaiter.onAccessibilityEvent(event)
+
         if (event.eventType != AccessibilityEvent.TYPE_NOTIFICATION_STATE_CHANGED) return
         if (!SettingsStore(this).shouldProcessNotifications) return
 
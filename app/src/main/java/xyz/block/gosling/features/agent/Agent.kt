@@ -12,37 +12,45 @@ import android.view.WindowManager
 import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.SupervisorJob
+import kotlinx.coroutines.async
+import kotlinx.coroutines.awaitAll
+import kotlinx.coroutines.coroutineScope
 import kotlinx.coroutines.delay
 import kotlinx.coroutines.launch
 import kotlinx.coroutines.withContext
-import kotlinx.serialization.encodeToString
 import kotlinx.serialization.json.Json
+import kotlinx.serialization.json.JsonObject
+import kotlinx.serialization.json.JsonPrimitive
 import kotlinx.serialization.json.encodeToJsonElement
+import kotlinx.serialization.json.intOrNull
+import kotlinx.serialization.json.jsonPrimitive
 import okhttp3.ConnectionPool
-import okhttp3.MediaType.Companion.toMediaType
 import okhttp3.OkHttpClient
-import okhttp3.Request
-import okhttp3.RequestBody.Companion.toRequestBody
-import org.json.JSONObject
 import xyz.block.gosling.features.accessibility.GoslingAccessibilityService
 import xyz.block.gosling.features.agent.ToolHandler.callTool
-import xyz.block.gosling.features.agent.ToolHandler.getSerializableToolDefinitions
 import xyz.block.gosling.features.settings.SettingsStore
 import java.io.File
-import java.net.HttpURLConnection
 import java.time.LocalDateTime
 import java.time.format.DateTimeFormatter
 import java.util.concurrent.TimeUnit
-import kotlin.math.pow
 
 open class AgentException(message: String) : Exception(message)
 
 class ApiKeyException(message: String) : AgentException(message)
 
 sealed class AgentStatus {
-    data class Processing(val message: String) : AgentStatus()
+    /**
+     * [isPartial] marks a reply that is still streaming in; listeners should show it in place
+     * rather than treat it as a new event.
+     */
+    data class Processing(val message: String, val isPartial: Boolean = false) : AgentStatus()
     data class Success(val message: String, val milliseconds: Double = 0.0) : AgentStatus()
     data class Error(val message: String) : AgentStatus()
+
+    /**
+     * The run was cut short by one of its [RunLimits] or because it stopped making progress.
+     */
+    data class Stopped(val message: String, val reason: StopReason) : AgentStatus()
 }
 
 class Agent : Service() {
@@ -55,6 +63,13 @@ class Agent : Service() {
     private var statusListener: ((AgentStatus) -> Unit)? = null
     lateinit var conversationManager: ConversationManager
 
+    /**
+     * Creates the provider each LLM call goes through. Replace it to run the agent loop against
+     * a fake provider.
+     */
+    var providerFactory: (AiModel, ProviderConfig) -> LlmProvider =
+        { model, config -> LlmProvider.forModel(model, config) }
+
     enum class TriggerType {
         MAIN,
         NOTIFICATION,
@@ -66,20 +81,19 @@ class Agent : Service() {
         private var instance: Agent? = null
         fun getInstance(): Agent? = instance
         private const val TAG = "Agent"
-        private val jsonFormat = Json {
-            prettyPrint = true
-            encodeDefaults = true
-            ignoreUnknownKeys = true
-        }
 
-        private val okHttpClient by lazy {
-            OkHttpClient.Builder()
+        // Pause after a tool that changed the screen before running the next one
+        private const val UI_SETTLE_DELAY_MS = 500L
+
+        private val llmClient by lazy {
+            val httpClient = OkHttpClient.Builder()
                 .connectionPool(ConnectionPool(5, 5, TimeUnit.MINUTES))
                 .connectTimeout(30, TimeUnit.SECONDS)
                 .readTimeout(30, TimeUnit.SECONDS)
                 .writeTimeout(30, TimeUnit.SECONDS)
                 .retryOnConnectionFailure(true)
                 .build()
+            LlmClient(httpClient)
         }
     }
 
@@ -248,6 +262,12 @@ class Agent : Service() {
                 |the screen. In some cases you can call actionView to get something done in one shot -
                 |do so only if you are sure about the url to use.
                 |
+                |Every element in the UI hierarchy starts with a ref like [e12]. Prefer clickElement,
+                |longPressElement, setTextOnElement and scrollElement with that ref over tapping
+                |coordinates. An element keeps its ref when you read the hierarchy again as long as it
+                |stays in the same place in the same app; refs missing from the latest hierarchy no
+                |longer work.
+                |
                 |If you are not sure about content or apps pertaining to the user’s request, use your tools to control or gather the relevant information: do NOT guess or make up an answer.
                 |If you get stuck, you MUST re-plan extensively before each function call, and reflect extensively on the outcomes of the previous function calls. DO NOT do this entire process by making function calls only, as this can impair your ability to solve the problem and think insightfully.
                 |
@@ -274,8 +294,8 @@ class Agent : Service() {
                 |For example, check the calendar for free time and then check the maps that there is enough time to get between appointments. 
                 |
                 |If after taking a step and getting the ui hierarchy you don't what you find, don't
-                |immediately give up. Try asking for the hierarchy again to give the app more time
-                |to finalize rendering.
+                |immediately give up. Use waitFor to wait until the text or app you expect shows up,
+                |or until the screen stops changing, then read the hierarchy again.
                 |
                 |When you start an app, make sure the app is in the state you expect it to be in. If it is not, 
                 |try to navigate to the correct state (for example, getting back to the home page or start screen).
@@ -284,7 +304,15 @@ class Agent : Service() {
                 |you resolve this step. Keep iterating until you complete the task or have exhausted all possible approaches.
                 |
                 |When you think you are finished, double check to make sure you are done (sometimes you need to click more to continue).
-                |Use a screenshot if necessary to check.
+                |Use takeScreenshot if necessary to check.
+                |If the hierarchy is sparse or doesn't match what is on screen (games, WebViews), call
+                |takeScreenshot with marks and use tapMark with the number of the element you want.
+                |
+                |To move text such as a code between apps, put it on the clipboard with setClipboard and
+                |paste it with copyPasteText, which keeps what is already in the field.
+                |
+                |Before reading information out of an app's screens, check listResources: apps may share
+                |it directly, which readResource returns much faster.
                 |
                 |Some tasks will be one shot, but CRITICALLY some will require multiple steps and iteration and checking if you are done.
                 | for example, adding to a shopping cart will require multiple steps, as will planning a trip.
@@ -293,6 +321,8 @@ class Agent : Service() {
                 """.trimMargin()
 
             val startTime = System.currentTimeMillis()
+            val budget = RunBudget(settings.getRunLimits(triggerType), startTime)
+            val loopDetector = LoopDetector()
             val userMessage = if (imageUri != null) {
                 val contentResolver = applicationContext.contentResolver
 
@@ -347,173 +377,45 @@ class Agent : Service() {
             updateStatus(AgentStatus.Processing("Thinking..."))
 
             return withContext(scope.coroutineContext) {
-                var retryCount = 0
-                val maxRetries = 3
+                val loop = AgentLoop(
+                    budget = budget,
+                    messages = {
+                        conversationManager.currentConversation.value?.messages ?: emptyList()
+                    },
+                    append = { message -> appendMessage(message, newConversation) },
+                    callModel = { messages -> callLlm(messages, context) },
+                    runTools = { toolCalls, attachments ->
+                        executeTools(toolCalls, context, attachments)
+                    },
+                    isCancelled = { isCancelled },
+                    onStatus = { updateStatus(it) }
+                )
 
-                while (true) {
-                    if (isCancelled) {
+                val stopped = when (val outcome = loop.run()) {
+                    is LoopOutcome.Completed -> null
+                    is LoopOutcome.Stopped -> outcome.status
+                    LoopOutcome.Cancelled -> {
                         updateStatus(AgentStatus.Success("Operation cancelled"))
                         return@withContext "Operation cancelled by user"
                     }
 
-                    val startTimeLLMCall = System.currentTimeMillis()
-                    var response: JSONObject?
-                    try {
-                        if (retryCount > 0) {
-                            val delayMs = (2.0.pow(retryCount.toDouble()) * 1000).toLong()
-                            delay(delayMs)
-                            updateStatus(AgentStatus.Processing("Retrying... (attempt ${retryCount + 1})"))
-                        }
-
-                        response = callLlm(
-                            conversationManager.currentConversation.value?.messages ?: emptyList(),
-                            context
-                        )
-                        retryCount = 0
-                    } catch (e: AgentException) {
-                        if (isCancelled) {
-                            updateStatus(AgentStatus.Success("Operation cancelled"))
-                            return@withContext "Operation cancelled by user"
-                        }
-
-                        // Don't retry for API key errors
-                        if (e is ApiKeyException) {
-                            val errorMsg = "API key error: ${e.message}"
-                            updateStatus(AgentStatus.Error(errorMsg))
-                            Log.e(tag, "API key error", e)
+                    is LoopOutcome.Failed -> {
+                        Log.e(tag, outcome.message, outcome.cause)
+                        updateStatus(AgentStatus.Error(outcome.message))
+                        if (outcome.cause is ApiKeyException) {
                             // Make sure we're properly reporting this to the UI
                             CoroutineScope(Dispatchers.Main).launch {
-                                statusListener?.invoke(AgentStatus.Error(errorMsg))
+                                statusListener?.invoke(AgentStatus.Error(outcome.message))
                             }
-                            return@withContext errorMsg
                         }
-
-                        retryCount++
-
-                        if (retryCount >= maxRetries) {
-                            val errorMsg = "Failed after $maxRetries attempts: ${e.message}"
-                            updateStatus(AgentStatus.Error(errorMsg))
-                            Log.e(tag, "Error processing response", e)
-                            return@withContext errorMsg
-                        }
-                        continue
+                        return@withContext outcome.message
                     }
-                    val llmDuration = (System.currentTimeMillis() - startTimeLLMCall) / 1000.0
-
-                    try {
-                        val (assistantReply, toolCalls, annotation) = when {
-                            response.has("choices") -> {
-                                val assistantMessage =
-                                    response.getJSONArray("choices").getJSONObject(0)
-                                        .getJSONObject("message")
-                                val content = assistantMessage.optString("content", "Ok")
-                                val tools = assistantMessage.optJSONArray("tool_calls")?.let {
-                                    List(it.length()) { i -> ToolHandler.fromJson(it.getJSONObject(i)) }
-                                }
-                                val usage = response.getJSONObject("usage")
-                                val annotation = mapOf(
-                                    "duration" to llmDuration,
-                                    "input_tokens" to usage.getInt("prompt_tokens").toDouble(),
-                                    "output_tokens" to usage.getInt("completion_tokens").toDouble()
-                                )
-                                Triple(content, tools, annotation)
-                            }
-
-                            response.has("candidates") -> {
-                                val candidate = response.getJSONArray("candidates").getJSONObject(0)
-                                val content = candidate.getJSONObject("content")
-                                val text = content.getJSONArray("parts").getJSONObject(0)
-                                    .optString("text", "Ok")
-
-                                val tools = content.optJSONArray("parts")?.let { parts ->
-                                    List(parts.length()) { i ->
-                                        val part = parts.getJSONObject(i)
-                                        if (part.has("functionCall")) {
-                                            ToolHandler.fromJson(part)
-                                        } else null
-                                    }.filterNotNull()
-                                }
-                                val annotation = mapOf(
-                                    "duration" to llmDuration
-                                )
-                                Triple(text, tools, annotation)
-                            }
-
-                            else -> Triple("Unknown response format", null, emptyMap())
-                        }
-
-                        if (isCancelled) {
-                            updateStatus(AgentStatus.Success("Operation cancelled"))
-                            return@withContext "Operation cancelled by user"
-                        }
-
-                        updateStatus(AgentStatus.Processing(assistantReply))
-
-                        val (toolResults, toolAnnotations) = executeTools(toolCalls, context)
-
-                        val assistantMessage = Message(
-                            role = "assistant",
-                            content = contentWithText(assistantReply),
-                            toolCalls = toolCalls?.map { toolCall ->
-                                ToolCall(
-                                    id = toolCall.toolId,
-                                    function = ToolFunction(
-                                        name = toolCall.name,
-                                        arguments = toolCall.arguments.toString()
-                                    )
-                                )
-                            },
-                            stats = annotation
-                        )
-
-                        conversationManager.updateCurrentConversation(
-                            conversationManager.currentConversation.value?.copy(
-                                messages = conversationManager.currentConversation.value?.messages?.plus(
-                                    assistantMessage
-                                )
-                                    ?: listOf(assistantMessage)
-                            ) ?: newConversation
-                        )
-
-                        if (toolResults.isEmpty() || isCancelled) {
-                            if (isCancelled) {
-                                updateStatus(AgentStatus.Success("Operation cancelled"))
-                                return@withContext "Operation cancelled by user"
-                            } else {
-                                updateStatus(AgentStatus.Success(assistantReply))
-                                break
-                            }
-                        }
-
-                        for ((result, toolAnnotation) in toolResults.zip(toolAnnotations)) {
-                            val toolMessage = Message(
-                                role = "tool",
-                                toolCallId = result["tool_call_id"].toString(),
-                                content = listOf(Content.Text(text = result["output"].toString())),
-                                name = result["name"].toString(),
-                                stats = toolAnnotation
-                            )
-
-                            conversationManager.updateCurrentConversation(
-                                conversationManager.currentConversation.value?.copy(
-                                    messages = conversationManager.currentConversation.value?.messages?.plus(
-                                        toolMessage
-                                    )
-                                        ?: listOf(toolMessage)
-                                ) ?: newConversation
-                            )
-                        }
-                    } catch (e: Exception) {
-                        Log.e(tag, "Error processing response", e)
-                        val errorMsg = "Error processing response: ${e.message}"
-                        updateStatus(AgentStatus.Error(errorMsg))
-                        return@withContext errorMsg
-                    }
-                    continue
                 }
 
+                stopped?.let { recordStop(it, budget) }
+
                 val explanationPrompt = "" // change to something if you want an explanation
-                if (explanationPrompt != "") {
+                if (explanationPrompt != "" && stopped == null) {
                     val internetQuery = Message(
                         role = "user",
                         content = contentWithText(explanationPrompt)
@@ -529,11 +431,7 @@ class Agent : Service() {
                             explainConversation.messages,
                             context
                         )
-                        val assistantMessage =
-                            response.getJSONArray("choices").getJSONObject(0)
-                                .getJSONObject("message")
-                        val content = assistantMessage.optString("content", "Ok")
-                        Log.d(tag, "Explanation response: $content")
+                        Log.d(tag, "Explanation response: ${response.text}")
                     }
                 }
 
@@ -560,6 +458,12 @@ class Agent : Service() {
                     )
                 }
 
+                stopped?.let { status ->
+                    Log.w(tag, status.message)
+                    updateStatus(status)
+                    return@withContext status.message
+                }
+
                 val completionTime = (System.currentTimeMillis() - startTime) / 1000.0
                 val completionMessage =
                     "Task completed successfully in %.1f seconds".format(completionTime)
@@ -669,165 +573,147 @@ class Agent : Service() {
         }
     }
 
-    private fun removeOutdatedPayloads(messages: List<Message>): List<Message> {
-        val isUiHierarchyCall = { message: Message ->
-            message.role == "tool" && message.name == "getUiHierarchy"
-        }
-
-        val lastUiHierarchyIndex = messages.indexOfLast(isUiHierarchyCall)
-
-        return messages.mapIndexed { index, message ->
-            when {
-                index >= lastUiHierarchyIndex -> message
-                isUiHierarchyCall(message) ->
-                    message.copy(content = contentWithText("{UI hierarchy output truncated}"))
-
-                message.role == "user" && message.content?.any { it is Content.ImageUrl } == true ->
-                    message.copy(content = message.content.filterNot { it is Content.ImageUrl })
+    private fun publishPartialReply(text: String) {
+        conversationManager.setStreamingReply(text)
+        updateStatus(AgentStatus.Processing(text, isPartial = true))
+    }
 
-                else -> message
-            }
-        }
+    private fun appendMessage(message: Message, fallback: Conversation) {
+        conversationManager.updateCurrentConversation(
+            conversationManager.currentConversation.value?.copy(
+                messages = conversationManager.currentConversation.value?.messages?.plus(message)
+                    ?: listOf(message)
+            ) ?: fallback
+        )
     }
 
-    private fun makeHttpCall(
-        urlString: String,
-        requestBody: String,
-        apiKey: String?,
-        model: AiModel
-    ): JSONObject {
-        // Check for missing API key before making the request
-        if (apiKey.isNullOrBlank()) {
+    private suspend fun callLlm(messages: List<Message>, context: Context): AssistantTurn {
+        val settings = SettingsStore(context)
+        val model = settings.currentModel
+        val apiKey = settings.getApiKey(model.provider)
+        val config = ProviderConfig(
+            baseUrl = settings.getBaseUrl(model.provider),
+            extraHeaders = ProviderConfig.parseHeaders(settings.getExtraHeaders(model.provider))
+        )
+
+        // Check for empty API key early. Custom endpoints may not need one.
+        if (apiKey.isBlank() && config.baseUrl.isBlank()) {
             val errorMsg = "API key is missing for ${model.provider}. Please add your API key in settings."
             Log.e(tag, errorMsg)
+            updateStatus(AgentStatus.Error(errorMsg))
             throw ApiKeyException(errorMsg)
         }
-        
-        val request = Request.Builder()
-            .url(urlString)
-            .post(requestBody.toRequestBody("application/json".toMediaType()))
-            .apply {
-                if (model.provider == ModelProvider.OPENAI) {
-                    addHeader("Authorization", "Bearer $apiKey")
-                }
-            }
-            .build()
-
-        okHttpClient.newCall(request).execute().use { response ->
-            if (!response.isSuccessful) {
-                val errorBody = response.body?.string() ?: ""
-                val errorResponse = errorBody.ifEmpty {
-                    when (response.code) {
-                        HttpURLConnection.HTTP_UNAUTHORIZED -> "Unauthorized - API key may be invalid"
-                        HttpURLConnection.HTTP_FORBIDDEN -> "Forbidden - Access denied"
-                        HttpURLConnection.HTTP_NOT_FOUND -> "Not found - Invalid API endpoint"
-                        HttpURLConnection.HTTP_BAD_REQUEST -> "Bad request"
-                        else -> "HTTP Error ${response.code}"
-                    }
-                }
-                handleHttpError(response.code, errorResponse)
-            }
-
-            val responseBody = response.body?.string()
-                ?: throw AgentException("Empty response body")
-
-            return JSONObject(responseBody)
-        }
-    }
 
-    private suspend fun callLlm(messages: List<Message>, context: Context): JSONObject {
-        val settings = SettingsStore(context)
-        val model = AiModel.fromIdentifier(settings.llmModel)
-        val apiKey = settings.getApiKey(model.provider)
-        
-        // Check for empty API key early
-        if (apiKey.isNullOrBlank()) {
-            updateStatus(AgentStatus.Error("API key is missing. Please add your API key in settings."))
-            throw ApiKeyException("API key is missing. Please add your API key in settings.")
+        val compaction = ContextCompactor(model, settings.toolOutputMaxAge).compact(messages)
+        if (compaction.didCompact) {
+            recordCompaction(compaction)
         }
+        val processedMessages = compaction.messages
+        val provider = providerFactory(model, config)
 
-        val processedMessages = removeOutdatedPayloads(messages)
-
-        val urlString = when (model.provider) {
-            ModelProvider.OPENAI -> "https://api.openai.com/v1/chat/completions"
-            ModelProvider.GEMINI -> "https://generativelanguage.googleapis.com/v1beta/models/${model.identifier}:generateContent?key=$apiKey"
+        return withContext(Dispatchers.IO) {
+            val stream = settings.streamResponses && provider.supportsStreaming
+            val request = provider.buildRequest(
+                model,
+                apiKey,
+                processedMessages,
+                ToolHandler.getToolDefinitions(context),
+                stream
+            )
+            try {
+                if (stream) {
+                    llmClient.stream(provider, request, { isCancelled }, ::publishPartialReply)
+                } else {
+                    llmClient.complete(provider, request)
+                }
+            } catch (e: ApiKeyException) {
+                Log.e(tag, "API key error: ${e.message}")
+                updateStatus(AgentStatus.Error(e.message ?: "Invalid API key"))
+                throw e
+            } finally {
+                if (stream) conversationManager.setStreamingReply(null)
+            }
         }
+    }
 
-        val json = jsonFormat
-
-        val requestBody = when (model.provider) {
-            ModelProvider.OPENAI -> {
-                val toolDefinitions =
-                    when (val result = getSerializableToolDefinitions(context, model.provider)) {
-                        is SerializableToolDefinitions.OpenAITools -> result.definitions
-                        else -> emptyList()
-                    }
-
-                val openAIRequest = OpenAIRequest(
-                    model = model.identifier,
-                    messages = processedMessages,
-                    temperature = if (model.identifier != "o3-mini") 0.1 else null,
-                    tools = toolDefinitions
-                )
+    /**
+     * Runs the tool calls of one turn. Consecutive [ToolConcurrency.CONCURRENT] calls run
+     * together; everything else runs one at a time in the order requested, with a pause after
+     * each UI tool so the screen can settle before the next call looks at it. Results are
+     * returned in the order of [toolCalls] regardless of when each finished.
+     */
+    private suspend fun executeTools(
+        toolCalls: List<InternalToolCall>?,
+        context: Context,
+        attachments: MutableList<Content>
+    ): Pair<List<Map<String, String>>, List<Map<String, Double>>> {
+        if (toolCalls == null || isCancelled) return Pair(emptyList(), emptyList())
 
-                json.encodeToString(openAIRequest)
+        val results = arrayOfNulls<Pair<Map<String, String>, Map<String, Double>>>(toolCalls.size)
+        var index = 0
+        var previousWasUi = false
+
+        while (index < toolCalls.size) {
+            val concurrency = ToolHandler.concurrencyOf(toolCalls[index].name)
+            val batchEnd = if (concurrency == ToolConcurrency.CONCURRENT) {
+                var end = index
+                while (end < toolCalls.size &&
+                    ToolHandler.concurrencyOf(toolCalls[end].name) == ToolConcurrency.CONCURRENT
+                ) {
+                    end++
+                }
+                end
+            } else {
+                index + 1
             }
 
-            ModelProvider.GEMINI -> {
-                val combinedText = processedMessages.joinToString("\n") {
-                    "${it.role}: ${it.content}"
-                }
+            if (previousWasUi && !isCancelled) {
+                delay(UI_SETTLE_DELAY_MS)
+            }
 
-                val tools =
-                    when (val result = getSerializableToolDefinitions(context, model.provider)) {
-                        is SerializableToolDefinitions.GeminiTools -> result.tools
-                        else -> emptyList()
+            coroutineScope {
+                (index until batchEnd).map { i ->
+                    async(Dispatchers.IO) {
+                        results[i] = executeTool(toolCalls[i], i, context, attachments)
                     }
-
-                val geminiRequest = GeminiRequest(
-                    contents = GeminiContent(
-                        parts = listOf(GeminiPart(text = combinedText))
-                    ),
-                    tools = tools
-                )
-
-                json.encodeToString(geminiRequest)
+                }.awaitAll()
             }
-        }
 
-        return withContext(Dispatchers.IO) {
-            makeHttpCall(urlString, requestBody, apiKey, model)
+            previousWasUi = concurrency == ToolConcurrency.UI
+            index = batchEnd
         }
-    }
 
-    private fun executeTools(
-        toolCalls: List<InternalToolCall>?,
-        context: Context
-    ): Pair<List<Map<String, String>>, List<Map<String, Double>>> {
-        if (toolCalls == null || isCancelled) return Pair(emptyList(), emptyList())
-
-        val annotations: MutableList<Map<String, Double>> = mutableListOf()
+        return Pair(results.map { it!!.first }, results.map { it!!.second })
+    }
 
-        val results = toolCalls.mapIndexed { index, toolCall ->
-            if (isCancelled) {
-                annotations.add(emptyMap())
-                return@mapIndexed mapOf(
-                    "tool_call_id" to "cancelled_${System.currentTimeMillis()}_$index",
-                    "output" to "Operation cancelled by user",
-                    "name" to "cancelled"
-                )
-            }
+    private fun executeTool(
+        toolCall: InternalToolCall,
+        index: Int,
+        context: Context,
+        attachments: MutableList<Content>
+    ): Pair<Map<String, String>, Map<String, Double>> {
+        if (isCancelled) {
+            return mapOf(
+                "tool_call_id" to "cancelled_${System.currentTimeMillis()}_$index",
+                "output" to "Operation cancelled by user",
+                "name" to "cancelled"
+            ) to emptyMap()
+        }
 
-            val startTime = System.currentTimeMillis()
-            val result = callTool(toolCall, context, GoslingAccessibilityService.getInstance())
-            annotations.add(mapOf("duration" to (System.currentTimeMillis() - startTime) / 1000.0))
-            mapOf(
-                "tool_call_id" to toolCall.toolId,
-                "output" to result,
-                "name" to toolCall.name
-            )
+        val startTime = System.currentTimeMillis()
+        val result = callTool(
+            toolCall,
+            context,
+            GoslingAccessibilityService.getInstance(),
+            onProgress = { updateStatus(AgentStatus.Processing("${toolCall.name}: $it")) }
+        ) {
+            attachments.add(it)
         }
-        return Pair(results, annotations)
+        return mapOf(
+            "tool_call_id" to toolCall.toolId,
+            "output" to result,
+            "name" to toolCall.name
+        ) to mapOf("duration" to (System.currentTimeMillis() - startTime) / 1000.0)
     }
 
     private fun calculateConversationStats(
@@ -862,19 +748,49 @@ class Agent : Service() {
         }
     }
 
-    private fun isApiKeyError(responseCode: Int): Boolean {
-        return responseCode == HttpURLConnection.HTTP_UNAUTHORIZED
+    /**
+     * Notes a compaction on the current conversation so session dumps show what the model
+     * didn't see. Only what wasn't covered by an earlier note is recorded.
+     */
+    private fun recordCompaction(compaction: CompactionResult) {
+        val conversation = conversationManager.currentConversation.value ?: return
+
+        val recorded = conversation.annotations
+            .filterIsInstance<JsonObject>()
+            .filter { it["type"] == JsonPrimitive("compaction") }
+        fun recordedCount(key: String) = recorded.sumOf { it[key]?.jsonPrimitive?.intOrNull ?: 0 }
+
+        val newCompaction = compaction.since(
+            summarized = recordedCount("summarized_messages"),
+            dropped = recordedCount("dropped_tool_outputs")
+        )
+        if (!newCompaction.didCompact) return
+
+        val annotation = newCompaction.toAnnotation()
+        Log.d(tag, "Compacted conversation: $annotation")
+        conversationManager.updateCurrentConversation(
+            conversation.copy(annotations = conversation.annotations + annotation)
+        )
     }
 
-    private fun handleHttpError(responseCode: Int, errorResponse: String): Nothing {
-        if (isApiKeyError(responseCode)) {
-            val errorMessage = "Invalid API key. Please check your API key in settings."
-            Log.e(tag, "API key error: $errorResponse")
-            updateStatus(AgentStatus.Error(errorMessage))
-            throw ApiKeyException(errorMessage)
-        }
-
-        throw AgentException(errorResponse)
+    /**
+     * Notes on the current conversation why the run was stopped and how far it got.
+     */
+    private fun recordStop(status: AgentStatus.Stopped, budget: RunBudget) {
+        val conversation = conversationManager.currentConversation.value ?: return
+        val annotation = JsonObject(
+            mapOf(
+                "type" to JsonPrimitive("stopped"),
+                "time" to JsonPrimitive(System.currentTimeMillis()),
+                "reason" to JsonPrimitive(status.reason.name),
+                "message" to JsonPrimitive(status.message),
+                "steps" to JsonPrimitive(budget.steps),
+                "tokens" to JsonPrimitive(budget.tokens)
+            )
+        )
+        conversationManager.updateCurrentConversation(
+            conversation.copy(annotations = conversation.annotations + annotation)
+        )
     }
 
     fun processScreenshot(uri: Uri, instructions: String) {
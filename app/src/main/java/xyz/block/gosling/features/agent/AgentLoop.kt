@@ -0,0 +1,178 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.coroutines.delay
+import java.util.Collections
+import kotlin.math.pow
+
+/**
+ * How a run of the [AgentLoop] ended.
+ */
+sealed class LoopOutcome {
+    data class Completed(val reply: String) : LoopOutcome()
+    data object Cancelled : LoopOutcome()
+    data class Stopped(val status: AgentStatus.Stopped) : LoopOutcome()
+    data class Failed(val message: String, val cause: Exception) : LoopOutcome()
+}
+
+/**
+ * The agent loop: asks the model for a turn, runs the tools it calls and feeds their results
+ * back until it answers without calling any, the [budget] runs out, it stops making progress
+ * or the run is cancelled. Failed model calls are retried with exponential backoff, except
+ * for [ApiKeyException]s.
+ *
+ * The conversation is read through [messages] and grown through [append], so the loop itself
+ * knows nothing about where it is stored or how the model and tools are reached.
+ */
+class AgentLoop(
+    private val budget: RunBudget,
+    private val messages: () -> List<Message>,
+    private val append: (Message) -> Unit,
+    private val callModel: suspend (List<Message>) -> AssistantTurn,
+    private val runTools: suspend (
+        toolCalls: List<InternalToolCall>?,
+        attachments: MutableList<Content>
+    ) -> Pair<List<Map<String, String>>, List<Map<String, Double>>>,
+    private val isCancelled: () -> Boolean,
+    private val onStatus: (AgentStatus) -> Unit,
+    private val loopDetector: LoopDetector = LoopDetector(),
+    private val retryDelayMs: (Int) -> Long = { retry -> (2.0.pow(retry.toDouble()) * 1000).toLong() }
+) {
+    companion object {
+        private const val MAX_RETRIES = 3
+
+        // How often the model is told it looks stuck before the run is stopped
+        private const val MAX_STUCK_WARNINGS = 1
+
+        private const val STUCK_PROMPT = "You appear to be stuck: %s. Do not repeat the same " +
+                "actions. Re-plan: look at the current screen again, consider why your previous " +
+                "attempts did not work and try a different approach. If the task cannot be " +
+                "completed, stop and explain why."
+    }
+
+    suspend fun run(): LoopOutcome {
+        var retryCount = 0
+        var stuckWarnings = 0
+
+        while (true) {
+            if (isCancelled()) return LoopOutcome.Cancelled
+
+            val exceeded = budget.exceeded()
+            if (exceeded != null) {
+                return LoopOutcome.Stopped(AgentStatus.Stopped(budget.describe(exceeded), exceeded))
+            }
+
+            val startTimeLLMCall = System.currentTimeMillis()
+            val response: AssistantTurn
+            try {
+                if (retryCount > 0) {
+                    delay(retryDelayMs(retryCount))
+                    onStatus(AgentStatus.Processing("Retrying... (attempt ${retryCount + 1})"))
+                }
+
+                response = callModel(messages())
+                retryCount = 0
+                budget.recordStep(response.usage)
+            } catch (e: AgentException) {
+                if (isCancelled()) return LoopOutcome.Cancelled
+
+                // Don't retry for API key errors
+                if (e is ApiKeyException) {
+                    return LoopOutcome.Failed("API key error: ${e.message}", e)
+                }
+
+                retryCount++
+                if (retryCount >= MAX_RETRIES) {
+                    return LoopOutcome.Failed("Failed after $MAX_RETRIES attempts: ${e.message}", e)
+                }
+                continue
+            }
+            val llmDuration = (System.currentTimeMillis() - startTimeLLMCall) / 1000.0
+
+            try {
+                val assistantReply = response.text
+                val toolCalls = response.toolCalls
+                val annotation = mapOf("duration" to llmDuration) +
+                        (response.usage?.let { usage ->
+                            mapOf(
+                                "input_tokens" to usage.inputTokens.toDouble(),
+                                "output_tokens" to usage.outputTokens.toDouble()
+                            )
+                        } ?: emptyMap())
+
+                if (isCancelled()) return LoopOutcome.Cancelled
+
+                onStatus(AgentStatus.Processing(assistantReply))
+
+                val attachments = Collections.synchronizedList(mutableListOf<Content>())
+                val (toolResults, toolAnnotations) = runTools(toolCalls, attachments)
+
+                append(
+                    Message(
+                        role = "assistant",
+                        content = contentWithText(assistantReply),
+                        toolCalls = toolCalls?.map { toolCall ->
+                            ToolCall(
+                                id = toolCall.toolId,
+                                function = ToolFunction(
+                                    name = toolCall.name,
+                                    arguments = toolCall.arguments.toString()
+                                )
+                            )
+                        },
+                        stats = annotation
+                    )
+                )
+
+                if (isCancelled()) return LoopOutcome.Cancelled
+                if (toolResults.isEmpty()) {
+                    onStatus(AgentStatus.Success(assistantReply))
+                    return LoopOutcome.Completed(assistantReply)
+                }
+
+                for ((result, toolAnnotation) in toolResults.zip(toolAnnotations)) {
+                    append(
+                        Message(
+                            role = "tool",
+                            toolCallId = result["tool_call_id"].toString(),
+                            content = listOf(Content.Text(text = result["output"].toString())),
+                            name = result["name"].toString(),
+                            stats = toolAnnotation
+                        )
+                    )
+                }
+
+                // Tool results are text only, so images they produce follow as a user turn
+                if (attachments.isNotEmpty()) {
+                    append(Message(role = "user", content = attachments.toList()))
+                }
+
+                val stuckReason = loopDetector.record(
+                    toolCalls.orEmpty(),
+                    toolResults.map { it["output"].orEmpty() }
+                )
+                if (stuckReason != null) {
+                    if (stuckWarnings >= MAX_STUCK_WARNINGS) {
+                        return LoopOutcome.Stopped(
+                            AgentStatus.Stopped(
+                                "${budget.describe(StopReason.STUCK)}: $stuckReason",
+                                StopReason.STUCK
+                            )
+                        )
+                    }
+                    stuckWarnings++
+                    loopDetector.reset()
+
+                    append(
+                        Message(
+                            role = "user",
+                            content = contentWithText(STUCK_PROMPT.format(stuckReason))
+                        )
+                    )
+                    onStatus(AgentStatus.Processing("Stuck, re-planning..."))
+                }
+            } catch (e: Exception) {
+                return LoopOutcome.Failed("Error processing response: ${e.message}", e)
+            }
+        }
+    }
+}
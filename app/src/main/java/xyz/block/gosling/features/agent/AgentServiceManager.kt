@@ -117,6 +117,7 @@ class AgentServiceManager(private val context: Context) {
             is AgentStatus.Processing -> status.message
             is AgentStatus.Success -> status.message
             is AgentStatus.Error -> "Error: ${status.message}"
+            is AgentStatus.Stopped -> status.message
         }
 
         val notification = createNotification(message)
@@ -138,6 +139,7 @@ class AgentServiceManager(private val context: Context) {
             .setSmallIcon(R.drawable.ic_launcher_foreground)
             .setContentIntent(pendingIntent)
             .setOngoing(true)
+            .setOnlyAlertOnce(true)
             .build()
     }
 } 
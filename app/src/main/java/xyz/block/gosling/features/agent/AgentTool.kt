@@ -0,0 +1,227 @@
+package xyz.block.gosling.features.agent
+
+import android.accessibilityservice.AccessibilityService
+import android.content.Context
+import org.json.JSONArray
+import org.json.JSONObject
+
+/**
+ * Everything a tool may need while it runs. Tools that set [AgentTool.requiresAccessibility]
+ * are only invoked when the accessibility service is connected.
+ */
+class ToolContext(
+    val context: Context,
+    private val accessibility: AccessibilityService?,
+    private val cancellation: () -> Boolean = { false },
+    private val attachments: (Content) -> Unit = {}
+) {
+    val accessibilityService: AccessibilityService
+        get() = accessibility ?: throw IllegalStateException("Accessibility service not available")
+
+    val hasAccessibility: Boolean
+        get() = accessibility != null
+
+    val isCancelled: Boolean
+        get() = cancellation()
+
+    /**
+     * Adds [content], such as an image, to a user message that follows the tool results of
+     * this turn. Tool results themselves can only carry text.
+     */
+    fun attach(content: Content) = attachments(content)
+}
+
+/**
+ * How a tool may be scheduled when the model asks for several in one turn.
+ */
+enum class ToolConcurrency {
+    // Reads data without side effects and can run alongside other concurrent tools
+    CONCURRENT,
+
+    // Runs on its own, in the order the model asked for it
+    SERIAL,
+
+    // Changes what is on screen; runs on its own and the UI is given time to settle after it
+    UI
+}
+
+/**
+ * A tool the model can call. The [parameters] schema is what the model is shown and also how
+ * [ToolArguments] resolves types and defaults.
+ */
+abstract class AgentTool(
+    val name: String,
+    val description: String,
+    val parameters: ToolParametersObject = toolParameters(),
+    val requiresAccessibility: Boolean = false,
+    val concurrency: ToolConcurrency = ToolConcurrency.SERIAL
+) {
+    abstract fun execute(ctx: ToolContext, args: ToolArguments): String
+
+    fun toDefinition(): ToolDefinition {
+        return ToolDefinition(
+            function = ToolFunctionDefinition(
+                name = name,
+                description = description,
+                parameters = parameters
+            )
+        )
+    }
+}
+
+/**
+ * Raised when the model passes arguments that don't fit the tool's schema. The message is
+ * written for the model so it can correct the call.
+ */
+class ToolArgumentException(
+    val field: String,
+    message: String,
+    val expected: String? = null
+) : Exception(message)
+
+/**
+ * Typed access to a tool call's arguments. Absent values fall back to the schema default, and
+ * anything missing or of the wrong shape raises a [ToolArgumentException].
+ */
+class ToolArguments(
+    private val json: JSONObject,
+    private val properties: Map<String, ToolParameter>,
+    private val path: String = ""
+) {
+    fun has(name: String): Boolean = json.has(name) && !json.isNull(name)
+
+    fun getString(name: String): String {
+        val value = when (val raw = valueOf(name)) {
+            is String -> raw
+            is Number, is Boolean -> raw.toString()
+            else -> throw typeError(name, "string")
+        }
+        properties[name]?.enum?.let { allowed ->
+            if (value !in allowed) {
+                throw ToolArgumentException(
+                    field = fieldName(name),
+                    message = "'${fieldName(name)}' must be one of ${allowed.joinToString()}, got '$value'",
+                    expected = allowed.joinToString("|")
+                )
+            }
+        }
+        return value
+    }
+
+    fun getInt(name: String): Int {
+        return when (val raw = valueOf(name)) {
+            is Number -> raw.toInt()
+            is String -> raw.trim().toDoubleOrNull()?.toInt()
+            else -> null
+        } ?: throw typeError(name, "integer")
+    }
+
+    fun getDouble(name: String): Double {
+        return when (val raw = valueOf(name)) {
+            is Number -> raw.toDouble()
+            is String -> raw.trim().toDoubleOrNull()
+            else -> null
+        } ?: throw typeError(name, "number")
+    }
+
+    fun getBoolean(name: String): Boolean {
+        return when (val raw = valueOf(name)) {
+            is Boolean -> raw
+            is String -> raw.trim().lowercase().toBooleanStrictOrNull()
+            else -> null
+        } ?: throw typeError(name, "boolean")
+    }
+
+    fun getObject(name: String): ToolArguments {
+        val raw = valueOf(name) as? JSONObject ?: throw typeError(name, "object")
+        return ToolArguments(raw, properties[name]?.properties.orEmpty(), "${fieldName(name)}.")
+    }
+
+    fun getObjectList(name: String): List<ToolArguments> {
+        val raw = valueOf(name) as? JSONArray ?: throw typeError(name, "array")
+        val itemProperties = properties[name]?.items?.properties.orEmpty()
+        return List(raw.length()) { i ->
+            val item = raw.opt(i) as? JSONObject
+                ?: throw typeError("$name[$i]", "object")
+            ToolArguments(item, itemProperties, "${fieldName(name)}[$i].")
+        }
+    }
+
+    fun optString(name: String): String? = if (hasValue(name)) getString(name) else null
+
+    fun optInt(name: String): Int? = if (hasValue(name)) getInt(name) else null
+
+    fun optBoolean(name: String): Boolean? = if (hasValue(name)) getBoolean(name) else null
+
+    private fun hasValue(name: String): Boolean = has(name) || properties[name]?.default != null
+
+    private fun valueOf(name: String): Any {
+        if (has(name)) return json.get(name)
+        properties[name]?.default?.toPlainValue()?.let { return it }
+        throw ToolArgumentException(
+            field = fieldName(name),
+            message = "Missing required argument '${fieldName(name)}'",
+            expected = properties[name]?.type
+        )
+    }
+
+    private fun typeError(name: String, expected: String): ToolArgumentException {
+        val actual = json.opt(name)
+        return ToolArgumentException(
+            field = fieldName(name),
+            message = "'${fieldName(name)}' must be of type $expected, got ${JSONObject.wrap(actual)}",
+            expected = expected
+        )
+    }
+
+    private fun fieldName(name: String) = "$path$name"
+}
+
+class ToolRegistry(tools: List<AgentTool>) {
+    private val toolsByName = tools.associateBy { it.name }
+
+    val names: Set<String>
+        get() = toolsByName.keys
+
+    operator fun get(name: String): AgentTool? = toolsByName[name]
+
+    fun definitions(): List<ToolDefinition> = toolsByName.values.map { it.toDefinition() }
+}
+
+/**
+ * Formats a tool failure as JSON so the model can tell what went wrong and retry with a fix.
+ */
+fun toolError(
+    tool: String,
+    type: String,
+    message: String,
+    field: String? = null,
+    expected: String? = null
+): String {
+    val error = JSONObject()
+        .put("type", type)
+        .put("tool", tool)
+        .put("message", message)
+    field?.let { error.put("field", it) }
+    expected?.let { error.put("expected", it) }
+    return JSONObject().put("error", error).toString()
+}
+
+fun invalidArgumentsError(tool: String, problems: List<ArgumentProblem>): String {
+    val details = JSONArray()
+    problems.forEach { problem ->
+        details.put(
+            JSONObject()
+                .put("field", problem.field)
+                .put("message", problem.message)
+                .apply { problem.expected?.let { put("expected", it) } }
+        )
+    }
+
+    val error = JSONObject()
+        .put("type", "invalid_arguments")
+        .put("tool", tool)
+        .put("message", "Fix the listed arguments and call $tool again")
+        .put("problems", details)
+    return JSONObject().put("error", error).toString()
+}
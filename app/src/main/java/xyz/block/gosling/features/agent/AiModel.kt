@@ -1,32 +1,56 @@
 package xyz.block.gosling.features.agent
 
-enum class ModelProvider {
-    OPENAI,
-    GEMINI
+enum class ModelProvider(val displayName: String, val defaultBaseUrl: String) {
+    OPENAI("OpenAI (or compatible)", "https://api.openai.com/v1"),
+    GEMINI("Gemini", "https://generativelanguage.googleapis.com/v1beta"),
+    ANTHROPIC("Anthropic", "https://api.anthropic.com/v1")
 }
 
 data class AiModel(
     val displayName: String,
     val identifier: String,
-    val provider: ModelProvider
+    val provider: ModelProvider,
+    // Context window in tokens
+    val contextWindow: Int = DEFAULT_CONTEXT_WINDOW
 ) {
     companion object {
+        // Conservative default for custom models, which are often small local ones
+        const val DEFAULT_CONTEXT_WINDOW = 32_000
+
         val AVAILABLE_MODELS = listOf(
-            AiModel("GPT-4.1", "gpt-4.1", ModelProvider.OPENAI),
-            AiModel("GPT-4o", "gpt-4o", ModelProvider.OPENAI),
-            AiModel("GPT-4o mini", "gpt-4o-mini", ModelProvider.OPENAI),
-            AiModel("O3 Mini", "o3-mini", ModelProvider.OPENAI),
-            AiModel("O3 Small", "o3-small", ModelProvider.OPENAI),
-            AiModel("O3 Medium", "o3-medium", ModelProvider.OPENAI),
-            AiModel("O3 Large", "o3-large", ModelProvider.OPENAI),
+            AiModel("GPT-4.1", "gpt-4.1", ModelProvider.OPENAI, 1_047_576),
+            AiModel("GPT-4o", "gpt-4o", ModelProvider.OPENAI, 128_000),
+            AiModel("GPT-4o mini", "gpt-4o-mini", ModelProvider.OPENAI, 128_000),
+            AiModel("O3 Mini", "o3-mini", ModelProvider.OPENAI, 200_000),
+            AiModel("O3 Small", "o3-small", ModelProvider.OPENAI, 200_000),
+            AiModel("O3 Medium", "o3-medium", ModelProvider.OPENAI, 200_000),
+            AiModel("O3 Large", "o3-large", ModelProvider.OPENAI, 200_000),
+
+            AiModel("Gemini Flash", "gemini-2.0-flash", ModelProvider.GEMINI, 1_048_576),
+            AiModel("Gemini Flash light", "gemini-2.0-flash-lite", ModelProvider.GEMINI, 1_048_576),
 
-            AiModel("Gemini Flash", "gemini-2.0-flash", ModelProvider.GEMINI),
-            AiModel("Gemini Flash light", "gemini-2.0-flash-lite", ModelProvider.GEMINI)
+            AiModel("Claude Sonnet 4", "claude-sonnet-4-20250514", ModelProvider.ANTHROPIC, 200_000),
+            AiModel("Claude 3.7 Sonnet", "claude-3-7-sonnet-latest", ModelProvider.ANTHROPIC, 200_000),
+            AiModel("Claude 3.5 Haiku", "claude-3-5-haiku-latest", ModelProvider.ANTHROPIC, 200_000)
         )
 
-        fun fromIdentifier(identifier: String): AiModel {
-            return AVAILABLE_MODELS.find { it.identifier == identifier }
-                ?: AVAILABLE_MODELS.first()
+        fun isKnown(identifier: String): Boolean {
+            return AVAILABLE_MODELS.any { it.identifier == identifier }
+        }
+
+        /**
+         * Looks up a built-in model, or describes a custom one (for example a model served by
+         * Ollama or a corporate gateway) using [provider] to decide which API it speaks.
+         */
+        fun fromIdentifier(
+            identifier: String,
+            provider: ModelProvider = ModelProvider.OPENAI
+        ): AiModel {
+            AVAILABLE_MODELS.find { it.identifier == identifier }?.let { return it }
+            if (identifier.isBlank()) {
+                return AVAILABLE_MODELS.first()
+            }
+            return AiModel(identifier, identifier, provider)
         }
     }
 }
@@ -0,0 +1,220 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.encodeToString
+import okhttp3.MediaType.Companion.toMediaType
+import okhttp3.Request
+import okhttp3.RequestBody.Companion.toRequestBody
+import org.json.JSONObject
+
+class AnthropicProvider(private val config: ProviderConfig = ProviderConfig()) : LlmProvider {
+    companion object {
+        private const val API_VERSION = "2023-06-01"
+        private const val MAX_TOKENS = 4096
+    }
+
+    override val supportsStreaming: Boolean
+        get() = true
+
+    override fun buildRequest(
+        model: AiModel,
+        apiKey: String,
+        messages: List<Message>,
+        tools: List<ToolDefinition>,
+        stream: Boolean
+    ): Request {
+        val systemPrompt = messages
+            .filter { it.role == "system" }
+            .flatMap { it.content.orEmpty() }
+            .filterIsInstance<Content.Text>()
+            .joinToString("\n") { it.text }
+
+        val anthropicRequest = AnthropicRequest(
+            model = model.identifier,
+            maxTokens = MAX_TOKENS,
+            system = systemPrompt.ifBlank { null },
+            messages = toAnthropicMessages(messages),
+            tools = tools.map { toolDef ->
+                AnthropicTool(
+                    name = toolDef.function.name,
+                    description = toolDef.function.description,
+                    inputSchema = toolDef.function.parameters
+                )
+            },
+            stream = if (stream) true else null
+        )
+
+        val requestBody = LlmProvider.jsonFormat.encodeToString(anthropicRequest)
+
+        return Request.Builder()
+            .url("${config.resolveBaseUrl(ModelProvider.ANTHROPIC)}/messages")
+            .post(requestBody.toRequestBody("application/json".toMediaType()))
+            .apply {
+                if (apiKey.isNotBlank()) {
+                    addHeader("x-api-key", apiKey)
+                }
+            }
+            .addHeader("anthropic-version", API_VERSION)
+            .addHeaders(config.extraHeaders)
+            .build()
+    }
+
+    override fun parseResponse(body: String): AssistantTurn {
+        val response = JSONObject(body)
+        val content = response.optJSONArray("content")
+            ?: return AssistantTurn("Unknown response format")
+
+        val textParts = mutableListOf<String>()
+        val toolCalls = mutableListOf<InternalToolCall>()
+
+        for (i in 0 until content.length()) {
+            val block = content.getJSONObject(i)
+            when (block.optString("type")) {
+                "text" -> textParts.add(block.optString("text"))
+                "tool_use" -> toolCalls.add(
+                    InternalToolCall(
+                        toolId = block.getString("id"),
+                        name = block.getString("name"),
+                        arguments = block.optJSONObject("input") ?: JSONObject()
+                    )
+                )
+            }
+        }
+
+        val usage = response.optJSONObject("usage")?.let {
+            TokenUsage(
+                inputTokens = it.optInt("input_tokens"),
+                outputTokens = it.optInt("output_tokens")
+            )
+        }
+
+        return AssistantTurn(
+            text = textParts.joinToString("\n").ifEmpty { "Ok" },
+            toolCalls = toolCalls.ifEmpty { null },
+            usage = usage
+        )
+    }
+
+    override fun newStreamDecoder(): LlmStreamDecoder = StreamDecoder()
+
+    /**
+     * Messages streams open content blocks by index, then send `text_delta` or
+     * `input_json_delta` events for them. Input tokens arrive in `message_start` and output
+     * tokens in the final `message_delta`.
+     */
+    private class StreamDecoder : LlmStreamDecoder {
+        private val text = StringBuilder()
+        private val toolCalls = sortedMapOf<Int, PartialToolCall>()
+        private var inputTokens = 0
+        private var outputTokens = 0
+
+        override fun onData(data: String): String? {
+            val event = JSONObject(data)
+            when (event.optString("type")) {
+                "message_start" -> event.optJSONObject("message")?.optJSONObject("usage")?.let {
+                    inputTokens = it.optInt("input_tokens")
+                }
+
+                "content_block_start" -> {
+                    val block = event.getJSONObject("content_block")
+                    if (block.optString("type") == "tool_use") {
+                        toolCalls[event.getInt("index")] = PartialToolCall(
+                            id = block.getString("id"),
+                            name = block.getString("name")
+                        )
+                    }
+                }
+
+                "content_block_delta" -> {
+                    val delta = event.getJSONObject("delta")
+                    when (delta.optString("type")) {
+                        "text_delta" -> {
+                            val deltaText = delta.optString("text")
+                            text.append(deltaText)
+                            return deltaText
+                        }
+
+                        "input_json_delta" -> toolCalls[event.getInt("index")]
+                            ?.arguments?.append(delta.optString("partial_json"))
+                    }
+                }
+
+                "message_delta" -> event.optJSONObject("usage")?.let {
+                    outputTokens = it.optInt("output_tokens")
+                }
+
+                "error" -> throw AgentException(
+                    event.optJSONObject("error")?.optString("message") ?: data
+                )
+            }
+            return null
+        }
+
+        override fun finish(): AssistantTurn {
+            return AssistantTurn(
+                text = text.toString().ifEmpty { "Ok" },
+                toolCalls = toolCalls.values.map { it.toInternalToolCall() }.ifEmpty { null },
+                usage = TokenUsage(inputTokens, outputTokens)
+            )
+        }
+    }
+
+    /**
+     * The Messages API takes the system prompt separately, expects tool results as blocks in a
+     * user turn and requires user and assistant turns to alternate, so consecutive messages
+     * that map to the same role are merged.
+     */
+    private fun toAnthropicMessages(messages: List<Message>): List<AnthropicMessage> {
+        val result = mutableListOf<AnthropicMessage>()
+
+        for (message in messages) {
+            val (role, blocks) = when (message.role) {
+                "user" -> "user" to contentBlocks(message.content)
+                "assistant" -> "assistant" to contentBlocks(message.content) +
+                        message.toolCalls.orEmpty().map { toolCall ->
+                            AnthropicContentBlock.ToolUse(
+                                id = toolCall.id,
+                                name = toolCall.function.name,
+                                input = parseToolArguments(toolCall.function.arguments)
+                            )
+                        }
+
+                "tool" -> "user" to listOf(
+                    AnthropicContentBlock.ToolResult(
+                        toolUseId = message.toolCallId ?: "",
+                        content = contentBlocks(message.content)
+                            .ifEmpty { listOf(AnthropicContentBlock.Text("<empty>")) }
+                    )
+                )
+
+                else -> continue
+            }
+
+            if (blocks.isEmpty()) continue
+
+            val previous = result.lastOrNull()
+            if (previous != null && previous.role == role) {
+                result[result.lastIndex] = previous.copy(content = previous.content + blocks)
+            } else {
+                result.add(AnthropicMessage(role = role, content = blocks))
+            }
+        }
+
+        return result
+    }
+
+    private fun contentBlocks(content: List<Content>?): List<AnthropicContentBlock> {
+        return content.orEmpty().mapNotNull { item ->
+            when (item) {
+                is Content.Text -> item.text.takeIf { it.isNotBlank() }
+                    ?.let { AnthropicContentBlock.Text(it) }
+
+                is Content.ImageUrl -> AnthropicContentBlock.Image(imageSource(item.imageUrl.url))
+            }
+        }
+    }
+
+    private fun imageSource(url: String): AnthropicImageSource {
+        val (mediaType, data) = parseDataUrl(url) ?: return AnthropicImageSource.Url(url)
+        return AnthropicImageSource.Base64(mediaType = mediaType, data = data)
+    }
+}
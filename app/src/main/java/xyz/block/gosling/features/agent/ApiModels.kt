@@ -65,7 +65,9 @@ data class Conversation(
     val startTime: Long = System.currentTimeMillis(),
     val endTime: Long? = null,
     val messages: List<Message> = emptyList(),
-    val isComplete: Boolean = false
+    val isComplete: Boolean = false,
+    // Records of processing applied to the conversation, such as context compaction
+    val annotations: List<JsonElement> = emptyList()
 )
 
 @Serializable
@@ -84,7 +86,12 @@ data class ToolFunction(
 @Serializable
 data class ToolParameter(
     val type: String,
-    val description: String
+    val description: String? = null,
+    val enum: List<String>? = null,
+    val items: ToolParameter? = null,
+    val properties: Map<String, ToolParameter>? = null,
+    val required: List<String>? = null,
+    val default: JsonElement? = null
 )
 
 @Serializable
@@ -113,7 +120,16 @@ data class OpenAIRequest(
     val model: String,
     val messages: List<Message>,
     val temperature: Double? = 0.1,
-    val tools: List<ToolDefinition>? = null
+    val tools: List<ToolDefinition>? = null,
+    val stream: Boolean? = null,
+    @SerialName("stream_options")
+    val streamOptions: OpenAIStreamOptions? = null
+)
+
+@Serializable
+data class OpenAIStreamOptions(
+    @SerialName("include_usage")
+    val includeUsage: Boolean = true
 )
 
 @Serializable
@@ -129,13 +145,14 @@ data class OpenAIChoice(
 // Gemini specific models
 @Serializable
 data class GeminiRequest(
-    val contents: GeminiContent,
+    val contents: List<GeminiContent>,
+    val systemInstruction: GeminiContent? = null,
     val tools: List<GeminiTool>? = null
 )
 
 @Serializable
 data class GeminiContent(
-    val role: String = "user",
+    val role: String? = "user",
     val parts: List<GeminiPart>
 )
 
@@ -143,7 +160,11 @@ data class GeminiContent(
 data class GeminiPart(
     val text: String? = null,
     @SerialName("functionCall")
-    val functionCall: GeminiFunctionCall? = null
+    val functionCall: GeminiFunctionCall? = null,
+    @SerialName("functionResponse")
+    val functionResponse: GeminiFunctionResponse? = null,
+    @SerialName("inlineData")
+    val inlineData: GeminiInlineData? = null
 )
 
 @Serializable
@@ -152,6 +173,18 @@ data class GeminiFunctionCall(
     val args: JsonObject
 )
 
+@Serializable
+data class GeminiFunctionResponse(
+    val name: String,
+    val response: JsonObject
+)
+
+@Serializable
+data class GeminiInlineData(
+    val mimeType: String,
+    val data: String
+)
+
 @Serializable
 data class GeminiTool(
     @SerialName("function_declarations")
@@ -175,6 +208,75 @@ data class GeminiCandidate(
     val content: GeminiContent
 )
 
+// Anthropic specific models
+@Serializable
+data class AnthropicRequest(
+    val model: String,
+    @SerialName("max_tokens")
+    val maxTokens: Int,
+    val system: String? = null,
+    val messages: List<AnthropicMessage>,
+    val temperature: Double? = 0.1,
+    val tools: List<AnthropicTool>? = null,
+    val stream: Boolean? = null
+)
+
+@Serializable
+data class AnthropicMessage(
+    val role: String,
+    val content: List<AnthropicContentBlock>
+)
+
+@Serializable
+sealed class AnthropicContentBlock {
+    @Serializable
+    @SerialName("text")
+    data class Text(val text: String) : AnthropicContentBlock()
+
+    @Serializable
+    @SerialName("image")
+    data class Image(val source: AnthropicImageSource) : AnthropicContentBlock()
+
+    @Serializable
+    @SerialName("tool_use")
+    data class ToolUse(
+        val id: String,
+        val name: String,
+        val input: JsonObject
+    ) : AnthropicContentBlock()
+
+    @Serializable
+    @SerialName("tool_result")
+    data class ToolResult(
+        @SerialName("tool_use_id")
+        val toolUseId: String,
+        val content: List<AnthropicContentBlock>
+    ) : AnthropicContentBlock()
+}
+
+@Serializable
+sealed class AnthropicImageSource {
+    @Serializable
+    @SerialName("base64")
+    data class Base64(
+        @SerialName("media_type")
+        val mediaType: String,
+        val data: String
+    ) : AnthropicImageSource()
+
+    @Serializable
+    @SerialName("url")
+    data class Url(val url: String) : AnthropicImageSource()
+}
+
+@Serializable
+data class AnthropicTool(
+    val name: String,
+    val description: String,
+    @SerialName("input_schema")
+    val inputSchema: ToolParametersObject
+)
+
 // UI Hierarchy models
 @Serializable
 data class NodeBounds(
@@ -0,0 +1,254 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.json.JsonObject
+import kotlinx.serialization.json.JsonPrimitive
+import kotlinx.serialization.json.contentOrNull
+import kotlinx.serialization.json.jsonPrimitive
+
+/**
+ * What [ContextCompactor.compact] did to a conversation before it was sent.
+ */
+data class CompactionResult(
+    val messages: List<Message>,
+    val summarizedMessages: Int,
+    val droppedToolOutputs: Int,
+    val estimatedTokensBefore: Int,
+    val estimatedTokensAfter: Int
+) {
+    val didCompact: Boolean
+        get() = summarizedMessages > 0 || droppedToolOutputs > 0
+
+    /**
+     * What this compaction did beyond earlier ones that together summarized [summarized]
+     * messages and dropped [dropped] tool outputs. Every call compacts the whole conversation
+     * again, so its own counts include theirs.
+     */
+    fun since(summarized: Int, dropped: Int): CompactionResult = copy(
+        summarizedMessages = (summarizedMessages - summarized).coerceAtLeast(0),
+        droppedToolOutputs = (droppedToolOutputs - dropped).coerceAtLeast(0)
+    )
+
+    fun toAnnotation(): JsonObject {
+        return JsonObject(
+            mapOf(
+                "type" to JsonPrimitive("compaction"),
+                "time" to JsonPrimitive(System.currentTimeMillis()),
+                "summarized_messages" to JsonPrimitive(summarizedMessages),
+                "dropped_tool_outputs" to JsonPrimitive(droppedToolOutputs),
+                "estimated_tokens_before" to JsonPrimitive(estimatedTokensBefore),
+                "estimated_tokens_after" to JsonPrimitive(estimatedTokensAfter)
+            )
+        )
+    }
+}
+
+/**
+ * Keeps the conversation sent to the model inside its context window. The stored conversation
+ * is never modified; compaction only shapes the copy that goes over the wire.
+ *
+ * In order, it:
+ * 1. truncates all but the latest look at the screen, whether a UI hierarchy, the shade or a
+ *    screenshot, and all but the latest web page read,
+ * 2. replaces tool outputs older than [maxToolOutputAge] assistant turns with a placeholder,
+ * 3. if still over budget, folds the oldest turns after the task request into one summary
+ *    message, never separating a tool call from its result.
+ */
+class ContextCompactor(
+    model: AiModel,
+    private val maxToolOutputAge: Int
+) {
+    companion object {
+        // Share of the context window the conversation may use; the rest is left for tool
+        // definitions and the reply
+        private const val BUDGET_FRACTION = 0.7
+
+        // Share of the budget kept verbatim at the end of the conversation when summarising
+        private const val RECENT_FRACTION = 0.5
+
+        private const val CHARS_PER_TOKEN = 4
+        private const val TOKENS_PER_IMAGE = 1000
+        private const val SUMMARY_SNIPPET_LENGTH = 200
+
+        // Tools whose output describes the screen, and those whose output is a web page
+        private val SCREEN_READING_TOOLS = setOf("getUiHierarchy", "getUiChanges")
+        private val WEB_READING_TOOLS = setOf("webSearch", "openUrl", "scrollBrowse")
+
+        // globalAction returns what is in the shade after opening it
+        private val SHADE_ACTIONS = setOf("notifications", "quick_settings")
+
+        fun estimateTokens(message: Message): Int {
+            val contentTokens = message.content.orEmpty().sumOf { content ->
+                when (content) {
+                    is Content.Text -> content.text.length / CHARS_PER_TOKEN
+                    is Content.ImageUrl -> TOKENS_PER_IMAGE
+                }
+            }
+            val toolCallTokens = message.toolCalls.orEmpty().sumOf { toolCall ->
+                (toolCall.function.name.length + toolCall.function.arguments.length) / CHARS_PER_TOKEN
+            }
+            return contentTokens + toolCallTokens + 4
+        }
+
+        fun estimateTokens(messages: List<Message>): Int = messages.sumOf { estimateTokens(it) }
+    }
+
+    private val budget = (model.contextWindow * BUDGET_FRACTION).toInt()
+
+    fun compact(messages: List<Message>): CompactionResult {
+        val tokensBefore = estimateTokens(messages)
+
+        val trimmed = removeOutdatedPayloads(messages)
+        val (aged, droppedToolOutputs) = dropStaleToolOutputs(trimmed)
+        val (result, summarizedMessages) = if (estimateTokens(aged) > budget) {
+            summarizeOldTurns(aged)
+        } else {
+            aged to 0
+        }
+
+        return CompactionResult(
+            messages = result,
+            summarizedMessages = summarizedMessages,
+            droppedToolOutputs = droppedToolOutputs,
+            estimatedTokensBefore = tokensBefore,
+            estimatedTokensAfter = estimateTokens(result)
+        )
+    }
+
+    private fun removeOutdatedPayloads(messages: List<Message>): List<Message> {
+        val shadeCallIds = messages
+            .flatMap { it.toolCalls.orEmpty() }
+            .filter { toolCall ->
+                toolCall.function.name == "globalAction" &&
+                        parseToolArguments(toolCall.function.arguments)["action"]
+                            ?.jsonPrimitive?.contentOrNull in SHADE_ACTIONS
+            }
+            .map { it.id }
+            .toSet()
+
+        val readsScreen = { message: Message ->
+            message.role == "tool" && (message.name in SCREEN_READING_TOOLS ||
+                    message.toolCallId in shadeCallIds)
+        }
+
+        val readsWeb = { message: Message ->
+            message.role == "tool" && message.name in WEB_READING_TOOLS
+        }
+
+        val hasImage = { message: Message ->
+            message.role == "user" && message.content?.any { it is Content.ImageUrl } == true
+        }
+
+        // Only the latest look at the screen is worth sending, hierarchy or screenshot
+        val lastScreenReadIndex = messages.indexOfLast(readsScreen)
+        val lastScreenIndex = maxOf(lastScreenReadIndex, messages.indexOfLast(hasImage))
+        val lastWebReadIndex = messages.indexOfLast(readsWeb)
+
+        return messages.mapIndexed { index, message ->
+            when {
+                readsScreen(message) && index < lastScreenReadIndex ||
+                        readsWeb(message) && index < lastWebReadIndex ->
+                    message.copy(
+                        content = contentWithText("{outdated ${message.name ?: "tool"} output truncated}")
+                    )
+
+                hasImage(message) && index < lastScreenIndex ->
+                    message.copy(content = message.content?.filterNot { it is Content.ImageUrl })
+
+                else -> message
+            }
+        }
+    }
+
+    private fun dropStaleToolOutputs(messages: List<Message>): Pair<List<Message>, Int> {
+        var dropped = 0
+        var assistantTurnsAfter = 0
+        val result = messages.asReversed().map { message ->
+            when {
+                message.role == "assistant" -> {
+                    assistantTurnsAfter++
+                    message
+                }
+
+                message.role == "tool" && assistantTurnsAfter > maxToolOutputAge -> {
+                    dropped++
+                    message.copy(
+                        content = contentWithText(
+                            "{output of ${message.name ?: "tool"} dropped, $assistantTurnsAfter turns old}"
+                        )
+                    )
+                }
+
+                else -> message
+            }
+        }.asReversed()
+        return result to dropped
+    }
+
+    private fun summarizeOldTurns(messages: List<Message>): Pair<List<Message>, Int> {
+        // Always keep the system prompt and the request that started the task
+        val firstUserIndex = messages.indexOfFirst { it.role == "user" }
+        if (firstUserIndex < 0) return messages to 0
+        val headEnd = firstUserIndex + 1
+
+        // Keep as many recent messages as fit in the recent share of the budget
+        val recentBudget = (budget * RECENT_FRACTION).toInt()
+        var tailStart = messages.size
+        var tailTokens = 0
+        while (tailStart > headEnd) {
+            val tokens = estimateTokens(messages[tailStart - 1])
+            if (tailTokens + tokens > recentBudget && tailStart < messages.size) break
+            tailTokens += tokens
+            tailStart--
+        }
+
+        // Tool results must follow the assistant message that requested them
+        while (tailStart > headEnd && messages[tailStart].role == "tool") {
+            tailStart--
+        }
+
+        val summarized = messages.subList(headEnd, tailStart)
+        if (summarized.isEmpty()) return messages to 0
+
+        val summary = Message(
+            role = "user",
+            content = contentWithText(buildSummary(summarized))
+        )
+        return messages.subList(0, headEnd) + summary + messages.subList(tailStart, messages.size) to
+                summarized.size
+    }
+
+    private fun buildSummary(messages: List<Message>): String {
+        val lines = mutableListOf(
+            "[Earlier steps of this task were compacted to save space. Summary of what happened:]"
+        )
+        for (message in messages) {
+            when (message.role) {
+                "user" -> lines.add("- User: ${snippet(message)}")
+                "assistant" -> {
+                    val text = snippet(message)
+                    if (text.isNotBlank() && text != "<empty>") {
+                        lines.add("- Assistant: $text")
+                    }
+                    message.toolCalls.orEmpty().forEach { toolCall ->
+                        lines.add(
+                            "- Called ${toolCall.function.name}" +
+                                    "(${toolCall.function.arguments.take(SUMMARY_SNIPPET_LENGTH)})"
+                        )
+                    }
+                }
+
+                "tool" -> lines.add("  -> ${snippet(message).lineSequence().first()}")
+            }
+        }
+        return lines.joinToString("\n")
+    }
+
+    private fun snippet(message: Message): String {
+        val text = firstText(message)
+        return if (text.length > SUMMARY_SNIPPET_LENGTH) {
+            text.take(SUMMARY_SNIPPET_LENGTH) + "..."
+        } else {
+            text
+        }
+    }
+}
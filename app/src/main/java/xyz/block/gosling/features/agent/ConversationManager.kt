@@ -23,6 +23,9 @@ class ConversationManager(context: Context) {
     private val _currentConversation = MutableStateFlow<Conversation?>(null)
     val currentConversation: StateFlow<Conversation?> = _currentConversation.asStateFlow()
 
+    // Text of the assistant reply currently streaming in, not yet part of the conversation
+    private val _streamingReply = MutableStateFlow<String?>(null)
+    val streamingReply: StateFlow<String?> = _streamingReply.asStateFlow()
 
     private val json = Json {
         prettyPrint = true
@@ -71,6 +74,10 @@ class ConversationManager(context: Context) {
         }
     }
 
+    fun setStreamingReply(text: String?) {
+        _streamingReply.value = text
+    }
+
     fun updateCurrentConversation(conversation: Conversation) {
         // Update the current conversation state
         _currentConversation.update { conversation }
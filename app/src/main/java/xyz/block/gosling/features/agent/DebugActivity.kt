@@ -10,9 +10,8 @@ import kotlinx.coroutines.flow.firstOrNull
 import kotlinx.coroutines.launch
 import kotlinx.serialization.encodeToString
 import kotlinx.serialization.json.Json
-import org.json.JSONObject
 import xyz.block.gosling.features.accessibility.GoslingAccessibilityService
-import xyz.block.gosling.features.agent.ToolHandler.getUiHierarchy
+import xyz.block.gosling.features.agent.ToolHandler.describeUiHierarchy
 import java.io.File
 import java.text.SimpleDateFormat
 import java.util.Date
@@ -30,7 +29,7 @@ class DebugActivity : AppCompatActivity() {
             "xyz.block.gosling.GET_UI_HIERARCHY" -> {
                 val service = GoslingAccessibilityService.getInstance()
                 if (service != null) {
-                    val hierarchyText = getUiHierarchy(service, JSONObject())
+                    val hierarchyText = describeUiHierarchy(service)
                     Log.d("UiHierarchy", "CAPTURED:\n$hierarchyText")
                 } else {
                     Log.e("UiHierarchy", "Service not running")
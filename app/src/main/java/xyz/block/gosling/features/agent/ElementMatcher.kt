@@ -0,0 +1,192 @@
+package xyz.block.gosling.features.agent
+
+import android.graphics.Rect
+import android.view.accessibility.AccessibilityNodeInfo
+
+/**
+ * A field that matched a description, with the score of its best matching label and where that
+ * label came from (hint, id, a label next to it, ...).
+ */
+data class ElementMatch(
+    val node: AccessibilityNodeInfo,
+    val score: Double,
+    val matchedBy: String,
+    val label: String
+)
+
+sealed class MatchResult {
+    data class Found(val match: ElementMatch) : MatchResult()
+
+    /**
+     * Several fields scored about the same; the model has to say which one it meant.
+     */
+    data class Ambiguous(val candidates: List<ElementMatch>) : MatchResult()
+
+    object NotFound : MatchResult()
+}
+
+/**
+ * Finds the text field a description like "Email" or "Last name" refers to. Every editable
+ * field is scored against everything that can name it: its hint, content description,
+ * labelFor relationship, resource id and the label drawn above or to the left of it. Exact
+ * matches beat prefixes, which beat words somewhere in a longer label, so "Name" picks the
+ * "Name" field over "Company name".
+ */
+object ElementMatcher {
+    private const val MIN_SCORE = 0.3
+    private const val AMBIGUITY_MARGIN = 0.08
+    private const val MAX_CANDIDATES = 5
+
+    // How much a match counts depending on where the label came from
+    private const val WEIGHT_LABELED_BY = 1.0
+    private const val WEIGHT_HINT = 1.0
+    private const val WEIGHT_DESCRIPTION = 0.95
+    private const val WEIGHT_NEARBY_LABEL = 0.9
+    private const val WEIGHT_RESOURCE_ID = 0.8
+    private const val WEIGHT_TEXT = 0.6
+
+    private class Label(val text: String, val bounds: Rect)
+
+    fun findTextField(root: AccessibilityNodeInfo, description: String): MatchResult {
+        val query = normalize(description)
+        if (query.isEmpty()) return MatchResult.NotFound
+
+        val fields = mutableListOf<AccessibilityNodeInfo>()
+        val labels = mutableListOf<Label>()
+        collect(root, fields, labels)
+
+        val matches = fields
+            .mapNotNull { field -> score(field, query, labels) }
+            .filter { it.score >= MIN_SCORE }
+            .sortedByDescending { it.score }
+        if (matches.isEmpty()) return MatchResult.NotFound
+
+        val best = matches.first()
+        val close = matches.filter { best.score - it.score < AMBIGUITY_MARGIN }
+        return if (close.size == 1) {
+            MatchResult.Found(best)
+        } else {
+            MatchResult.Ambiguous(close.take(MAX_CANDIDATES))
+        }
+    }
+
+    private fun collect(
+        node: AccessibilityNodeInfo,
+        fields: MutableList<AccessibilityNodeInfo>,
+        labels: MutableList<Label>
+    ) {
+        if (node.isEditable) {
+            fields.add(node)
+        } else if (node.isVisibleToUser) {
+            val text = node.text?.toString()?.takeIf { it.isNotBlank() }
+                ?: node.contentDescription?.toString()?.takeIf { it.isNotBlank() }
+            if (text != null) {
+                labels.add(Label(text, Rect().also { node.getBoundsInScreen(it) }))
+            }
+        }
+
+        for (i in 0 until node.childCount) {
+            val child = node.getChild(i) ?: continue
+            collect(child, fields, labels)
+        }
+    }
+
+    private fun score(
+        field: AccessibilityNodeInfo,
+        query: String,
+        labels: List<Label>
+    ): ElementMatch? {
+        val bounds = Rect().also { field.getBoundsInScreen(it) }
+        val sources = mutableListOf<Triple<String, String, Double>>()
+
+        field.labeledBy?.text?.toString()?.let { sources.add(Triple("label", it, WEIGHT_LABELED_BY)) }
+        field.hintText?.toString()?.let { sources.add(Triple("hint", it, WEIGHT_HINT)) }
+        field.contentDescription?.toString()
+            ?.let { sources.add(Triple("description", it, WEIGHT_DESCRIPTION)) }
+        field.viewIdResourceName?.let { sources.add(Triple("id", idWords(it), WEIGHT_RESOURCE_ID)) }
+        field.text?.toString()?.let {
+            // Older apps report the hint as the text of an empty field
+            val weight = if (field.isShowingHintText) WEIGHT_HINT else WEIGHT_TEXT
+            sources.add(Triple(if (field.isShowingHintText) "hint" else "text", it, weight))
+        }
+        nearbyLabel(bounds, labels)?.let { (label, proximity) ->
+            sources.add(Triple("nearby label", label, WEIGHT_NEARBY_LABEL * proximity))
+        }
+
+        val best = sources
+            .map { (source, text, weight) -> Triple(source, text, weight * similarity(query, normalize(text))) }
+            .maxByOrNull { it.third }
+            ?: return null
+
+        var score = best.third
+        // Fields the user can't see are rarely the ones meant
+        if (!field.isVisibleToUser || bounds.isEmpty) score *= 0.5
+        if (!field.isEnabled) score *= 0.7
+
+        return ElementMatch(field, score, best.first, best.second)
+    }
+
+    /**
+     * The closest label above the field or to its left in the same row, with a proximity
+     * factor that drops from 1 to 0.5 as the gap grows to three field heights.
+     */
+    private fun nearbyLabel(field: Rect, labels: List<Label>): Pair<String, Double>? {
+        if (field.isEmpty) return null
+        val maxGap = maxOf(field.height() * 3, 1)
+
+        return labels.mapNotNull { label ->
+            val overlapsColumns = label.bounds.right > field.left && label.bounds.left < field.right
+            val overlapsRows = label.bounds.bottom > field.top && label.bounds.top < field.bottom
+            val gap = when {
+                overlapsColumns && label.bounds.bottom <= field.top + field.height() / 2 ->
+                    field.top - label.bounds.bottom
+
+                overlapsRows && label.bounds.right <= field.left + field.width() / 4 ->
+                    field.left - label.bounds.right
+
+                else -> return@mapNotNull null
+            }.coerceAtLeast(0)
+            if (gap > maxGap) null else label to gap
+        }
+            .minByOrNull { it.second }
+            ?.let { (label, gap) -> label.text to 1.0 - gap.toDouble() / maxGap * 0.5 }
+    }
+
+    /**
+     * How well [query] matches [text], both normalized: 1 for an exact match, less for a prefix,
+     * a whole word, all words in any order or a plain substring, scaled down by how much of the
+     * label the query covers.
+     */
+    internal fun similarity(query: String, text: String): Double {
+        if (text.isEmpty()) return 0.0
+        if (text == query) return 1.0
+
+        val queryWords = query.split(' ')
+        val textWords = text.split(' ')
+        val base = when {
+            text.startsWith("$query ") -> 0.75
+            textWords.windowed(queryWords.size).any { it == queryWords } -> 0.6
+            queryWords.all { it in textWords } -> 0.5
+            query in text -> 0.4
+            text in query && text.length >= 3 -> 0.35
+            else -> return 0.0
+        }
+        val coverage = minOf(query.length, text.length).toDouble() / maxOf(query.length, text.length)
+        return base * (0.7 + 0.3 * coverage)
+    }
+
+    internal fun normalize(text: String): String {
+        return text.lowercase()
+            .replace(Regex("[^\\p{L}\\p{N}]+"), " ")
+            .trim()
+    }
+
+    /**
+     * Turns "com.example:id/first_name_input" or "firstNameInput" into "first name input".
+     */
+    internal fun idWords(resourceId: String): String {
+        return resourceId.substringAfter(":id/")
+            .replace(Regex("([a-z])([A-Z])"), "$1 $2")
+            .replace('_', ' ')
+    }
+}
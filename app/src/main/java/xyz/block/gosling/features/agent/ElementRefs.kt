@@ -0,0 +1,91 @@
+package xyz.block.gosling.features.agent
+
+import android.view.accessibility.AccessibilityNodeInfo
+
+/**
+ * Short references ("e12") for the nodes printed by the UI hierarchy tools, so the model can
+ * act on an element instead of copying its pixel coordinates.
+ *
+ * Each hierarchy dump starts a new snapshot; only refs from the latest snapshot resolve. A node
+ * keeps its ref across snapshots as long as it stays at the same place in the tree of the same
+ * app, so the model can keep using a ref after the screen re-renders.
+ */
+object ElementRefs {
+    private const val PREFIX = "e"
+
+    private val lock = Any()
+    private var packageName: String? = null
+    private val refsByFingerprint = mutableMapOf<String, String>()
+    private var nodesByRef = mutableMapOf<String, AccessibilityNodeInfo>()
+    private var statesByRef = mutableMapOf<String, UiNode>()
+    private var previousStates: Map<String, UiNode> = emptyMap()
+    private var nextId = 1
+
+    class Snapshot internal constructor(
+        private val nodes: MutableMap<String, AccessibilityNodeInfo>,
+        private val states: MutableMap<String, UiNode>
+    ) {
+        /**
+         * Assigns a ref to [node], found at [path] (child indices from the root) in the tree.
+         */
+        fun register(node: AccessibilityNodeInfo, path: String): String {
+            val fingerprint = "$path|${node.className}|${node.viewIdResourceName.orEmpty()}"
+            val ref = synchronized(lock) {
+                refsByFingerprint.getOrPut(fingerprint) { "$PREFIX${nextId++}" }
+            }
+            nodes[ref] = node
+            if (UiTree.isMeaningful(node)) {
+                states[ref] = UiNode.from(node, ref)
+            }
+            return ref
+        }
+    }
+
+    fun beginSnapshot(root: AccessibilityNodeInfo): Snapshot {
+        synchronized(lock) {
+            val rootPackage = root.packageName?.toString()
+            if (rootPackage != packageName) {
+                packageName = rootPackage
+                refsByFingerprint.clear()
+                statesByRef.clear()
+                nextId = 1
+            }
+            previousStates = statesByRef
+            nodesByRef = mutableMapOf()
+            statesByRef = mutableMapOf()
+            return Snapshot(nodesByRef, statesByRef)
+        }
+    }
+
+    /**
+     * Element states of the snapshot before the latest one, for diffing. Empty when there was
+     * none for the current app.
+     */
+    fun previousStates(): Map<String, UiNode> = synchronized(lock) { previousStates }
+
+    fun currentStates(): Map<String, UiNode> = synchronized(lock) { statesByRef.toMap() }
+
+    fun currentNodes(): Map<String, AccessibilityNodeInfo> = synchronized(lock) { nodesByRef.toMap() }
+
+    /**
+     * The node behind [ref] from the latest snapshot, or a [ToolArgumentException] explaining
+     * why it can't be used.
+     */
+    fun resolve(ref: String): AccessibilityNodeInfo {
+        val normalized = ref.trim().removePrefix("[").removeSuffix("]")
+        val node = synchronized(lock) { nodesByRef[normalized] }
+            ?: throw ToolArgumentException(
+                field = "ref",
+                message = "Unknown element ref '$ref'. Call getUiHierarchy to get current refs."
+            )
+
+        // refresh() fails once the view behind the node has gone away
+        if (!node.refresh()) {
+            throw ToolArgumentException(
+                field = "ref",
+                message = "Element '$ref' is no longer on screen. Call getUiHierarchy again."
+            )
+        }
+        return node
+    }
+}
@@ -0,0 +1,254 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.encodeToString
+import kotlinx.serialization.json.JsonObject
+import kotlinx.serialization.json.JsonPrimitive
+import okhttp3.MediaType.Companion.toMediaType
+import okhttp3.Request
+import okhttp3.RequestBody.Companion.toRequestBody
+import org.json.JSONObject
+import java.net.HttpURLConnection
+import java.util.Locale
+
+class GeminiProvider(private val config: ProviderConfig = ProviderConfig()) : LlmProvider {
+    override val supportsStreaming: Boolean
+        get() = true
+
+    override fun buildRequest(
+        model: AiModel,
+        apiKey: String,
+        messages: List<Message>,
+        tools: List<ToolDefinition>,
+        stream: Boolean
+    ): Request {
+        val systemText = messages
+            .filter { it.role == "system" }
+            .flatMap { it.content.orEmpty() }
+            .filterIsInstance<Content.Text>()
+            .joinToString("\n") { it.text }
+
+        val geminiRequest = GeminiRequest(
+            contents = toGeminiContents(messages),
+            systemInstruction = systemText.takeIf { it.isNotBlank() }?.let {
+                GeminiContent(role = null, parts = listOf(GeminiPart(text = it)))
+            },
+            tools = geminiTools(tools)
+        )
+
+        val requestBody = LlmProvider.jsonFormat.encodeToString(geminiRequest)
+
+        val baseUrl = config.resolveBaseUrl(ModelProvider.GEMINI)
+        val method = if (stream) "streamGenerateContent" else "generateContent"
+        val queryParams = listOfNotNull(
+            if (stream) "alt=sse" else null,
+            if (apiKey.isNotBlank()) "key=$apiKey" else null
+        )
+        val query = if (queryParams.isEmpty()) "" else queryParams.joinToString("&", prefix = "?")
+
+        return Request.Builder()
+            .url("$baseUrl/models/${model.identifier}:$method$query")
+            .post(requestBody.toRequestBody("application/json".toMediaType()))
+            .addHeaders(config.extraHeaders)
+            .build()
+    }
+
+    override fun parseResponse(body: String): AssistantTurn {
+        val response = JSONObject(body)
+        if (!response.has("candidates")) {
+            return AssistantTurn("Unknown response format")
+        }
+
+        val textParts = mutableListOf<String>()
+        val toolCalls = mutableListOf<InternalToolCall>()
+        collectParts(response, textParts, toolCalls)
+
+        return AssistantTurn(
+            text = textParts.joinToString("\n").ifBlank { "Ok" },
+            toolCalls = toolCalls,
+            usage = parseUsage(response)
+        )
+    }
+
+    override fun newStreamDecoder(): LlmStreamDecoder = StreamDecoder()
+
+    /**
+     * Each streamed chunk is a complete response holding only the newest parts. Function calls
+     * always arrive whole, and the usage metadata is cumulative so the last one wins.
+     */
+    private inner class StreamDecoder : LlmStreamDecoder {
+        private val text = StringBuilder()
+        private val toolCalls = mutableListOf<InternalToolCall>()
+        private var usage: TokenUsage? = null
+
+        override fun onData(data: String): String? {
+            val chunk = JSONObject(data)
+            parseUsage(chunk)?.let { usage = it }
+
+            val textParts = mutableListOf<String>()
+            collectParts(chunk, textParts, toolCalls)
+
+            val delta = textParts.joinToString("")
+            if (delta.isEmpty()) return null
+            text.append(delta)
+            return delta
+        }
+
+        override fun finish(): AssistantTurn {
+            return AssistantTurn(
+                text = text.toString().ifBlank { "Ok" },
+                toolCalls = toolCalls,
+                usage = usage
+            )
+        }
+    }
+
+    private fun collectParts(
+        response: JSONObject,
+        textParts: MutableList<String>,
+        toolCalls: MutableList<InternalToolCall>
+    ) {
+        val parts = response.optJSONArray("candidates")?.optJSONObject(0)
+            ?.optJSONObject("content")?.optJSONArray("parts") ?: return
+
+        for (i in 0 until parts.length()) {
+            val part = parts.getJSONObject(i)
+            when {
+                part.has("functionCall") -> toolCalls.add(ToolHandler.fromJson(part))
+                part.has("text") -> textParts.add(part.getString("text"))
+            }
+        }
+    }
+
+    private fun parseUsage(response: JSONObject): TokenUsage? {
+        return response.optJSONObject("usageMetadata")?.let {
+            TokenUsage(
+                inputTokens = it.optInt("promptTokenCount"),
+                outputTokens = it.optInt("candidatesTokenCount")
+            )
+        }
+    }
+
+    /**
+     * Encodes the conversation as alternating user/model turns. Tool results go back as
+     * functionResponse parts named after the call they answer, matched through the tool call id,
+     * and all responses to one model turn are grouped into a single content entry.
+     */
+    private fun toGeminiContents(messages: List<Message>): List<GeminiContent> {
+        val toolNamesById = messages
+            .flatMap { it.toolCalls.orEmpty() }
+            .associate { it.id to it.function.name }
+
+        val result = mutableListOf<GeminiContent>()
+
+        for (message in messages) {
+            val (role, parts) = when (message.role) {
+                "user" -> "user" to contentParts(message.content)
+                "assistant" -> "model" to contentParts(message.content) +
+                        message.toolCalls.orEmpty().map { toolCall ->
+                            GeminiPart(
+                                functionCall = GeminiFunctionCall(
+                                    name = toolCall.function.name,
+                                    args = parseToolArguments(toolCall.function.arguments)
+                                )
+                            )
+                        }
+
+                "tool" -> {
+                    val output = message.content.orEmpty()
+                        .filterIsInstance<Content.Text>()
+                        .joinToString("\n") { it.text }
+                    val name = message.toolCallId?.let { toolNamesById[it] }
+                        ?: message.name
+                        ?: continue
+                    "user" to listOf(
+                        GeminiPart(
+                            functionResponse = GeminiFunctionResponse(
+                                name = name,
+                                response = JsonObject(mapOf("output" to JsonPrimitive(output)))
+                            )
+                        )
+                    ) + imageParts(message.content)
+                }
+
+                else -> continue
+            }
+
+            if (parts.isEmpty()) continue
+
+            val previous = result.lastOrNull()
+            if (previous != null && previous.role == role) {
+                result[result.lastIndex] = previous.copy(parts = previous.parts + parts)
+            } else {
+                result.add(GeminiContent(role = role, parts = parts))
+            }
+        }
+
+        return result
+    }
+
+    private fun contentParts(content: List<Content>?): List<GeminiPart> {
+        val textParts = content.orEmpty()
+            .filterIsInstance<Content.Text>()
+            .filter { it.text.isNotBlank() }
+            .map { GeminiPart(text = it.text) }
+        return textParts + imageParts(content)
+    }
+
+    private fun imageParts(content: List<Content>?): List<GeminiPart> {
+        return content.orEmpty()
+            .filterIsInstance<Content.ImageUrl>()
+            .mapNotNull { image ->
+                // Gemini only takes inline image data, not remote URLs
+                parseDataUrl(image.imageUrl.url)?.let { (mimeType, data) ->
+                    GeminiPart(inlineData = GeminiInlineData(mimeType = mimeType, data = data))
+                }
+            }
+    }
+
+    override fun classifyError(responseCode: Int, errorResponse: String): AgentException {
+        // Gemini reports a bad key as a 400 rather than a 401
+        if (responseCode == HttpURLConnection.HTTP_BAD_REQUEST &&
+            errorResponse.contains("API_KEY_INVALID")
+        ) {
+            return ApiKeyException("Invalid API key. Please check your API key in settings.")
+        }
+        return super.classifyError(responseCode, errorResponse)
+    }
+
+    private fun geminiTools(tools: List<ToolDefinition>): List<GeminiTool> {
+        val functionDeclarations = tools.map { toolDef ->
+            val parameters = toolDef.function.parameters
+            GeminiFunctionDeclaration(
+                name = toolDef.function.name,
+                description = toolDef.function.description,
+                parameters = if (parameters.properties.isEmpty()) null else parameters.copy(
+                    properties = parameters.properties.mapValues { (_, param) -> geminiParameter(param) }
+                )
+            )
+        }
+
+        return listOf(
+            GeminiTool(
+                functionDeclarations = functionDeclarations
+            )
+        )
+    }
+
+    // Gemini accepts a subset of JSON Schema: defaults are dropped and types narrowed
+    private fun geminiParameter(param: ToolParameter): ToolParameter {
+        return param.copy(
+            type = when (param.type.lowercase(Locale.getDefault())) {
+                "integer" -> "string" // Use string for integers
+                "boolean" -> "boolean"
+                "string" -> "string"
+                "double", "float", "number" -> "number"
+                "array" -> "array"
+                "object" -> "object"
+                else -> "string"
+            },
+            items = param.items?.let { geminiParameter(it) },
+            properties = param.properties?.mapValues { (_, nested) -> geminiParameter(nested) },
+            default = null
+        )
+    }
+}
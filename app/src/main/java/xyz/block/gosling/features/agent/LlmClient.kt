@@ -0,0 +1,106 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.SerializationException
+import okhttp3.OkHttpClient
+import okhttp3.Request
+import okhttp3.Response
+import org.json.JSONException
+import java.io.IOException
+import java.net.HttpURLConnection
+
+/**
+ * Sends the requests an [LlmProvider] builds and decodes the reply, either as one response or
+ * as a server-sent event stream. Failed responses are turned into the provider's
+ * [AgentException]s.
+ */
+class LlmClient(private val httpClient: OkHttpClient) {
+    companion object {
+        // Minimum gap between partial reply updates while streaming
+        private const val STREAM_UPDATE_INTERVAL_MS = 200L
+    }
+
+    fun complete(provider: LlmProvider, request: Request): AssistantTurn {
+        httpClient.newCall(request).execute().use { response ->
+            checkResponse(provider, response)
+
+            val responseBody = response.body?.string()
+                ?: throw AgentException("Empty response body")
+
+            return provider.parseResponse(responseBody)
+        }
+    }
+
+    /**
+     * Reads a server-sent event stream, passing the reply text to [onPartialText] as it grows
+     * so it can be shown before the turn is complete. Tool calls are only returned once the
+     * stream ends.
+     */
+    fun stream(
+        provider: LlmProvider,
+        request: Request,
+        isCancelled: () -> Boolean = { false },
+        onPartialText: (String) -> Unit = {}
+    ): AssistantTurn {
+        val decoder = provider.newStreamDecoder()
+        val partialText = StringBuilder()
+        var lastUpdate = 0L
+
+        try {
+            httpClient.newCall(request).execute().use { response ->
+                checkResponse(provider, response)
+
+                val source = response.body?.source()
+                    ?: throw AgentException("Empty response body")
+
+                while (!isCancelled()) {
+                    val line = source.readUtf8Line() ?: break
+                    if (!line.startsWith("data:")) continue
+
+                    val data = line.removePrefix("data:").trim()
+                    if (data.isEmpty() || data == "[DONE]") continue
+
+                    val delta = decodeChunk(decoder, data) ?: continue
+                    partialText.append(delta)
+
+                    val now = System.currentTimeMillis()
+                    if (now - lastUpdate >= STREAM_UPDATE_INTERVAL_MS) {
+                        lastUpdate = now
+                        onPartialText(partialText.toString())
+                    }
+                }
+            }
+        } catch (e: IOException) {
+            throw AgentException("Stream interrupted: ${e.message}")
+        }
+
+        return decoder.finish()
+    }
+
+    // A chunk that doesn't parse is most likely cut off, so the turn is retried like any other
+    // interrupted stream
+    private fun decodeChunk(decoder: LlmStreamDecoder, data: String): String? {
+        return try {
+            decoder.onData(data)
+        } catch (e: JSONException) {
+            throw AgentException("Malformed stream chunk: ${e.message}")
+        } catch (e: SerializationException) {
+            throw AgentException("Malformed stream chunk: ${e.message}")
+        }
+    }
+
+    private fun checkResponse(provider: LlmProvider, response: Response) {
+        if (response.isSuccessful) return
+
+        val errorBody = response.body?.string() ?: ""
+        val errorResponse = errorBody.ifEmpty {
+            when (response.code) {
+                HttpURLConnection.HTTP_UNAUTHORIZED -> "Unauthorized - API key may be invalid"
+                HttpURLConnection.HTTP_FORBIDDEN -> "Forbidden - Access denied"
+                HttpURLConnection.HTTP_NOT_FOUND -> "Not found - Invalid API endpoint"
+                HttpURLConnection.HTTP_BAD_REQUEST -> "Bad request"
+                else -> "HTTP Error ${response.code}"
+            }
+        }
+        throw provider.classifyError(response.code, errorResponse)
+    }
+}
@@ -0,0 +1,167 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.ExperimentalSerializationApi
+import kotlinx.serialization.json.Json
+import kotlinx.serialization.json.JsonObject
+import kotlinx.serialization.json.jsonObject
+import okhttp3.Request
+import org.json.JSONException
+import org.json.JSONObject
+import java.net.HttpURLConnection
+
+data class TokenUsage(
+    val inputTokens: Int,
+    val outputTokens: Int
+)
+
+/**
+ * A single decoded reply from the model: the text it wrote, the tools it wants called and
+ * how many tokens the exchange cost (when the backend reports it).
+ */
+data class AssistantTurn(
+    val text: String,
+    val toolCalls: List<InternalToolCall>? = null,
+    val usage: TokenUsage? = null
+)
+
+/**
+ * Where and how to reach a provider. An empty [baseUrl] means the provider's public API; set it
+ * to target a self-hosted or proxied server that speaks the same protocol.
+ */
+data class ProviderConfig(
+    val baseUrl: String = "",
+    val extraHeaders: Map<String, String> = emptyMap()
+) {
+    fun resolveBaseUrl(provider: ModelProvider): String {
+        return baseUrl.ifBlank { provider.defaultBaseUrl }.trimEnd('/')
+    }
+
+    companion object {
+        /**
+         * Parses headers entered one per line as `Name: value`. Blank or malformed lines are ignored.
+         */
+        fun parseHeaders(text: String): Map<String, String> {
+            return text.lines()
+                .mapNotNull { line ->
+                    val name = line.substringBefore(':', "").trim()
+                    val value = line.substringAfter(':', "").trim()
+                    if (name.isEmpty()) null else name to value
+                }
+                .toMap()
+        }
+    }
+}
+
+/**
+ * A tool call whose arguments are still arriving in pieces over a stream.
+ */
+internal class PartialToolCall(
+    var id: String = "",
+    var name: String = "",
+    val arguments: StringBuilder = StringBuilder()
+) {
+    /**
+     * Throws [AgentException] when the arguments never finished arriving, e.g. because the
+     * stream was cut off, so the turn is retried like any other interrupted stream.
+     */
+    fun toInternalToolCall(): InternalToolCall {
+        val json = try {
+            JSONObject(arguments.toString().ifBlank { "{}" })
+        } catch (e: JSONException) {
+            throw AgentException("Incomplete arguments for tool call $name")
+        }
+        return InternalToolCall(
+            toolId = id,
+            name = name,
+            arguments = json
+        )
+    }
+}
+
+internal fun parseToolArguments(arguments: String): JsonObject {
+    return try {
+        Json.parseToJsonElement(arguments).jsonObject
+    } catch (e: Exception) {
+        JsonObject(emptyMap())
+    }
+}
+
+/**
+ * Splits a `data:<mime>;base64,<data>` URL into its media type and payload, or returns null
+ * for regular URLs.
+ */
+internal fun parseDataUrl(url: String): Pair<String, String>? {
+    if (!url.startsWith("data:")) return null
+
+    val mediaType = url.substringAfter("data:").substringBefore(";")
+    val data = url.substringAfter("base64,").filterNot { it.isWhitespace() }
+    return mediaType to data
+}
+
+internal fun Request.Builder.addHeaders(headers: Map<String, String>): Request.Builder {
+    headers.forEach { (name, value) -> addHeader(name, value) }
+    return this
+}
+
+/**
+ * Incrementally decodes a server-sent event stream into an [AssistantTurn]. Text is surfaced
+ * as it arrives while tool call arguments are buffered until the stream ends.
+ */
+interface LlmStreamDecoder {
+    /**
+     * Consumes the payload of one `data:` line and returns any new assistant text it carried.
+     */
+    fun onData(data: String): String?
+
+    fun finish(): AssistantTurn
+}
+
+/**
+ * A backend that can drive the agent loop. Implementations own everything that differs
+ * between APIs: the endpoint, auth, the wire format of the conversation and tools, and how
+ * replies and failures are decoded. The agent only ever deals in [Message]s and [AssistantTurn]s.
+ */
+interface LlmProvider {
+    val supportsStreaming: Boolean
+        get() = false
+
+    fun buildRequest(
+        model: AiModel,
+        apiKey: String,
+        messages: List<Message>,
+        tools: List<ToolDefinition>,
+        stream: Boolean = false
+    ): Request
+
+    fun parseResponse(body: String): AssistantTurn
+
+    fun newStreamDecoder(): LlmStreamDecoder {
+        throw UnsupportedOperationException("${this::class.simpleName} does not support streaming")
+    }
+
+    fun classifyError(responseCode: Int, errorResponse: String): AgentException {
+        if (responseCode == HttpURLConnection.HTTP_UNAUTHORIZED) {
+            return ApiKeyException("Invalid API key. Please check your API key in settings.")
+        }
+        return AgentException(errorResponse)
+    }
+
+    companion object {
+        // Omitting nulls keeps optional fields off the wire, which Gemini's oneof parts need
+        @OptIn(ExperimentalSerializationApi::class)
+        internal val jsonFormat = Json {
+            prettyPrint = true
+            encodeDefaults = true
+            ignoreUnknownKeys = true
+            explicitNulls = false
+        }
+
+        fun forModel(model: AiModel, config: ProviderConfig = ProviderConfig()): LlmProvider {
+            return when (model.provider) {
+                ModelProvider.OPENAI -> OpenAIProvider(config)
+                ModelProvider.GEMINI -> GeminiProvider(config)
+                ModelProvider.ANTHROPIC -> AnthropicProvider(config)
+            }
+        }
+    }
+}
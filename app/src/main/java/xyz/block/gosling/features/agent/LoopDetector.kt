@@ -0,0 +1,79 @@
+package xyz.block.gosling.features.agent
+
+/**
+ * Spots runs that have stopped making progress: the model asking for exactly the same tool
+ * calls with the same results turn after turn, or acting on the screen while the UI hierarchy
+ * it reads back never changes. Repeating a call is fine as long as its output changes, as when
+ * scrolling through a long list.
+ */
+class LoopDetector(
+    private val repeatedCallThreshold: Int = REPEATED_CALL_THRESHOLD,
+    private val unchangedUiThreshold: Int = UNCHANGED_UI_THRESHOLD
+) {
+    companion object {
+        private const val REPEATED_CALL_THRESHOLD = 3
+        private const val UNCHANGED_UI_THRESHOLD = 4
+        private val SCREEN_READING_TOOLS = setOf("getUiHierarchy", "getUiChanges")
+    }
+
+    private var lastSignature: String? = null
+    private var repeatedCalls = 0
+
+    private var lastUiHash: Int? = null
+    private var actedSinceLastUi = false
+    private var unchangedUi = 0
+
+    /**
+     * Records one turn's tool calls and their outputs, in the same order. Returns why the run
+     * looks stuck, or null if it still seems to be getting somewhere.
+     */
+    fun record(toolCalls: List<InternalToolCall>, outputs: List<String>): String? {
+        val signature = toolCalls
+            .mapIndexed { i, toolCall ->
+                "${toolCall.name}(${toolCall.arguments})=${outputs.getOrNull(i).hashCode()}"
+            }
+            .sorted()
+            .joinToString(";")
+        if (signature == lastSignature) {
+            repeatedCalls++
+        } else {
+            lastSignature = signature
+            repeatedCalls = 1
+        }
+
+        toolCalls.zip(outputs).forEach { (toolCall, output) ->
+            if (toolCall.name in SCREEN_READING_TOOLS) {
+                val hash = output.hashCode()
+                // Reading the screen twice without acting in between is just waiting for it
+                if (hash == lastUiHash && actedSinceLastUi) {
+                    unchangedUi++
+                } else if (hash != lastUiHash) {
+                    unchangedUi = 0
+                }
+                lastUiHash = hash
+                actedSinceLastUi = false
+            } else if (ToolHandler.concurrencyOf(toolCall.name) == ToolConcurrency.UI) {
+                actedSinceLastUi = true
+            }
+        }
+
+        return when {
+            repeatedCalls >= repeatedCallThreshold ->
+                "the same tool calls were made with the same results $repeatedCalls turns " +
+                        "in a row (${toolCalls.joinToString { it.name }})"
+
+            unchangedUi >= unchangedUiThreshold ->
+                "the screen did not change after the last $unchangedUi actions"
+
+            else -> null
+        }
+    }
+
+    fun reset() {
+        lastSignature = null
+        repeatedCalls = 0
+        lastUiHash = null
+        actedSinceLastUi = false
+        unchangedUi = 0
+    }
+}
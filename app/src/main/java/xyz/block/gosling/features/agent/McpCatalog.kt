@@ -0,0 +1,223 @@
+package xyz.block.gosling.features.agent
+
+import android.content.BroadcastReceiver
+import android.content.ComponentName
+import android.content.Context
+import android.content.Intent
+import android.content.IntentFilter
+import android.util.Log
+import androidx.core.content.ContextCompat
+import org.json.JSONArray
+import org.json.JSONObject
+import xyz.block.gosling.features.settings.SettingsStore
+import xyz.block.gosling.mmcp.MobileMcp
+import java.io.File
+import java.util.concurrent.Executors
+
+/**
+ * The Mobile MCP servers installed on this device, kept in a file so agent turns read them
+ * instead of waiting for discovery. Servers are rediscovered when their package is installed,
+ * updated or removed, when the catalogue no longer matches the installed packages at start, and
+ * when the user asks for it in settings.
+ *
+ * Each server keeps its short localId for as long as Gosling is installed, also across removal
+ * and reinstallation of its app.
+ */
+object McpCatalog {
+    private const val TAG = "McpCatalog"
+    private const val FILE_NAME = "mcp_catalog.json"
+
+    private val lock = Any()
+
+    // Refreshes run one at a time, in the order they were asked for
+    private val refresher = Executors.newSingleThreadExecutor()
+
+    private var loaded = false
+    private var watching = false
+    private var servers: List<DiscoveredMcp> = emptyList()
+
+    // "package/receiver" -> localId, never forgotten so ids stay stable
+    private val localIds = mutableMapOf<String, String>()
+
+    private val packageReceiver = object : BroadcastReceiver() {
+        override fun onReceive(context: Context, intent: Intent) {
+            val packageName = intent.data?.schemeSpecificPart ?: return
+            // An update sends REMOVED with EXTRA_REPLACING before REPLACED, only act on the latter
+            if (intent.action == Intent.ACTION_PACKAGE_REMOVED &&
+                intent.getBooleanExtra(Intent.EXTRA_REPLACING, false)
+            ) {
+                return
+            }
+            Log.d(TAG, "${intent.action} for $packageName")
+            refreshPackagesAsync(context.applicationContext, setOf(packageName))
+        }
+    }
+
+    /**
+     * Watches for installed, updated and removed packages while the process runs, and brings
+     * the catalogue up to date with changes made while it didn't. Does nothing while app
+     * extensions are turned off, so call it again when they are turned on.
+     */
+    fun start(context: Context) {
+        val appContext = context.applicationContext
+        if (!SettingsStore(appContext).enableAppExtensions) return
+
+        val firstStart = synchronized(lock) {
+            val first = !watching
+            watching = true
+            first
+        }
+        if (firstStart) registerPackageReceiver(appContext)
+
+        refresher.execute {
+            load(appContext)
+            val stale = stalePackages(appContext)
+            if (stale.isNotEmpty()) refreshPackages(appContext, stale)
+        }
+    }
+
+    private fun registerPackageReceiver(appContext: Context) {
+        val filter = IntentFilter().apply {
+            addAction(Intent.ACTION_PACKAGE_ADDED)
+            addAction(Intent.ACTION_PACKAGE_REPLACED)
+            addAction(Intent.ACTION_PACKAGE_REMOVED)
+            addDataScheme("package")
+        }
+        ContextCompat.registerReceiver(
+            appContext,
+            packageReceiver,
+            filter,
+            ContextCompat.RECEIVER_NOT_EXPORTED
+        )
+    }
+
+    /**
+     * The known servers. Only the very first call, before anything was ever discovered, waits
+     * for discovery, so don't call this from the main thread.
+     */
+    fun servers(context: Context): List<DiscoveredMcp> {
+        if (!File(context.filesDir, FILE_NAME).exists()) {
+            // Queued behind the check from start(), which most likely discovered them already
+            refresher.submit {
+                if (!File(context.filesDir, FILE_NAME).exists()) discoverAll(context)
+            }.get()
+        }
+        return synchronized(lock) {
+            load(context)
+            servers
+        }
+    }
+
+    fun find(context: Context, localId: String): DiscoveredMcp? {
+        return synchronized(lock) {
+            load(context)
+            servers.find { it.localId == localId }
+        }
+    }
+
+    /**
+     * Discovers all servers again and waits for it to finish, returning what was found.
+     */
+    fun refresh(context: Context): List<DiscoveredMcp> {
+        return refresher.submit<List<DiscoveredMcp>> { discoverAll(context) }.get()
+    }
+
+    private fun discoverAll(context: Context): List<DiscoveredMcp> {
+        // Loaded first so known servers get their ids back
+        load(context)
+        val discovered = MobileMCP.discoverMCPs(context) { localIdFor(it) }
+        synchronized(lock) {
+            servers = discovered
+            save(context)
+        }
+        return discovered
+    }
+
+    private fun refreshPackagesAsync(context: Context, packageNames: Set<String>) {
+        refresher.execute {
+            // Don't wake apps the user opted out of; start() catches up when turned back on
+            if (SettingsStore(context).enableAppExtensions) refreshPackages(context, packageNames)
+        }
+    }
+
+    private fun refreshPackages(context: Context, packageNames: Set<String>) {
+        load(context)
+        val discovered = packageNames.flatMap { packageName ->
+            MobileMCP.discoverMCPs(context, packageName) { localIdFor(it) }
+        }
+        synchronized(lock) {
+            val kept = servers.filter { it.packageName !in packageNames }
+            // Most package changes are of apps without any server
+            if (kept.size == servers.size && discovered.isEmpty()) return
+            servers = kept + discovered
+            save(context)
+        }
+        Log.d(TAG, "Refreshed $packageNames: ${discovered.map { it.info.name }}")
+    }
+
+    /**
+     * Packages whose servers were added, updated or removed since the catalogue was saved.
+     */
+    private fun stalePackages(context: Context): Set<String> {
+        val installed = context.packageManager
+            .queryBroadcastReceivers(Intent(MobileMcp.ACTION_DISCOVER), 0)
+            .map { it.activityInfo.packageName }
+            .toSet()
+        val known = synchronized(lock) { servers.associate { it.packageName to it.lastUpdateTime } }
+
+        val changed = installed.filter { packageName ->
+            known[packageName] != MobileMCP.lastUpdateTime(context, packageName)
+        }
+        return changed.toSet() + (known.keys - installed)
+    }
+
+    private fun localIdFor(component: ComponentName): String {
+        val key = component.flattenToString()
+        return synchronized(lock) {
+            localIds.getOrPut(key) { generateLocalId() }
+        }
+    }
+
+    // A unique 3-character localId (2 letters + 1 digit) to keep tool names short
+    private fun generateLocalId(): String {
+        while (true) {
+            val letters = (1..2).map { (('a'..'z') + ('A'..'Z')).random() }.joinToString("")
+            val localId = "$letters${('0'..'9').random()}"
+            if (localId !in localIds.values) return localId
+        }
+    }
+
+    private fun load(context: Context) {
+        synchronized(lock) {
+            if (loaded) return
+            loaded = true
+
+            val file = File(context.filesDir, FILE_NAME)
+            if (!file.exists()) return
+            try {
+                val json = JSONObject(file.readText())
+                val ids = json.getJSONObject("localIds")
+                ids.keys().forEach { localIds[it] = ids.getString(it) }
+                val array = json.getJSONArray("servers")
+                servers = (0 until array.length()).map { DiscoveredMcp.fromJson(array.getJSONObject(it)) }
+            } catch (e: Exception) {
+                Log.e(TAG, "Error reading MCP catalogue, discovering again: ${e.message}")
+                file.delete()
+            }
+        }
+    }
+
+    private fun save(context: Context) {
+        val json = JSONObject()
+            .put("localIds", JSONObject(localIds.toMap()))
+            .put("servers", JSONArray(servers.map { it.toJson() }))
+        try {
+            val file = File(context.filesDir, FILE_NAME)
+            val temp = File(context.filesDir, "$FILE_NAME.tmp")
+            temp.writeText(json.toString())
+            temp.renameTo(file)
+        } catch (e: Exception) {
+            Log.e(TAG, "Error saving MCP catalogue: ${e.message}")
+        }
+    }
+}
@@ -0,0 +1,293 @@
+package xyz.block.gosling.features.agent
+
+import android.content.ComponentName
+import android.content.Context
+import android.content.Intent
+import android.content.ServiceConnection
+import android.os.Handler
+import android.os.IBinder
+import android.os.Looper
+import android.os.ParcelFileDescriptor
+import android.os.RemoteException
+import android.os.SystemClock
+import android.util.Log
+import org.json.JSONObject
+import xyz.block.gosling.mmcp.IMobileMcpCallback
+import xyz.block.gosling.mmcp.IMobileMcpService
+import xyz.block.gosling.mmcp.McpErrorCode
+import xyz.block.gosling.mmcp.McpPayloads
+import xyz.block.gosling.mmcp.McpResult
+import xyz.block.gosling.mmcp.MobileMcp
+import java.util.UUID
+import java.util.concurrent.ConcurrentHashMap
+import java.util.concurrent.CountDownLatch
+import java.util.concurrent.TimeUnit
+import java.util.concurrent.atomic.AtomicBoolean
+import java.util.concurrent.atomic.AtomicLong
+import java.util.concurrent.atomic.AtomicReference
+
+/**
+ * Calls Mobile MCP tools over the bound-service transport of apps that offer one. Connections
+ * are kept per package and reused across calls, which may run at the same time. A connection
+ * nobody used for [UNBIND_AFTER_MS] is released so the app's service can stop.
+ */
+object McpServiceClient {
+    private const val TAG = "McpServiceClient"
+    private const val BIND_TIMEOUT_SECONDS = 5L
+    private const val POLL_INTERVAL_MS = 200L
+    private const val UNBIND_AFTER_MS = 30_000L
+
+    // A call may run as long as it likes while it keeps reporting progress
+    private const val IDLE_TIMEOUT_MS = 60_000L
+
+    private val connections = ConcurrentHashMap<String, Connection>()
+    private val handler = Handler(Looper.getMainLooper())
+
+    private class Connection(
+        val context: Context,
+        val component: ComponentName
+    ) : ServiceConnection, IBinder.DeathRecipient {
+        val connected = CountDownLatch(1)
+
+        @Volatile
+        var service: IMobileMcpService? = null
+
+        // Fails each call still waiting on the service, keyed by call id
+        val pendingCalls = ConcurrentHashMap<String, () -> Unit>()
+
+        // Guarded by this connection
+        var users = 0
+        var released = false
+
+        val release = Runnable {
+            synchronized(this) {
+                if (users > 0) return@Runnable
+                released = true
+            }
+            disconnect(this)
+        }
+
+        override fun onServiceConnected(name: ComponentName, binder: IBinder) {
+            try {
+                binder.linkToDeath(this, 0)
+            } catch (e: RemoteException) {
+                // Died before we got to it; the system reconnects once it's back
+                return
+            }
+            service = IMobileMcpService.Stub.asInterface(binder)
+            connected.countDown()
+        }
+
+        override fun onServiceDisconnected(name: ComponentName) {
+            // The system rebinds on its own once the app's process is back
+            lost()
+        }
+
+        override fun binderDied() {
+            lost()
+        }
+
+        override fun onBindingDied(name: ComponentName) {
+            lost()
+            connections.remove(name.packageName, this)
+        }
+
+        override fun onNullBinding(name: ComponentName) {
+            connections.remove(name.packageName, this)
+            connected.countDown()
+        }
+
+        private fun lost() {
+            service = null
+            pendingCalls.values.forEach { fail -> fail() }
+        }
+    }
+
+    /**
+     * The Mobile MCP service of [packageName], or null if it only speaks broadcasts.
+     */
+    fun findService(context: Context, packageName: String): ComponentName? {
+        val intent = Intent(MobileMcp.ACTION_BIND).setPackage(packageName)
+        return context.packageManager.queryIntentServices(intent, 0)
+            .firstOrNull()
+            ?.serviceInfo
+            ?.let { ComponentName(it.packageName, it.name) }
+    }
+
+    /**
+     * Calls [tool] on [component] and waits for its result, reporting progress through
+     * [onProgress]. Returns null when the service could not be reached, so the caller can fall
+     * back to broadcasts.
+     */
+    fun invoke(
+        context: Context,
+        component: ComponentName,
+        protocolVersion: Int,
+        tool: String,
+        arguments: JSONObject,
+        onProgress: (String) -> Unit,
+        isCancelled: () -> Boolean
+    ): McpResult? {
+        val connection = acquire(context, component) ?: return null
+        try {
+            return call(connection, protocolVersion, tool, arguments, onProgress, isCancelled)
+        } finally {
+            releaseLater(connection)
+        }
+    }
+
+    private fun call(
+        connection: Connection,
+        protocolVersion: Int,
+        tool: String,
+        arguments: JSONObject,
+        onProgress: (String) -> Unit,
+        isCancelled: () -> Boolean
+    ): McpResult? {
+        val component = connection.component
+        val service = connection.service ?: return null
+
+        val callId = UUID.randomUUID().toString()
+        val outcome = AtomicReference<McpResult>()
+        val lastActivity = AtomicLong(SystemClock.uptimeMillis())
+        val done = CountDownLatch(1)
+        // Set once this call stopped waiting, after which a late result is dropped unread
+        val abandoned = AtomicBoolean(false)
+
+        val callback = object : IMobileMcpCallback.Stub() {
+            override fun onProgress(callId: String, progress: Float, message: String?) {
+                lastActivity.set(SystemClock.uptimeMillis())
+                val percent = if (progress >= 0) " (${(progress * 100).toInt()}%)" else ""
+                onProgress("${message.orEmpty()}$percent")
+            }
+
+            override fun onResult(callId: String, result: String?, largeResult: ParcelFileDescriptor?) {
+                if (abandoned.get()) {
+                    largeResult?.close()
+                    return
+                }
+                val decoded = try {
+                    McpPayloads.join(result, largeResult)
+                        ?.let { McpResult.fromJson(JSONObject(it)) }
+                        ?: McpResult.Error(McpErrorCode.NO_RESPONSE, "Empty result for $tool")
+                } catch (e: Exception) {
+                    McpResult.Error(McpErrorCode.INTERNAL_ERROR, "Invalid result: ${e.message}")
+                }
+                outcome.compareAndSet(null, decoded)
+                done.countDown()
+            }
+        }
+
+        // Ends the call as soon as the app dies or disconnects, instead of at the idle timeout
+        connection.pendingCalls[callId] = {
+            val lost = McpResult.Error(
+                McpErrorCode.NO_RESPONSE,
+                "${component.packageName} stopped before answering $tool"
+            )
+            if (outcome.compareAndSet(null, lost)) done.countDown()
+        }
+        try {
+            val (inline, large) = McpPayloads.split(arguments.toString())
+            try {
+                service.callTool(callId, protocolVersion, tool, inline, large, callback)
+            } catch (e: RemoteException) {
+                Log.w(TAG, "Calling $tool on $component failed: ${e.message}")
+                disconnect(connection)
+                return null
+            } finally {
+                large?.close()
+            }
+
+            while (!done.await(POLL_INTERVAL_MS, TimeUnit.MILLISECONDS)) {
+                val stop = when {
+                    isCancelled() -> McpResult.Error(McpErrorCode.CANCELLED, "Cancelled by the user")
+                    SystemClock.uptimeMillis() - lastActivity.get() > IDLE_TIMEOUT_MS ->
+                        McpResult.Error(
+                            McpErrorCode.TIMEOUT,
+                            "${component.packageName} reported nothing for ${IDLE_TIMEOUT_MS / 1000} seconds"
+                        )
+
+                    else -> null
+                }
+                if (stop != null) {
+                    abandoned.set(true)
+                    try {
+                        service.cancel(callId)
+                    } catch (e: RemoteException) {
+                        // Gone already, nothing left to cancel
+                    }
+                    return stop
+                }
+            }
+
+            return outcome.get()
+        } finally {
+            connection.pendingCalls.remove(callId)
+        }
+    }
+
+    /**
+     * Connects to [component] and keeps the connection from being released until
+     * [releaseLater] is called.
+     */
+    private fun acquire(context: Context, component: ComponentName): Connection? {
+        // A connection released while we waited on it is replaced by a fresh one
+        repeat(2) {
+            val connection = connect(context, component) ?: return null
+            synchronized(connection) {
+                if (!connection.released) {
+                    connection.users++
+                    handler.removeCallbacks(connection.release)
+                    return connection
+                }
+            }
+        }
+        return null
+    }
+
+    private fun releaseLater(connection: Connection) {
+        synchronized(connection) {
+            connection.users--
+            if (connection.users == 0 && !connection.released) {
+                handler.postDelayed(connection.release, UNBIND_AFTER_MS)
+            }
+        }
+    }
+
+    private fun connect(context: Context, component: ComponentName): Connection? {
+        val connection = connections.getOrPut(component.packageName) {
+            Connection(context.applicationContext, component).also { connection ->
+                val intent = Intent(MobileMcp.ACTION_BIND).setComponent(component)
+                val bound = connection.context
+                    .bindService(intent, connection, Context.BIND_AUTO_CREATE)
+                if (!bound) {
+                    Log.w(TAG, "Could not bind to $component")
+                    connection.connected.countDown()
+                }
+            }
+        }
+
+        try {
+            connection.connected.await(BIND_TIMEOUT_SECONDS, TimeUnit.SECONDS)
+        } catch (e: InterruptedException) {
+            return null
+        }
+
+        if (connection.service == null) {
+            disconnect(connection)
+            return null
+        }
+        return connection
+    }
+
+    private fun disconnect(connection: Connection) {
+        synchronized(connection) { connection.released = true }
+        handler.removeCallbacks(connection.release)
+        connections.remove(connection.component.packageName, connection)
+        try {
+            connection.context.unbindService(connection)
+        } catch (e: IllegalArgumentException) {
+            // Never got bound, or already unbound
+        }
+    }
+}
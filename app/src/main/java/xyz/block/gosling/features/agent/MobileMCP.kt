@@ -4,95 +4,147 @@ import android.content.BroadcastReceiver
 import android.content.ComponentName
 import android.content.Context
 import android.content.Intent
+import android.content.pm.PackageManager
+import android.os.Bundle
 import android.os.Looper
 import android.util.Log
+import org.json.JSONArray
+import org.json.JSONObject
+import xyz.block.gosling.mmcp.McpErrorCode
+import xyz.block.gosling.mmcp.McpPrompt
+import xyz.block.gosling.mmcp.McpResource
+import xyz.block.gosling.mmcp.McpResult
+import xyz.block.gosling.mmcp.McpServerInfo
+import xyz.block.gosling.mmcp.McpTool
+import xyz.block.gosling.mmcp.MobileMcp
 import java.util.concurrent.CountDownLatch
 import java.util.concurrent.TimeUnit
 
-// a lightweight MCP client that will discover apps that can add tools
+/**
+ * An app on this device that answered Mobile MCP discovery. [lastUpdateTime] is that of its
+ * package when it answered, to tell when it needs to be asked again.
+ */
+data class DiscoveredMcp(
+    val localId: String,
+    val packageName: String,
+    val receiverName: String,
+    val info: McpServerInfo,
+    val protocolVersion: Int,
+    val capabilities: Set<String>,
+    val tools: List<McpTool>,
+    val resources: List<McpResource> = emptyList(),
+    val prompts: List<McpPrompt> = emptyList(),
+    val lastUpdateTime: Long = 0L
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("localId", localId)
+        .put("packageName", packageName)
+        .put("receiverName", receiverName)
+        .put("info", info.toJson())
+        .put("protocolVersion", protocolVersion)
+        .put("capabilities", JSONArray(capabilities.toList()))
+        .put("tools", JSONArray(McpTool.listToJson(tools)))
+        .put("resources", JSONArray(McpResource.listToJson(resources)))
+        .put("prompts", JSONArray(McpPrompt.listToJson(prompts)))
+        .put("lastUpdateTime", lastUpdateTime)
+
+    companion object {
+        fun fromJson(json: JSONObject): DiscoveredMcp {
+            val capabilities = json.getJSONArray("capabilities")
+            return DiscoveredMcp(
+                localId = json.getString("localId"),
+                packageName = json.getString("packageName"),
+                receiverName = json.getString("receiverName"),
+                info = McpServerInfo.fromJson(json.getJSONObject("info")),
+                protocolVersion = json.getInt("protocolVersion"),
+                capabilities = (0 until capabilities.length()).map { capabilities.getString(it) }.toSet(),
+                tools = McpTool.listFromJson(json.getJSONArray("tools").toString()),
+                resources = McpResource.listFromJson(json.getJSONArray("resources").toString()),
+                prompts = McpPrompt.listFromJson(json.getJSONArray("prompts").toString()),
+                lastUpdateTime = json.optLong("lastUpdateTime")
+            )
+        }
+    }
+}
+
+/**
+ * A prompt of an extension, offered to the user as a command to start with.
+ */
+data class ExtensionPrompt(
+    val localId: String,
+    val appName: String,
+    val prompt: McpPrompt
+) {
+    val label: String
+        get() = prompt.description.ifEmpty { prompt.name }
+}
+
+// a lightweight MCP client that will discover apps that can add tools, see mmcp/README.md
 object MobileMCP {
     private const val TAG = "MobileMCP"
+    private const val DISCOVERY_TIMEOUT_SECONDS = 5L
+    private const val INVOKE_TIMEOUT_MS = 10_000L
+    private const val POLL_INTERVAL_MS = 200L
 
-    // Map to store localId -> (packageName, appName) mappings to keep names short in function calls
-    private val mcpRegistry = mutableMapOf<String, Pair<String, String>>()
+    // Capabilities this client knows how to use
+    private val clientCapabilities = arrayOf(
+        MobileMcp.CAPABILITY_TOOLS,
+        MobileMcp.CAPABILITY_RESOURCES,
+        MobileMcp.CAPABILITY_PROMPTS
+    )
 
-    // Generate a unique 3-character localId (2 letters + 1 digit)
-    private fun generateLocalId(): String {
-        val charPool = ('a'..'z') + ('A'..'Z') + ('0'..'9')
-        val letters = (1..2).map { charPool.filter { it.isLetter() }.random() }.joinToString("")
-        val digit = charPool.filter { it.isDigit() }.random()
-        val localId = "$letters$digit"
-
-        // Ensure the ID is unique
-        return if (mcpRegistry.containsKey(localId)) {
-            generateLocalId() // Try again if this ID is already used
-        } else {
-            localId
-        }
+    private fun Intent.withVersionRange(): Intent = apply {
+        putExtra(MobileMcp.EXTRA_PROTOCOL_VERSION, MobileMcp.PROTOCOL_VERSION)
+        putExtra(MobileMcp.EXTRA_MIN_PROTOCOL_VERSION, MobileMcp.MIN_PROTOCOL_VERSION)
     }
 
-    // discover MCPs that are on this device.
-    fun discoverMCPs(context: Context): List<Map<String, Any>> {
+    /**
+     * Asks the MCPs on this device, or only those of [onlyPackage], what they offer. This sends
+     * broadcasts and waits for the answers; use [McpCatalog] for what was found before.
+     * [localIdFor] names each receiver with the short id used in tool names.
+     */
+    fun discoverMCPs(
+        context: Context,
+        onlyPackage: String? = null,
+        localIdFor: (ComponentName) -> String
+    ): List<DiscoveredMcp> {
         if (Looper.myLooper() == Looper.getMainLooper()) {
             throw IllegalStateException("Don't call this from the main thread!")
         }
 
-        val action = "com.example.ACTION_MMCP_DISCOVERY" // TODO we need a permanent name for this.
-        val intent = Intent(action)
         val packageManager = context.packageManager
-        val resolveInfos = packageManager.queryBroadcastReceivers(intent, 0)
+        val query = Intent(MobileMcp.ACTION_DISCOVER).setPackage(onlyPackage)
+        val resolveInfos = packageManager.queryBroadcastReceivers(query, 0)
 
-        val results = mutableListOf<Map<String, Any>>()
+        val results = mutableListOf<DiscoveredMcp>()
         val latch = CountDownLatch(resolveInfos.size) // Wait for all broadcasts to finish
 
         for (resolveInfo in resolveInfos) {
-            val componentName = ComponentName(
-                resolveInfo.activityInfo.packageName,
-                resolveInfo.activityInfo.name
-            )
+            val packageName = resolveInfo.activityInfo.packageName
+            val receiverName = resolveInfo.activityInfo.name
+            val componentName = ComponentName(packageName, receiverName)
+            val updateTime = lastUpdateTime(context, packageName)
 
-            val broadcastIntent = Intent(action).apply {
+            val broadcastIntent = Intent(MobileMcp.ACTION_DISCOVER).apply {
                 component = componentName
-            }
+                putExtra(MobileMcp.EXTRA_CAPABILITIES, clientCapabilities)
+            }.withVersionRange()
 
             val receiver = object : BroadcastReceiver() {
                 override fun onReceive(context: Context?, intent: Intent?) {
                     Log.d(TAG, "MCP receive from $componentName")
-                    val extras = getResultExtras(true)
-                    Log.d(TAG, "Extras: $extras")
-                    if (extras != null) {
-                        val packageName = resolveInfo.activityInfo.packageName
-                        val appName = resolveInfo.activityInfo.name
-
-                        // Generate or retrieve a localId for this MCP
-                        val localId =
-                            mcpRegistry.entries.find { it.value == Pair(packageName, appName) }?.key
-                                ?: generateLocalId().also {
-                                    mcpRegistry[it] = Pair(packageName, appName)
-                                }
-
-                        val result = mapOf(
-                            "packageName" to packageName,
-                            "name" to appName,
-                            "localId" to localId,
-                            "tools" to (extras.getStringArray("tools")?.toList() ?: emptyList())
-                                .associateWith { tool ->
-                                    mapOf(
-                                        "description" to (extras.getString("$tool.description")
-                                            ?: ""),
-                                        "parameters" to (extras.getString("$tool.parameters")
-                                            ?: "{}")
-                                    )
-                                }
-                        )
-                        Log.d(TAG, "Results adding: $result")
-                        results.add(result)
+                    try {
+                        parseDiscovery(componentName, updateTime, getResultExtras(true), localIdFor)
+                            ?.let { synchronized(results) { results.add(it) } }
+                    } catch (e: Exception) {
+                        Log.e(TAG, "Invalid discovery response from $componentName: ${e.message}")
+                    } finally {
+                        latch.countDown()
                     }
-                    latch.countDown() // ✅ Signal that this receiver has finished processing
                 }
             }
 
-            Log.d(TAG, "Sending broadcast to $componentName...")
+            Log.d(TAG, "Sending discovery to $componentName...")
 
             context.sendOrderedBroadcast(
                 broadcastIntent,
@@ -103,53 +155,187 @@ object MobileMCP {
                 null, // initial data
                 null // No initial extras
             )
-
-            Log.d(TAG, "Broadcast finished for $componentName")
         }
 
         try {
             // Wait for all broadcasts to finish (5-second timeout to avoid hanging forever), these should be fast
             // and this is a one time wait
-            latch.await(5, TimeUnit.SECONDS)
+            latch.await(DISCOVERY_TIMEOUT_SECONDS, TimeUnit.SECONDS)
         } catch (e: InterruptedException) {
             Log.e(TAG, "Latch interrupted: ${e.message}")
         }
 
-        Log.d(TAG, "Returning results: $results")
+        Log.d(TAG, "Discovered: ${results.map { it.info.name }}")
 
-        return results
+        return synchronized(results) { results.toList() }
     }
 
-    // invoke a specific tool in an external app
+    private fun parseDiscovery(
+        component: ComponentName,
+        lastUpdateTime: Long,
+        extras: Bundle,
+        localIdFor: (ComponentName) -> String
+    ): DiscoveredMcp? {
+        val packageName = component.packageName
+        extras.getString(MobileMcp.EXTRA_RESULT)?.let { json ->
+            val error = McpResult.fromJson(JSONObject(json)) as? McpResult.Error
+            Log.w(TAG, "$packageName refused discovery: ${error?.code} ${error?.message}")
+            return null
+        }
+
+        val version = extras.getInt(MobileMcp.EXTRA_PROTOCOL_VERSION, 0)
+        if (MobileMcp.negotiateVersion(version, version) == null) {
+            Log.w(TAG, "$packageName answered with unsupported protocol version $version")
+            return null
+        }
+
+        val capabilities = extras.getStringArray(MobileMcp.EXTRA_CAPABILITIES)?.toSet().orEmpty()
+        val info = extras.getString(MobileMcp.EXTRA_SERVER_INFO)
+            ?.let { McpServerInfo.fromJson(JSONObject(it)) }
+            ?: McpServerInfo(name = packageName, version = "")
+        val tools = if (MobileMcp.CAPABILITY_TOOLS in capabilities) {
+            extras.getString(MobileMcp.EXTRA_TOOLS)?.let { McpTool.listFromJson(it) }.orEmpty()
+        } else {
+            emptyList()
+        }
+        val resources = if (MobileMcp.CAPABILITY_RESOURCES in capabilities) {
+            extras.getString(MobileMcp.EXTRA_RESOURCES)?.let { McpResource.listFromJson(it) }.orEmpty()
+        } else {
+            emptyList()
+        }
+        val prompts = if (MobileMcp.CAPABILITY_PROMPTS in capabilities) {
+            extras.getString(MobileMcp.EXTRA_PROMPTS)?.let { McpPrompt.listFromJson(it) }.orEmpty()
+        } else {
+            emptyList()
+        }
+
+        return DiscoveredMcp(
+            localId = localIdFor(component),
+            packageName = packageName,
+            receiverName = component.className,
+            info = info,
+            protocolVersion = version,
+            capabilities = capabilities,
+            tools = tools,
+            resources = resources,
+            prompts = prompts,
+            lastUpdateTime = lastUpdateTime
+        )
+    }
+
+    fun lastUpdateTime(context: Context, packageName: String): Long {
+        return try {
+            context.packageManager.getPackageInfo(packageName, 0).lastUpdateTime
+        } catch (e: PackageManager.NameNotFoundException) {
+            0L
+        }
+    }
+
+    // invoke a specific tool in an external app, over its bound service if it has one
     fun invokeTool(
         context: Context,
         localId: String,
         tool: String,
-        params: String
-    ): String? {
-        // Look up the packageName and appName from the registry
-        val (packageName, appName) = mcpRegistry[localId] ?: run {
-            Log.e(TAG, "Error: Unknown MCP ID: $localId")
-            return "Error: Unknown MCP ID: $localId"
-        }
-
-        val intent = Intent("com.example.ACTION_MMCP_INVOKE").apply {
-            component = ComponentName(
-                packageName,
-                appName
-            )
-            putExtra("tool", tool)
-            putExtra("params", params)
+        arguments: JSONObject,
+        onProgress: (String) -> Unit = {},
+        isCancelled: () -> Boolean = { false }
+    ): McpResult {
+        val mcp = McpCatalog.find(context, localId)
+            ?: run {
+                Log.e(TAG, "Error: Unknown MCP ID: $localId")
+                return McpResult.Error(McpErrorCode.UNKNOWN_TOOL, "Unknown MCP ID: $localId")
+            }
+
+        McpServiceClient.findService(context, mcp.packageName)?.let { service ->
+            McpServiceClient.invoke(
+                context, service, mcp.protocolVersion, tool, arguments, onProgress, isCancelled
+            )?.let { return it }
+            Log.w(TAG, "Service of ${mcp.packageName} unreachable, falling back to broadcast")
+        }
+
+        // Simple extensions only have the broadcast receiver
+        return sendRequest(context, localId, MobileMcp.ACTION_INVOKE, tool, isCancelled) {
+            putExtra(MobileMcp.EXTRA_TOOL, tool)
+            putExtra(MobileMcp.EXTRA_ARGUMENTS, arguments.toString())
+        }
+    }
+
+    // read a resource an external app exposes
+    fun readResource(context: Context, localId: String, uri: String): McpResult {
+        return sendRequest(context, localId, MobileMcp.ACTION_READ_RESOURCE, uri) {
+            putExtra(MobileMcp.EXTRA_URI, uri)
+        }
+    }
+
+    // get the text of a prompt an external app suggests
+    fun getPrompt(
+        context: Context,
+        localId: String,
+        prompt: String,
+        arguments: JSONObject = JSONObject()
+    ): McpResult {
+        return sendRequest(context, localId, MobileMcp.ACTION_GET_PROMPT, prompt) {
+            putExtra(MobileMcp.EXTRA_PROMPT, prompt)
+            putExtra(MobileMcp.EXTRA_ARGUMENTS, arguments.toString())
         }
+    }
+
+    /**
+     * Prompts of all extensions that can be used as they are, without arguments, to offer as
+     * suggested commands.
+     */
+    fun suggestedPrompts(context: Context): List<ExtensionPrompt> {
+        return McpCatalog.servers(context).flatMap { mcp ->
+            mcp.prompts
+                .filter { prompt -> prompt.arguments.none { it.required } }
+                .map { ExtensionPrompt(mcp.localId, mcp.info.name, it) }
+        }
+    }
 
-        var result: String? = null
+    /**
+     * Sends one request broadcast to the receiver behind [localId] and waits for its result.
+     * [subject] names what is asked for in error messages.
+     */
+    private fun sendRequest(
+        context: Context,
+        localId: String,
+        action: String,
+        subject: String,
+        isCancelled: () -> Boolean = { false },
+        extras: Intent.() -> Unit
+    ): McpResult {
+        val mcp = McpCatalog.find(context, localId)
+            ?: run {
+                Log.e(TAG, "Error: Unknown MCP ID: $localId")
+                return McpResult.Error(McpErrorCode.NOT_FOUND, "Unknown MCP ID: $localId")
+            }
+        val packageName = mcp.packageName
+
+        val intent = Intent(action).apply {
+            component = ComponentName(packageName, mcp.receiverName)
+            // Pin the version agreed during discovery
+            putExtra(MobileMcp.EXTRA_PROTOCOL_VERSION, mcp.protocolVersion)
+            putExtra(MobileMcp.EXTRA_MIN_PROTOCOL_VERSION, mcp.protocolVersion)
+            extras()
+        }
+
+        var result: McpResult? = null
 
         val latch = CountDownLatch(1)
 
         val receiver = object : BroadcastReceiver() {
             override fun onReceive(context: Context?, intent: Intent?) {
-                result = resultData
-                Log.d(TAG, "RESULT FROM MCP ----> " + result)
+                result = try {
+                    getResultExtras(true).getString(MobileMcp.EXTRA_RESULT)
+                        ?.let { McpResult.fromJson(JSONObject(it)) }
+                        ?: McpResult.Error(
+                            McpErrorCode.NO_RESPONSE,
+                            "$packageName did not return a result for $subject"
+                        )
+                } catch (e: Exception) {
+                    McpResult.Error(McpErrorCode.INTERNAL_ERROR, "Invalid result: ${e.message}")
+                }
+                Log.d(TAG, "RESULT FROM MCP ----> $result")
                 latch.countDown()
             }
         }
@@ -165,12 +351,23 @@ object MobileMCP {
         )
 
         try {
-            // Wait for for the app to respond, 10 seconds as why not?
-            latch.await(10, TimeUnit.SECONDS)
+            val deadline = System.currentTimeMillis() + INVOKE_TIMEOUT_MS
+            while (!latch.await(POLL_INTERVAL_MS, TimeUnit.MILLISECONDS)) {
+                if (isCancelled()) {
+                    return McpResult.Error(McpErrorCode.CANCELLED, "Cancelled by the user")
+                }
+                if (System.currentTimeMillis() > deadline) {
+                    return McpResult.Error(
+                        McpErrorCode.TIMEOUT,
+                        "$packageName did not answer within ${INVOKE_TIMEOUT_MS / 1000} seconds"
+                    )
+                }
+            }
         } catch (e: InterruptedException) {
             Log.e(TAG, "Latch interrupted: ${e.message}")
+            return McpResult.Error(McpErrorCode.TIMEOUT, "Interrupted waiting for $packageName")
         }
 
-        return result
+        return result ?: McpResult.Error(McpErrorCode.NO_RESPONSE, "$packageName did not answer")
     }
 }
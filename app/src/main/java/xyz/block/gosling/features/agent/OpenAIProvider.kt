@@ -0,0 +1,139 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.encodeToString
+import okhttp3.MediaType.Companion.toMediaType
+import okhttp3.Request
+import okhttp3.RequestBody.Companion.toRequestBody
+import org.json.JSONObject
+import java.net.HttpURLConnection
+import java.util.concurrent.ConcurrentHashMap
+
+class OpenAIProvider(private val config: ProviderConfig = ProviderConfig()) : LlmProvider {
+    companion object {
+        // Base URLs of compatible servers that rejected stream_options, streamed without usage
+        private val rejectsStreamOptions = ConcurrentHashMap.newKeySet<String>()
+    }
+
+    override val supportsStreaming: Boolean
+        get() = true
+
+    override fun buildRequest(
+        model: AiModel,
+        apiKey: String,
+        messages: List<Message>,
+        tools: List<ToolDefinition>,
+        stream: Boolean
+    ): Request {
+        val baseUrl = config.resolveBaseUrl(ModelProvider.OPENAI)
+        val openAIRequest = OpenAIRequest(
+            model = model.identifier,
+            messages = messages,
+            temperature = if (model.identifier != "o3-mini") 0.1 else null,
+            tools = tools.ifEmpty { null },
+            stream = if (stream) true else null,
+            streamOptions = if (stream && baseUrl !in rejectsStreamOptions) {
+                OpenAIStreamOptions()
+            } else {
+                null
+            }
+        )
+
+        val requestBody = LlmProvider.jsonFormat.encodeToString(openAIRequest)
+
+        return Request.Builder()
+            .url("$baseUrl/chat/completions")
+            .post(requestBody.toRequestBody("application/json".toMediaType()))
+            .apply {
+                // Local servers such as Ollama or LM Studio don't need a key
+                if (apiKey.isNotBlank()) {
+                    addHeader("Authorization", "Bearer $apiKey")
+                }
+            }
+            .addHeaders(config.extraHeaders)
+            .build()
+    }
+
+    override fun parseResponse(body: String): AssistantTurn {
+        val response = JSONObject(body)
+        if (!response.has("choices")) {
+            return AssistantTurn("Unknown response format")
+        }
+
+        val assistantMessage = response.getJSONArray("choices").getJSONObject(0)
+            .getJSONObject("message")
+        val content = assistantMessage.optString("content", "Ok")
+        val toolCalls = assistantMessage.optJSONArray("tool_calls")?.let {
+            List(it.length()) { i -> ToolHandler.fromJson(it.getJSONObject(i)) }
+        }
+        val usage = response.optJSONObject("usage")?.let {
+            TokenUsage(
+                inputTokens = it.getInt("prompt_tokens"),
+                outputTokens = it.getInt("completion_tokens")
+            )
+        }
+
+        return AssistantTurn(content, toolCalls, usage)
+    }
+
+    override fun newStreamDecoder(): LlmStreamDecoder = StreamDecoder()
+
+    override fun classifyError(responseCode: Int, errorResponse: String): AgentException {
+        // Older compatible servers don't know stream_options; the retried turn goes without it
+        if (responseCode == HttpURLConnection.HTTP_BAD_REQUEST &&
+            errorResponse.contains("stream_options")
+        ) {
+            rejectsStreamOptions.add(config.resolveBaseUrl(ModelProvider.OPENAI))
+        }
+        return super.classifyError(responseCode, errorResponse)
+    }
+
+    /**
+     * Chat completion chunks carry text in `delta.content` and tool calls as fragments keyed by
+     * `index`: the first fragment has the id and name, later ones append to the arguments.
+     */
+    private class StreamDecoder : LlmStreamDecoder {
+        private val text = StringBuilder()
+        private val toolCalls = sortedMapOf<Int, PartialToolCall>()
+        private var usage: TokenUsage? = null
+
+        override fun onData(data: String): String? {
+            val chunk = JSONObject(data)
+
+            chunk.optJSONObject("usage")?.let {
+                usage = TokenUsage(
+                    inputTokens = it.optInt("prompt_tokens"),
+                    outputTokens = it.optInt("completion_tokens")
+                )
+            }
+
+            val delta = chunk.optJSONArray("choices")?.optJSONObject(0)?.optJSONObject("delta")
+                ?: return null
+
+            delta.optJSONArray("tool_calls")?.let { fragments ->
+                for (i in 0 until fragments.length()) {
+                    val fragment = fragments.getJSONObject(i)
+                    val partial = toolCalls.getOrPut(fragment.optInt("index", i)) { PartialToolCall() }
+                    fragment.optString("id").takeIf { it.isNotEmpty() }?.let { partial.id = it }
+                    fragment.optJSONObject("function")?.let { function ->
+                        function.optString("name").takeIf { it.isNotEmpty() }
+                            ?.let { partial.name = it }
+                        partial.arguments.append(function.optString("arguments"))
+                    }
+                }
+            }
+
+            val content = if (delta.isNull("content")) "" else delta.optString("content")
+            if (content.isEmpty()) return null
+            text.append(content)
+            return content
+        }
+
+        override fun finish(): AssistantTurn {
+            return AssistantTurn(
+                text = text.toString().ifEmpty { "Ok" },
+                toolCalls = toolCalls.values.map { it.toInternalToolCall() }.ifEmpty { null },
+                usage = usage
+            )
+        }
+    }
+}
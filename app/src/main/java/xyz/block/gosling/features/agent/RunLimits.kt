@@ -0,0 +1,68 @@
+package xyz.block.gosling.features.agent
+
+/**
+ * Why the agent stopped a run before the model said it was done.
+ */
+enum class StopReason(val description: String) {
+    MAX_STEPS("step limit"),
+    MAX_TOKENS("token limit"),
+    MAX_DURATION("time limit"),
+    STUCK("no progress")
+}
+
+/**
+ * Upper bounds for a single run of the agent loop. A value of 0 disables that limit.
+ */
+data class RunLimits(
+    val maxSteps: Int,
+    val maxTokens: Int,
+    val maxDurationSeconds: Int
+) {
+    companion object {
+        // Background runs get tighter limits since nobody is watching them
+        fun defaultFor(triggerType: Agent.TriggerType): RunLimits = when (triggerType) {
+            Agent.TriggerType.MAIN -> RunLimits(maxSteps = 50, maxTokens = 1_000_000, maxDurationSeconds = 600)
+            Agent.TriggerType.ASSISTANT -> RunLimits(maxSteps = 50, maxTokens = 1_000_000, maxDurationSeconds = 600)
+            Agent.TriggerType.IMAGE -> RunLimits(maxSteps = 25, maxTokens = 500_000, maxDurationSeconds = 300)
+            Agent.TriggerType.NOTIFICATION -> RunLimits(maxSteps = 15, maxTokens = 250_000, maxDurationSeconds = 180)
+        }
+    }
+}
+
+/**
+ * Tracks how much of its [RunLimits] a run has used.
+ */
+class RunBudget(
+    private val limits: RunLimits,
+    private val startTime: Long = System.currentTimeMillis()
+) {
+    var steps = 0
+        private set
+    var tokens = 0
+        private set
+
+    fun recordStep(usage: TokenUsage?) {
+        steps++
+        usage?.let { tokens += it.inputTokens + it.outputTokens }
+    }
+
+    fun exceeded(now: Long = System.currentTimeMillis()): StopReason? {
+        return when {
+            limits.maxSteps > 0 && steps >= limits.maxSteps -> StopReason.MAX_STEPS
+            limits.maxTokens > 0 && tokens >= limits.maxTokens -> StopReason.MAX_TOKENS
+            limits.maxDurationSeconds > 0 &&
+                    now - startTime >= limits.maxDurationSeconds * 1000L -> StopReason.MAX_DURATION
+
+            else -> null
+        }
+    }
+
+    fun describe(reason: StopReason): String = when (reason) {
+        StopReason.MAX_STEPS -> "Stopped after $steps steps (${reason.description})"
+        StopReason.MAX_TOKENS -> "Stopped after using $tokens tokens (${reason.description})"
+        StopReason.MAX_DURATION ->
+            "Stopped after ${limits.maxDurationSeconds} seconds (${reason.description})"
+
+        StopReason.STUCK -> "Stopped after $steps steps without making progress"
+    }
+}
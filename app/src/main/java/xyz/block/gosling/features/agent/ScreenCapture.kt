@@ -0,0 +1,99 @@
+package xyz.block.gosling.features.agent
+
+import android.accessibilityservice.AccessibilityService
+import android.graphics.Bitmap
+import android.os.Build
+import android.util.Base64
+import android.view.Display
+import androidx.annotation.RequiresApi
+import java.io.ByteArrayOutputStream
+import java.util.concurrent.CountDownLatch
+import java.util.concurrent.Executors
+import java.util.concurrent.TimeUnit
+
+/**
+ * Captures the screen through the accessibility service and prepares it for the model.
+ */
+object ScreenCapture {
+    private const val CAPTURE_TIMEOUT_MS = 5000L
+    const val DEFAULT_MAX_DIMENSION = 1024
+    const val DEFAULT_JPEG_QUALITY = 70
+
+    private val executor = Executors.newSingleThreadExecutor()
+
+    /**
+     * Takes a screenshot of the default display. Blocks until it is ready and throws with a
+     * readable message if the system refuses.
+     */
+    @RequiresApi(Build.VERSION_CODES.R)
+    fun capture(accessibilityService: AccessibilityService): Bitmap {
+        val latch = CountDownLatch(1)
+        var bitmap: Bitmap? = null
+        var failure: String? = null
+
+        accessibilityService.takeScreenshot(
+            Display.DEFAULT_DISPLAY,
+            executor,
+            object : AccessibilityService.TakeScreenshotCallback {
+                override fun onSuccess(screenshot: AccessibilityService.ScreenshotResult) {
+                    val buffer = screenshot.hardwareBuffer
+                    try {
+                        // Hardware bitmaps can't be scaled or compressed, so copy to memory
+                        bitmap = Bitmap.wrapHardwareBuffer(buffer, screenshot.colorSpace)
+                            ?.copy(Bitmap.Config.ARGB_8888, false)
+                        if (bitmap == null) failure = "could not read the screenshot"
+                    } finally {
+                        buffer.close()
+                        latch.countDown()
+                    }
+                }
+
+                override fun onFailure(errorCode: Int) {
+                    failure = describeError(errorCode)
+                    latch.countDown()
+                }
+            }
+        )
+
+        if (!latch.await(CAPTURE_TIMEOUT_MS, TimeUnit.MILLISECONDS)) {
+            throw IllegalStateException("Timed out taking the screenshot")
+        }
+        return bitmap ?: throw IllegalStateException("Screenshot failed: $failure")
+    }
+
+    /**
+     * Scales [bitmap] down so its longest side is at most [maxDimension]. Smaller images are
+     * returned as they are.
+     */
+    fun downscale(bitmap: Bitmap, maxDimension: Int): Bitmap {
+        val longest = maxOf(bitmap.width, bitmap.height)
+        if (longest <= maxDimension) return bitmap
+
+        val scale = maxDimension.toFloat() / longest
+        return Bitmap.createScaledBitmap(
+            bitmap,
+            (bitmap.width * scale).toInt().coerceAtLeast(1),
+            (bitmap.height * scale).toInt().coerceAtLeast(1),
+            true
+        )
+    }
+
+    fun toDataUrl(bitmap: Bitmap, quality: Int = DEFAULT_JPEG_QUALITY): String {
+        val output = ByteArrayOutputStream()
+        bitmap.compress(Bitmap.CompressFormat.JPEG, quality, output)
+        val base64 = Base64.encodeToString(output.toByteArray(), Base64.NO_WRAP)
+        return "data:image/jpeg;base64,$base64"
+    }
+
+    @RequiresApi(Build.VERSION_CODES.R)
+    private fun describeError(errorCode: Int): String = when (errorCode) {
+        AccessibilityService.ERROR_TAKE_SCREENSHOT_INTERVAL_TIME_SHORT ->
+            "screenshots were requested too quickly, try again in a moment"
+
+        AccessibilityService.ERROR_TAKE_SCREENSHOT_NO_ACCESSIBILITY_ACCESS ->
+            "the accessibility service is not allowed to take screenshots"
+
+        AccessibilityService.ERROR_TAKE_SCREENSHOT_INVALID_DISPLAY -> "invalid display"
+        else -> "internal error $errorCode"
+    }
+}
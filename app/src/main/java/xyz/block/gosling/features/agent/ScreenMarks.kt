@@ -0,0 +1,129 @@
+package xyz.block.gosling.features.agent
+
+import android.graphics.Bitmap
+import android.graphics.Canvas
+import android.graphics.Color
+import android.graphics.Paint
+import android.graphics.Rect
+import android.view.accessibility.AccessibilityNodeInfo
+
+/**
+ * A numbered box drawn on a screenshot around an element the model can interact with.
+ */
+data class ScreenMark(
+    val number: Int,
+    val ref: String,
+    val bounds: Rect,
+    val label: String
+)
+
+/**
+ * Set-of-marks annotation: numbers every interactable element on a screenshot and remembers
+ * which element each number stands for, so the model can point at what it sees in the image.
+ * Only the marks of the latest annotated screenshot resolve.
+ */
+object ScreenMarks {
+    private const val MAX_MARKS = 150
+
+    private val colors = listOf(
+        Color.rgb(230, 25, 75),
+        Color.rgb(60, 180, 75),
+        Color.rgb(0, 130, 200),
+        Color.rgb(245, 130, 48),
+        Color.rgb(145, 30, 180),
+        Color.rgb(0, 128, 128)
+    )
+
+    @Volatile
+    private var marks: Map<Int, ScreenMark> = emptyMap()
+
+    /**
+     * Picks the visible, interactable elements out of [nodes] (by ref, as registered in the
+     * current [ElementRefs] snapshot) and numbers them top to bottom, left to right.
+     */
+    fun collect(nodes: Map<String, AccessibilityNodeInfo>, screen: Rect): List<ScreenMark> {
+        val candidates = nodes.mapNotNull { (ref, node) ->
+            if (!isInteractable(node) || !node.isVisibleToUser) return@mapNotNull null
+            val bounds = Rect().also { node.getBoundsInScreen(it) }
+            if (bounds.isEmpty || !bounds.intersect(screen)) return@mapNotNull null
+            Triple(ref, bounds, labelOf(node))
+        }
+
+        val result = candidates
+            .sortedWith(compareBy({ it.second.top }, { it.second.left }))
+            .take(MAX_MARKS)
+            .mapIndexed { i, (ref, bounds, label) -> ScreenMark(i + 1, ref, bounds, label) }
+
+        marks = result.associateBy { it.number }
+        return result
+    }
+
+    fun resolve(number: Int): ScreenMark {
+        return marks[number] ?: throw ToolArgumentException(
+            field = "mark",
+            message = if (marks.isEmpty()) {
+                "There are no marks. Call takeScreenshot with marks first."
+            } else {
+                "Unknown mark $number. Marks go from 1 to ${marks.size}."
+            }
+        )
+    }
+
+    /**
+     * Draws the marks onto a copy of [screenshot], which must be at screen resolution.
+     */
+    fun draw(screenshot: Bitmap, marks: List<ScreenMark>): Bitmap {
+        val annotated = screenshot.copy(Bitmap.Config.ARGB_8888, true)
+        val canvas = Canvas(annotated)
+
+        val scale = maxOf(annotated.width, annotated.height) / 1000f
+        val boxPaint = Paint().apply {
+            style = Paint.Style.STROKE
+            strokeWidth = 3f * scale
+        }
+        val labelPaint = Paint().apply { style = Paint.Style.FILL }
+        val textPaint = Paint(Paint.ANTI_ALIAS_FLAG).apply {
+            color = Color.WHITE
+            textSize = 14f * scale
+            isFakeBoldText = true
+        }
+
+        for (mark in marks) {
+            val color = colors[mark.number % colors.size]
+            boxPaint.color = color
+            labelPaint.color = color
+            canvas.drawRect(mark.bounds, boxPaint)
+
+            // Number in a filled tag at the top left corner of the box
+            val text = mark.number.toString()
+            val padding = 3f * scale
+            val tagWidth = textPaint.measureText(text) + padding * 2
+            val tagHeight = textPaint.textSize + padding * 2
+            val left = mark.bounds.left.toFloat()
+            val top = mark.bounds.top.toFloat()
+            canvas.drawRect(left, top, left + tagWidth, top + tagHeight, labelPaint)
+            canvas.drawText(text, left + padding, top + tagHeight - padding * 1.5f, textPaint)
+        }
+
+        return annotated
+    }
+
+    fun legend(marks: List<ScreenMark>): String {
+        return marks.joinToString("\n") { mark ->
+            "${mark.number}: [${mark.ref}] ${mark.label} " +
+                    "(${mark.bounds.centerX()},${mark.bounds.centerY()})"
+        }
+    }
+
+    private fun isInteractable(node: AccessibilityNodeInfo): Boolean {
+        return node.isClickable || node.isLongClickable || node.isEditable ||
+                node.isScrollable || node.isCheckable
+    }
+
+    private fun labelOf(node: AccessibilityNodeInfo): String {
+        val type = node.className?.toString()?.substringAfterLast('.') ?: "View"
+        val text = node.text?.toString()?.takeIf { it.isNotBlank() }
+            ?: node.contentDescription?.toString()?.takeIf { it.isNotBlank() }
+        return if (text != null) "$type \"${text.take(40)}\"" else type
+    }
+}
@@ -0,0 +1,176 @@
+package xyz.block.gosling.features.agent
+
+import android.accessibilityservice.AccessibilityService
+import android.os.SystemClock
+import android.view.accessibility.AccessibilityEvent
+import java.util.concurrent.TimeUnit
+import java.util.concurrent.locks.ReentrantLock
+import kotlin.concurrent.withLock
+
+/**
+ * What [ScreenWaiter.waitFor] waits for. [Idle] is met once no window has changed for
+ * [Idle.quietMs]; with [Idle.afterChange] it first waits for the screen to start changing.
+ */
+sealed class WaitCondition {
+    data class Text(val text: String) : WaitCondition()
+    data class ViewId(val id: String) : WaitCondition()
+    data class Package(val packageName: String) : WaitCondition()
+    data class Idle(val quietMs: Long = DEFAULT_QUIET_MS, val afterChange: Boolean = false) :
+        WaitCondition()
+
+    companion object {
+        const val DEFAULT_QUIET_MS = 500L
+    }
+}
+
+data class WaitResult(
+    val satisfied: Boolean,
+    val elapsedMs: Long,
+    val cancelled: Boolean = false
+)
+
+/**
+ * Blocks until the screen reaches a condition, waking up on window change events that
+ * GoslingAccessibilityService forwards through [onAccessibilityEvent] rather than polling on a
+ * fixed delay.
+ */
+object ScreenWaiter {
+    // How long a tapped or focused field gets to take input focus, and how often it is checked
+    private const val FOCUS_TIMEOUT_MS = 1000L
+    private const val FOCUS_POLL_MS = 50L
+
+    private val lock = ReentrantLock()
+    private val changed = lock.newCondition()
+    private var lastChangeTime = 0L
+    private var changeCount = 0L
+
+    fun onAccessibilityEvent(event: AccessibilityEvent) {
+        when (event.eventType) {
+            AccessibilityEvent.TYPE_WINDOW_CONTENT_CHANGED,
+            AccessibilityEvent.TYPE_WINDOW_STATE_CHANGED,
+            AccessibilityEvent.TYPE_WINDOWS_CHANGED -> lock.withLock {
+                lastChangeTime = SystemClock.uptimeMillis()
+                changeCount++
+                changed.signalAll()
+            }
+        }
+    }
+
+    /**
+     * Waits until all [conditions] hold at the same time or [timeoutMs] passes.
+     */
+    fun waitFor(
+        accessibilityService: AccessibilityService,
+        conditions: List<WaitCondition>,
+        timeoutMs: Long,
+        isCancelled: () -> Boolean = { false }
+    ): WaitResult {
+        val start = SystemClock.uptimeMillis()
+        val changesAtStart = lock.withLock { changeCount }
+
+        while (true) {
+            val now = SystemClock.uptimeMillis()
+            val elapsed = now - start
+            if (isCancelled()) return WaitResult(false, elapsed, cancelled = true)
+
+            val (lastChange, changes) = lock.withLock { lastChangeTime to changeCount }
+            val met = conditions.all { condition ->
+                isMet(accessibilityService, condition, now, start, lastChange, changes > changesAtStart)
+            }
+            if (met) return WaitResult(true, elapsed)
+
+            val remaining = timeoutMs - elapsed
+            if (remaining <= 0) return WaitResult(false, elapsed)
+
+            lock.withLock {
+                // Wake up on the next change, or in time to notice the screen went quiet
+                if (changeCount == changes) {
+                    val waitMs = remaining.coerceAtMost(nextCheckMs(conditions)).coerceAtLeast(1)
+                    changed.await(waitMs, TimeUnit.MILLISECONDS)
+                }
+            }
+        }
+    }
+
+    /**
+     * Waits for the screen to react to an action and then settle, giving up after [timeoutMs].
+     * Used by tools in place of a fixed sleep after launching something.
+     */
+    fun waitForSettled(
+        accessibilityService: AccessibilityService?,
+        timeoutMs: Long,
+        isCancelled: () -> Boolean = { false }
+    ): WaitResult {
+        if (accessibilityService == null) {
+            Thread.sleep(timeoutMs)
+            return WaitResult(true, timeoutMs)
+        }
+        return waitFor(
+            accessibilityService,
+            listOf(WaitCondition.Idle(afterChange = true)),
+            timeoutMs,
+            isCancelled
+        )
+    }
+
+    /**
+     * Waits for focus to move after tapping or focusing a field, until [find] returns the node
+     * that should now have it or [timeoutMs] passes. Focus changes don't always come with a
+     * window change, so this also checks every [FOCUS_POLL_MS].
+     */
+    fun <T : Any> waitForFocus(
+        timeoutMs: Long = FOCUS_TIMEOUT_MS,
+        isCancelled: () -> Boolean = { false },
+        find: () -> T?
+    ): T? {
+        val start = SystemClock.uptimeMillis()
+
+        while (true) {
+            find()?.let { return it }
+
+            val remaining = timeoutMs - (SystemClock.uptimeMillis() - start)
+            if (remaining <= 0 || isCancelled()) return null
+
+            lock.withLock {
+                changed.await(remaining.coerceAtMost(FOCUS_POLL_MS), TimeUnit.MILLISECONDS)
+            }
+        }
+    }
+
+    private fun nextCheckMs(conditions: List<WaitCondition>): Long {
+        return conditions.filterIsInstance<WaitCondition.Idle>()
+            .minOfOrNull { it.quietMs }
+            ?: WaitCondition.DEFAULT_QUIET_MS
+    }
+
+    private fun isMet(
+        accessibilityService: AccessibilityService,
+        condition: WaitCondition,
+        now: Long,
+        start: Long,
+        lastChange: Long,
+        changedSinceStart: Boolean
+    ): Boolean {
+        return when (condition) {
+            is WaitCondition.Idle -> {
+                if (condition.afterChange && !changedSinceStart) return false
+                now - maxOf(lastChange, start) >= condition.quietMs
+            }
+
+            is WaitCondition.Text -> roots(accessibilityService).any { root ->
+                !root.findAccessibilityNodeInfosByText(condition.text).isNullOrEmpty()
+            }
+
+            is WaitCondition.ViewId -> roots(accessibilityService).any { root ->
+                !root.findAccessibilityNodeInfosByViewId(condition.id).isNullOrEmpty()
+            }
+
+            is WaitCondition.Package -> roots(accessibilityService).any { root ->
+                root.packageName?.toString() == condition.packageName
+            }
+        }
+    }
+
+    private fun roots(accessibilityService: AccessibilityService) =
+        UiWindows.list(accessibilityService).mapNotNull { it.root }
+}
@@ -0,0 +1,173 @@
+package xyz.block.gosling.features.agent
+
+import org.json.JSONArray
+import org.json.JSONException
+import org.json.JSONObject
+import org.json.JSONTokener
+
+data class ArgumentProblem(
+    val field: String,
+    val message: String,
+    val expected: String? = null
+)
+
+data class ArgumentValidation(
+    val arguments: JSONObject,
+    val problems: List<ArgumentProblem>
+) {
+    val isValid: Boolean
+        get() = problems.isEmpty()
+}
+
+/**
+ * Checks tool call arguments against the tool's schema before it runs. Values that are only
+ * wrong in representation are coerced - models regularly send numbers and booleans as strings,
+ * and Gemini always does for integers since its schema declares them as strings. Everything
+ * else is reported as an [ArgumentProblem] naming the field and the type it should have.
+ */
+object ToolArgumentValidator {
+    fun validate(arguments: JSONObject, schema: ToolParametersObject): ArgumentValidation {
+        val problems = mutableListOf<ArgumentProblem>()
+        val coerced = validateObject(arguments, schema.properties, schema.required, "", problems)
+        return ArgumentValidation(coerced, problems)
+    }
+
+    private fun validateObject(
+        value: JSONObject,
+        properties: Map<String, ToolParameter>,
+        required: List<String>,
+        path: String,
+        problems: MutableList<ArgumentProblem>
+    ): JSONObject {
+        val result = JSONObject()
+
+        for (name in value.keys()) {
+            val field = "$path$name"
+            val parameter = properties[name]
+            if (parameter == null) {
+                problems.add(
+                    ArgumentProblem(
+                        field = field,
+                        message = "Unknown argument '$field'. Accepted arguments: " +
+                                properties.keys.joinToString().ifEmpty { "none" }
+                    )
+                )
+                continue
+            }
+            if (value.isNull(name)) continue
+
+            validateValue(value.get(name), parameter, field, problems)?.let { result.put(name, it) }
+        }
+
+        for (name in required) {
+            if (!result.has(name) && (!value.has(name) || value.isNull(name))) {
+                problems.add(
+                    ArgumentProblem(
+                        field = "$path$name",
+                        message = "Missing required argument '$path$name'",
+                        expected = properties[name]?.type
+                    )
+                )
+            }
+        }
+
+        return result
+    }
+
+    private fun validateValue(
+        value: Any,
+        parameter: ToolParameter,
+        field: String,
+        problems: MutableList<ArgumentProblem>
+    ): Any? {
+        val coerced = when (parameter.type) {
+            "string" -> when (value) {
+                is String -> value
+                is Number, is Boolean -> value.toString()
+                else -> null
+            }
+
+            "integer" -> when (value) {
+                is Int, is Long -> value
+                is Number -> value.toDouble().takeIf { it % 1.0 == 0.0 }?.toLong()
+                is String -> value.trim().let { text ->
+                    text.toLongOrNull()
+                        ?: text.toDoubleOrNull()?.takeIf { it % 1.0 == 0.0 }?.toLong()
+                }
+
+                else -> null
+            }
+
+            "number" -> when (value) {
+                is Number -> value
+                is String -> value.trim().toDoubleOrNull()
+                else -> null
+            }
+
+            "boolean" -> when (value) {
+                is Boolean -> value
+                is String -> value.trim().lowercase().toBooleanStrictOrNull()
+                else -> null
+            }
+
+            "object" -> (value as? JSONObject ?: parseJson(value) as? JSONObject)?.let {
+                // Without declared properties any object goes
+                if (parameter.properties == null) return@let it
+                validateObject(
+                    it,
+                    parameter.properties.orEmpty(),
+                    parameter.required.orEmpty(),
+                    "$field.",
+                    problems
+                )
+            }
+
+            "array" -> (value as? JSONArray ?: parseJson(value) as? JSONArray)?.let { array ->
+                val items = parameter.items ?: return@let array
+                JSONArray().apply {
+                    for (i in 0 until array.length()) {
+                        if (array.isNull(i)) continue
+                        validateValue(array.get(i), items, "$field[$i]", problems)?.let { put(it) }
+                    }
+                }
+            }
+
+            else -> value
+        }
+
+        if (coerced == null) {
+            problems.add(
+                ArgumentProblem(
+                    field = field,
+                    message = "'$field' must be of type ${parameter.type}, got ${JSONObject.wrap(value)}",
+                    expected = parameter.type
+                )
+            )
+            return null
+        }
+
+        val allowed = parameter.enum
+        if (allowed != null && coerced.toString() !in allowed) {
+            problems.add(
+                ArgumentProblem(
+                    field = field,
+                    message = "'$field' must be one of ${allowed.joinToString()}, got '$coerced'",
+                    expected = allowed.joinToString("|")
+                )
+            )
+            return null
+        }
+
+        return coerced
+    }
+
+    // Some models send nested objects and arrays as JSON encoded strings
+    private fun parseJson(value: Any): Any? {
+        if (value !is String) return null
+        return try {
+            JSONTokener(value).nextValue()
+        } catch (e: JSONException) {
+            null
+        }
+    }
+}
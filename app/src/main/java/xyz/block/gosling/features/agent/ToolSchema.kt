@@ -0,0 +1,196 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.json.JsonElement
+import kotlinx.serialization.json.JsonPrimitive
+import org.json.JSONArray
+import org.json.JSONObject
+
+/**
+ * Builds the JSON Schema for a tool's arguments:
+ *
+ * ```
+ * toolParameters {
+ *     integer("x", "X coordinate to click")
+ *     string("direction", "Where to scroll", enum = listOf("up", "down"))
+ *     integer("duration", "Duration in milliseconds", default = 300)
+ * }
+ * ```
+ *
+ * A parameter with a default is optional unless stated otherwise.
+ */
+class ToolSchemaBuilder {
+    private val properties = linkedMapOf<String, ToolParameter>()
+    private val required = mutableListOf<String>()
+
+    fun string(
+        name: String,
+        description: String,
+        required: Boolean = true,
+        enum: List<String>? = null,
+        default: String? = null
+    ) = add(
+        name,
+        ToolParameter(
+            type = "string",
+            description = description,
+            enum = enum,
+            default = default?.let { JsonPrimitive(it) }
+        ),
+        required && default == null
+    )
+
+    fun integer(
+        name: String,
+        description: String,
+        required: Boolean = true,
+        default: Int? = null
+    ) = add(
+        name,
+        ToolParameter(
+            type = "integer",
+            description = description,
+            default = default?.let { JsonPrimitive(it) }
+        ),
+        required && default == null
+    )
+
+    fun number(
+        name: String,
+        description: String,
+        required: Boolean = true,
+        default: Double? = null
+    ) = add(
+        name,
+        ToolParameter(
+            type = "number",
+            description = description,
+            default = default?.let { JsonPrimitive(it) }
+        ),
+        required && default == null
+    )
+
+    fun boolean(
+        name: String,
+        description: String,
+        required: Boolean = true,
+        default: Boolean? = null
+    ) = add(
+        name,
+        ToolParameter(
+            type = "boolean",
+            description = description,
+            default = default?.let { JsonPrimitive(it) }
+        ),
+        required && default == null
+    )
+
+    fun array(
+        name: String,
+        description: String,
+        items: ToolParameter,
+        required: Boolean = true
+    ) = add(
+        name,
+        ToolParameter(type = "array", description = description, items = items),
+        required
+    )
+
+    fun obj(
+        name: String,
+        description: String,
+        required: Boolean = true,
+        block: ToolSchemaBuilder.() -> Unit
+    ) = add(name, objectParameter(description, block), required)
+
+    fun build(): ToolParametersObject {
+        return ToolParametersObject(properties = properties.toMap(), required = required.toList())
+    }
+
+    private fun add(name: String, parameter: ToolParameter, isRequired: Boolean) {
+        properties[name] = parameter
+        if (isRequired) required.add(name)
+    }
+}
+
+fun toolParameters(block: ToolSchemaBuilder.() -> Unit = {}): ToolParametersObject {
+    return ToolSchemaBuilder().apply(block).build()
+}
+
+/**
+ * Schema for a nested object, usable as a property or as the [ToolParameter.items] of an array.
+ */
+fun objectParameter(description: String? = null, block: ToolSchemaBuilder.() -> Unit): ToolParameter {
+    val schema = toolParameters(block)
+    return ToolParameter(
+        type = "object",
+        description = description,
+        properties = schema.properties,
+        required = schema.required
+    )
+}
+
+/**
+ * Reads a JSON Schema object, as declared by Mobile MCP extensions, into the same structure
+ * the built-in tools use. Only the parts models understand are kept: types, descriptions,
+ * enums, defaults, required lists, array items and nested objects.
+ */
+fun jsonSchemaToParameters(schema: JSONObject): ToolParametersObject {
+    val parameter = jsonSchemaToParameter(schema)
+    return ToolParametersObject(
+        properties = parameter.properties.orEmpty(),
+        required = parameter.required.orEmpty()
+    )
+}
+
+private fun jsonSchemaToParameter(schema: JSONObject): ToolParameter {
+    val properties = schema.optJSONObject("properties")?.let { json ->
+        json.keys().asSequence()
+            .mapNotNull { name -> json.optJSONObject(name)?.let { name to jsonSchemaToParameter(it) } }
+            .toMap(linkedMapOf())
+    }
+    val required = schema.optJSONArray("required")?.let { array ->
+        (0 until array.length()).map { array.optString(it) }
+            .filter { properties?.containsKey(it) == true }
+    }
+
+    return ToolParameter(
+        type = schemaType(schema),
+        description = schema.optString("description").ifEmpty { null },
+        enum = schema.optJSONArray("enum")?.let { array ->
+            (0 until array.length()).map { array.get(it).toString() }
+        },
+        items = schema.optJSONObject("items")?.let { jsonSchemaToParameter(it) },
+        properties = properties,
+        required = required?.ifEmpty { null },
+        default = when (val default = schema.opt("default")) {
+            is String -> JsonPrimitive(default)
+            is Boolean -> JsonPrimitive(default)
+            is Number -> JsonPrimitive(default)
+            else -> null
+        }
+    )
+}
+
+/**
+ * The type of a schema, which may be a list such as ["string", "null"] or left out.
+ */
+private fun schemaType(schema: JSONObject): String {
+    val declared = when (val type = schema.opt("type")) {
+        is String -> type
+        is JSONArray -> (0 until type.length()).map { type.optString(it) }.firstOrNull { it != "null" }
+        else -> null
+    }
+    return declared ?: when {
+        schema.has("properties") -> "object"
+        schema.has("items") -> "array"
+        else -> "string"
+    }
+}
+
+internal fun JsonElement.toPlainValue(): Any? {
+    if (this !is JsonPrimitive) return null
+    if (isString) return content
+    return content.toBooleanStrictOrNull()
+        ?: content.toLongOrNull()
+        ?: content.toDoubleOrNull()
+}
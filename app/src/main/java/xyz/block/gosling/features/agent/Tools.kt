@@ -4,9 +4,12 @@ import android.Manifest
 import android.accessibilityservice.AccessibilityService
 import android.accessibilityservice.GestureDescription
 import android.app.SearchManager
+import android.content.ClipData
+import android.content.ClipboardManager
 import android.content.Context
 import android.content.Intent
 import android.content.pm.PackageManager
+import android.graphics.Bitmap
 import android.graphics.Path
 import android.graphics.Rect
 import android.os.Build
@@ -19,7 +22,9 @@ import androidx.core.content.ContextCompat
 import androidx.core.net.toUri
 import org.json.JSONObject
 import xyz.block.gosling.features.overlay.OverlayService
+import xyz.block.gosling.mmcp.McpResult
 import java.util.Locale
+import java.util.concurrent.ConcurrentHashMap
 import java.util.concurrent.CountDownLatch
 import java.util.concurrent.TimeUnit
 import java.util.concurrent.atomic.AtomicLong
@@ -28,40 +33,19 @@ import xyz.block.gosling.features.agent.Content
 import xyz.block.gosling.features.agent.Message
 import xyz.block.gosling.features.agent.Conversation
 
-@Target(AnnotationTarget.FUNCTION)
-@Retention(AnnotationRetention.RUNTIME)
-annotation class Tool(
-    val name: String,
-    val description: String,
-    val parameters: Array<ParameterDef> = [],
-    val requiresContext: Boolean = false,
-    val requiresAccessibility: Boolean = false
-)
-
-@Target(AnnotationTarget.ANNOTATION_CLASS)
-@Retention(AnnotationRetention.RUNTIME)
-annotation class ParameterDef(
-    val name: String,
-    val type: String,
-    val description: String,
-    val required: Boolean = true
-)
-
 data class InternalToolCall(
     val toolId: String,
     val name: String,
     val arguments: JSONObject
 )
 
-sealed class SerializableToolDefinitions {
-    data class OpenAITools(val definitions: List<ToolDefinition>) : SerializableToolDefinitions()
-    data class GeminiTools(val tools: List<GeminiTool>) : SerializableToolDefinitions()
-}
-
 private const val appLoadTimeWait: Long = 2500
+private const val MAX_WAIT_MS = 30_000
 private const val coordinateHint =
     "(coordinates are of form: [x-coordinate of the left edge, y-coordinate of the top edge, " +
-            "x-coordinate of the right edge, y-coordinate of the bottom edge])"
+            "x-coordinate of the right edge, y-coordinate of the bottom edge]; " +
+            "the ref in front of each element, e.g. [e12], can be passed to clickElement, " +
+            "longPressElement, setTextOnElement and scrollElement)"
 
 object ToolHandler {
     private val toolCallCounter = AtomicLong(0)
@@ -73,7 +57,8 @@ object ToolHandler {
 
     private fun performGesture(
         gesture: GestureDescription,
-        accessibilityService: AccessibilityService
+        accessibilityService: AccessibilityService,
+        expectedDurationMs: Int = 0
     ): Boolean {
         var gestureResult = false
         val countDownLatch = CountDownLatch(1)
@@ -95,7 +80,8 @@ object ToolHandler {
         )
 
         try {
-            countDownLatch.await(2000, TimeUnit.MILLISECONDS)
+            // Long gestures only report back once they are done
+            countDownLatch.await(2000L + expectedDurationMs, TimeUnit.MILLISECONDS)
         } catch (e: InterruptedException) {
             return false
         }
@@ -128,58 +114,263 @@ object ToolHandler {
     }
 
     /* not operational
-    @Tool(
+    val recentApps = object : AgentTool(
         name = "recentApps",
-        description = "list recently used apps",
-        parameters = [],
-        requiresContext = true
-    )
-    fun recentApps(context: Context, args: JSONObject): String {
-        if (!AppUsageStats.hasPermission(context)) {
-            return "Don't have permission to collect app stats, consult app settings to correct this."
+        description = "list recently used apps"
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            if (!AppUsageStats.hasPermission(context)) {
+                return "Don't have permission to collect app stats, consult app settings to correct this."
+            }
+            return AppUsageStats.getRecentApps(context, limit = 10).joinToString { ", " }
         }
-        return AppUsageStats.getRecentApps(context, limit = 10).joinToString { ", " }
     }
 
-    @Tool(
+    val frequentlyUsedApps = object : AgentTool(
         name = "frequentlyUsedApps",
-        description = "list apps that are often used",
-        parameters = [],
-        requiresContext = true
-    )
-    fun frequentlyUsedApps(context: Context): String {
-        if (!AppUsageStats.hasPermission(context)) {
-            return "Don't have permission to collect app stats, consult app settings to correct this."
+        description = "list apps that are often used"
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            if (!AppUsageStats.hasPermission(context)) {
+                return "Don't have permission to collect app stats, consult app settings to correct this."
+            }
+            return AppUsageStats.getFrequentApps(context, limit = 20).joinToString { ", " }
         }
-        return AppUsageStats.getFrequentApps(context, limit = 20).joinToString { ", " }
     }
     */
 
 
-    @Tool(
+    val getUiHierarchy = object : AgentTool(
         name = "getUiHierarchy",
         description = "call this to show UI elements with their properties and locations on screen " +
-                "in a hierarchical structure. If the results from this or other tools don't seem" +
-                "complete, call getUiHierarchy again to give the system time to finish. But not " +
-                "more than twice",
-        parameters = [],
+                "in a hierarchical structure. If the results from this or other tools don't seem " +
+                "complete, call waitFor to let the app finish rendering and then call this again. The output starts with the windows on screen (dialogs, " +
+                "keyboard, notification shade, split-screen apps); by default the active window " +
+                "and any permission prompt are shown, pass window_id to look at another one.",
+        parameters = toolParameters {
+            integer(
+                "window_id",
+                "Id of the window to show, from the window list of a previous call",
+                required = false
+            )
+            boolean("all_windows", "Show the elements of every window on screen", default = false)
+            string(
+                "format",
+                "text is compact and easy to read, json is a typed tree with the state of each element",
+                enum = listOf("text", "json"),
+                default = "text"
+            )
+        },
+        requiresAccessibility = true
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            return describeUiHierarchy(
+                ctx.accessibilityService,
+                windowId = args.optInt("window_id"),
+                allWindows = args.getBoolean("all_windows"),
+                format = args.getString("format")
+            )
+        }
+    }
+
+    val getUiChanges = object : AgentTool(
+        name = "getUiChanges",
+        description = "Show only the UI elements that were added, removed or changed since the " +
+                "screen was last read, as JSON keyed by element ref. Much shorter than " +
+                "getUiHierarchy after small actions like a click or typing. Selects windows the " +
+                "same way getUiHierarchy does.",
+        parameters = toolParameters {
+            integer(
+                "window_id",
+                "Id of the window to compare, from the window list of getUiHierarchy",
+                required = false
+            )
+            boolean("all_windows", "Compare every window on screen", default = false)
+        },
+        requiresAccessibility = true
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            return describeUiChanges(
+                ctx.accessibilityService,
+                windowId = args.optInt("window_id"),
+                allWindows = args.getBoolean("all_windows")
+            )
+        }
+    }
+
+    val waitFor = object : AgentTool(
+        name = "waitFor",
+        description = "Wait until a text, resource id or app appears on screen, or if none is " +
+                "given until the screen stops changing. Returns as soon as the condition is met. " +
+                "Use this instead of reading the hierarchy repeatedly while an app is loading.",
+        parameters = toolParameters {
+            string("text", "Text that should appear on screen", required = false)
+            string("id", "Resource id of an element that should appear", required = false)
+            string("package_name", "Package name of the app that should be on screen", required = false)
+            integer(
+                "quiet_ms",
+                "How long the screen must stay unchanged to count as idle, in milliseconds",
+                default = WaitCondition.DEFAULT_QUIET_MS.toInt()
+            )
+            integer("timeout_ms", "Longest time to wait in milliseconds, at most 30000", default = 5000)
+        },
         requiresAccessibility = true
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val conditions = listOfNotNull(
+                args.optString("text")?.let { WaitCondition.Text(it) },
+                args.optString("id")?.let { WaitCondition.ViewId(it) },
+                args.optString("package_name")?.let { WaitCondition.Package(it) }
+            ).ifEmpty {
+                listOf(WaitCondition.Idle(quietMs = args.getInt("quiet_ms").toLong()))
+            }
+            val timeoutMs = args.getInt("timeout_ms").coerceIn(0, MAX_WAIT_MS).toLong()
+
+            val result = ScreenWaiter.waitFor(ctx.accessibilityService, conditions, timeoutMs) {
+                ctx.isCancelled
+            }
+
+            val target = conditions.joinToString(" and ") { condition ->
+                when (condition) {
+                    is WaitCondition.Text -> "text \"${condition.text}\""
+                    is WaitCondition.ViewId -> "id ${condition.id}"
+                    is WaitCondition.Package -> "app ${condition.packageName}"
+                    is WaitCondition.Idle -> "the screen to settle"
+                }
+            }
+            return when {
+                result.cancelled -> "Cancelled while waiting for $target"
+                result.satisfied -> "Done waiting for $target after ${result.elapsedMs} ms"
+                else -> "Timed out after ${result.elapsedMs} ms waiting for $target. " +
+                        "Call getUiHierarchy to see what is on screen."
+            }
+        }
+    }
+
+    private class WindowCapture(
+        val windows: List<UiWindow>,
+        val selected: List<UiWindow>,
+        val activeRoot: AccessibilityNodeInfo,
+        val snapshot: ElementRefs.Snapshot
     )
-    fun getUiHierarchy(accessibilityService: AccessibilityService, args: JSONObject): String {
+
+    /**
+     * Picks the windows to show and starts a new element snapshot for them. Returns null when
+     * there is nothing on screen to read.
+     */
+    private fun captureWindows(
+        accessibilityService: AccessibilityService,
+        windowId: Int?,
+        allWindows: Boolean
+    ): WindowCapture? {
+        val windows = UiWindows.list(accessibilityService)
+        if (windows.isEmpty()) return null
+
+        val selected = when {
+            allWindows -> windows
+            windowId != null -> listOf(
+                windows.find { it.id == windowId }
+                    ?: throw ToolArgumentException(
+                        field = "window_id",
+                        message = "No window with id $windowId. Windows on screen: " +
+                                windows.joinToString { it.id.toString() }
+                    )
+            )
+
+            // Permission prompts block the app underneath, so always show them
+            else -> windows.filter { it.isActive || it.isPermissionPrompt }
+                .ifEmpty { windows.take(1) }
+        }
+
+        val activeRoot = windows.find { it.isActive }?.root
+            ?: selected.firstNotNullOfOrNull { it.root }
+            ?: return null
+        return WindowCapture(windows, selected, activeRoot, ElementRefs.beginSnapshot(activeRoot))
+    }
+
+    fun describeUiHierarchy(
+        accessibilityService: AccessibilityService,
+        windowId: Int? = null,
+        allWindows: Boolean = false,
+        format: String = "text"
+    ): String {
         return try {
-            val activeWindow = accessibilityService.rootInActiveWindow
+            val capture = captureWindows(accessibilityService, windowId, allWindows)
                 ?: return "ERROR: No active window found"
 
-            val appInfo = "App: ${activeWindow.packageName}"
-            val hierarchy = buildCompactHierarchy(activeWindow)
-            //Log.d(TAG, "build compact: $hierarchy")
-            "$appInfo $coordinateHint\n$hierarchy"
+            if (format == "json") {
+                // Every window is listed, only the selected ones with their elements
+                return UiTree.encode(capture.windows.map { window ->
+                    val shown = if (window in capture.selected) window else window.copy(root = null)
+                    UiTree.build(shown, capture.snapshot)
+                })
+            }
+
+            buildString {
+                append("Windows (top-most first):\n")
+                capture.windows.forEach { append("  ${it.label}\n") }
+                append("App: ${capture.activeRoot.packageName} $coordinateHint\n")
+                for (window in capture.selected) {
+                    append("\n=== ${window.label} ===\n")
+                    val root = window.root
+                    if (root == null) {
+                        append("(content not available)\n")
+                        continue
+                    }
+                    append(buildCompactHierarchy(root, capture.snapshot, window.id).trim('\n'))
+                    append("\n")
+                }
+            }
+        } catch (e: ToolArgumentException) {
+            throw e
         } catch (e: Exception) {
             "ERROR: Failed to get UI hierarchy: ${e.message}"
         }
     }
 
-    fun buildCompactHierarchy(node: AccessibilityNodeInfo, depth: Int = 0): String {
+    fun describeUiChanges(
+        accessibilityService: AccessibilityService,
+        windowId: Int? = null,
+        allWindows: Boolean = false
+    ): String {
+        return try {
+            val capture = captureWindows(accessibilityService, windowId, allWindows)
+                ?: return "ERROR: No active window found"
+
+            val trees = capture.selected.map { UiTree.build(it, capture.snapshot) }
+            val previous = ElementRefs.previousStates()
+            if (previous.isEmpty()) {
+                return "No earlier snapshot of this app to compare with, this is the full " +
+                        "hierarchy:\n${UiTree.encode(trees)}"
+            }
+
+            val diff = UiTree.diff(previous, ElementRefs.currentStates())
+            if (diff.isEmpty) "No changes since the screen was last read" else UiTree.encode(diff)
+        } catch (e: ToolArgumentException) {
+            throw e
+        } catch (e: Exception) {
+            "ERROR: Failed to get UI changes: ${e.message}"
+        }
+    }
+
+    fun buildCompactHierarchy(
+        root: AccessibilityNodeInfo,
+        snapshot: ElementRefs.Snapshot = ElementRefs.beginSnapshot(root),
+        windowId: Int = root.windowId
+    ): String {
+        return buildCompactNode(root, 0, UiTree.rootPath(windowId, root), snapshot)
+    }
+
+    private fun buildCompactNode(
+        node: AccessibilityNodeInfo,
+        depth: Int,
+        path: String,
+        snapshot: ElementRefs.Snapshot
+    ): String {
         try {
             val bounds = Rect().also { node.getBoundsInScreen(it) }
             val attributes = mutableListOf<String>()
@@ -210,7 +401,7 @@ object ToolHandler {
             val hasSingleChild = node.childCount == 1
 
             if (hasNoAttributes && hasSingleChild && node.getChild(0) != null) {
-                return buildCompactHierarchy(node.getChild(0), depth)
+                return buildCompactNode(node.getChild(0), depth, "$path.0", snapshot)
             }
 
             // Get the node type
@@ -240,7 +431,13 @@ object ToolHandler {
             // Build the node line, or set to empty string if filtered
             val indent = "".repeat(depth)
             val attrStr = if (attributes.isNotEmpty()) " " + attributes.joinToString(" ") else ""
-            val nodeLine = if (shouldFilter) "" else "$indent$attrStr $boundsStr"
+            // Hidden nodes still get a ref so getUiChanges sees the same elements either way
+            val ref = if (!shouldFilter || UiTree.isMeaningful(node)) {
+                snapshot.register(node, path)
+            } else {
+                null
+            }
+            val nodeLine = if (shouldFilter) "" else "$indent[$ref]$attrStr $boundsStr"
 
             // Process children if any
             val childrenStr = if (node.childCount > 0) {
@@ -249,7 +446,8 @@ object ToolHandler {
                     val childNode = node.getChild(i)
                     if (childNode != null) {
                         try {
-                            val childResult = buildCompactHierarchy(childNode, depth + 1)
+                            val childResult =
+                                buildCompactNode(childNode, depth + 1, "$path.$i", snapshot)
                             if (childResult.isNotEmpty()) {
                                 childrenLines.add(childResult)
                             }
@@ -270,394 +468,398 @@ object ToolHandler {
         }
     }
 
-    @Tool(
+    val home = object : AgentTool(
         name = "home",
-        description = "Press the home button on the device"
-    )
-    fun home(args: JSONObject): String {
-        Runtime.getRuntime().exec(arrayOf("input", "keyevent", "KEYCODE_HOME"))
-        return "Pressed home button"
+        description = "Press the home button on the device",
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            Runtime.getRuntime().exec(arrayOf("input", "keyevent", "KEYCODE_HOME"))
+            return "Pressed home button"
+        }
     }
 
-    @Tool(
-        name = "startApp",
-        description = "Start an application by its package name",
-        parameters = [
-            ParameterDef(
-                name = "package_name",
-                type = "string",
-                description = "Full package name of the app to start"
-            )
-        ],
-        requiresContext = true
+    private const val SYSTEM_UI_PACKAGE = "com.android.systemui"
+
+    private val globalActions = linkedMapOf(
+        "back" to AccessibilityService.GLOBAL_ACTION_BACK,
+        "home" to AccessibilityService.GLOBAL_ACTION_HOME,
+        "recents" to AccessibilityService.GLOBAL_ACTION_RECENTS,
+        "notifications" to AccessibilityService.GLOBAL_ACTION_NOTIFICATIONS,
+        "quick_settings" to AccessibilityService.GLOBAL_ACTION_QUICK_SETTINGS,
+        "power_dialog" to AccessibilityService.GLOBAL_ACTION_POWER_DIALOG,
+        "split_screen" to AccessibilityService.GLOBAL_ACTION_TOGGLE_SPLIT_SCREEN,
+        "lock_screen" to AccessibilityService.GLOBAL_ACTION_LOCK_SCREEN,
+        "take_screenshot" to AccessibilityService.GLOBAL_ACTION_TAKE_SCREENSHOT
     )
-    fun startApp(context: Context, args: JSONObject): String {
-        val packageName = args.getString("package_name")
-        val launchIntent = context.packageManager.getLaunchIntentForPackage(packageName)
-            ?: return "Error: App $packageName not found."
-
-        launchIntent.addFlags(
-            Intent.FLAG_ACTIVITY_NEW_TASK or
-                    Intent.FLAG_ACTIVITY_CLEAR_TASK or
-                    Intent.FLAG_ACTIVITY_CLEAR_TOP or
-                    Intent.FLAG_ACTIVITY_RESET_TASK_IF_NEEDED
-        )
-
-        context.startActivity(launchIntent)
-        val appInstruction = AppInstructions.getInstructions(packageName)
-        val result = "Starting app: $packageName $appInstruction"
-        return result
-    }
 
-    @Tool(
-        name = "click",
-        description = "Click at specific coordinates on the device screen",
-        parameters = [
-            ParameterDef(
-                name = "x",
-                type = "integer",
-                description = "X coordinate to click"
-            ),
-            ParameterDef(
-                name = "y",
-                type = "integer",
-                description = "Y coordinate to click"
-            )
-        ],
-        requiresAccessibility = true
-    )
-    fun click(accessibilityService: AccessibilityService, args: JSONObject): String {
-        val x = args.getInt("x")
-        val y = args.getInt("y")
+    // Available from Android 9
+    private val pieGlobalActions = setOf("lock_screen", "take_screenshot")
+
+    val globalAction = object : AgentTool(
+        name = "globalAction",
+        description = "Perform a system navigation action: go back, open recent apps, pull down " +
+                "the notification shade or quick settings, show the power menu, toggle split " +
+                "screen, lock the screen or take a screenshot. Opening notifications or quick " +
+                "settings returns what is in the shade, so you can act on notifications and " +
+                "toggles directly. Use back to close the shade again.",
+        parameters = toolParameters {
+            string("action", "The action to perform", enum = globalActions.keys.toList())
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val action = args.getString("action")
+            if (action in pieGlobalActions && Build.VERSION.SDK_INT < Build.VERSION_CODES.P) {
+                return "Error: $action needs Android 9 or later"
+            }
 
-        val clickResult = performClickGesture(x, y, accessibilityService)
-        return if (clickResult) "Clicked at coordinates ($x, $y)" else "Failed to click at coordinates ($x, $y)"
-    }
+            if (!accessibilityService.performGlobalAction(globalActions.getValue(action))) {
+                return "Failed to perform global action $action"
+            }
 
-    @Tool(
-        name = "swipe",
-        description = "Swipe from one point to another on the screen for example to scroll.",
-        parameters = [
-            ParameterDef(
-                name = "start_x",
-                type = "integer",
-                description = "Starting X coordinate"
-            ),
-            ParameterDef(
-                name = "start_y",
-                type = "integer",
-                description = "Starting Y coordinate"
-            ),
-            ParameterDef(
-                name = "end_x",
-                type = "integer",
-                description = "Ending X coordinate"
-            ),
-            ParameterDef(
-                name = "end_y",
-                type = "integer",
-                description = "Ending Y coordinate"
-            ),
-            ParameterDef(
-                name = "duration",
-                type = "integer",
-                description = "Duration of swipe in milliseconds. Default is 300. Use longer duration (500+) for text selection",
-                required = false
-            )
-        ],
-        requiresAccessibility = true
-    )
-    fun swipe(accessibilityService: AccessibilityService, args: JSONObject): String {
-        val startX = args.getInt("start_x")
-        val startY = args.getInt("start_y")
-        val endX = args.getInt("end_x")
-        val endY = args.getInt("end_y")
-        val duration = if (args.has("duration")) args.getInt("duration") else 300
+            if (action != "notifications" && action != "quick_settings") {
+                return "Performed global action $action"
+            }
 
-        val swipePath = Path()
-        swipePath.moveTo(startX.toFloat(), startY.toFloat())
-        swipePath.lineTo(endX.toFloat(), endY.toFloat())
+            ScreenWaiter.waitForSettled(accessibilityService, appLoadTimeWait) { ctx.isCancelled }
 
-        val gestureBuilder = GestureDescription.Builder()
-        gestureBuilder.addStroke(
-            GestureDescription.StrokeDescription(
-                swipePath,
-                0,
-                duration.toLong()
-            )
-        )
+            val shade = UiWindows.list(accessibilityService)
+                .filter { it.packageName == SYSTEM_UI_PACKAGE && it.root != null }
+                .maxByOrNull { window -> boundsOf(window.root!!).let { it.width() * it.height() } }
+                ?: return "Opened $action, but could not read the shade. Call getUiHierarchy " +
+                        "with all_windows to look for it."
 
-        val swipeResult = performGesture(gestureBuilder.build(), accessibilityService)
-        return if (swipeResult) {
-            "Swiped from ($startX, $startY) to ($endX, $endY) over $duration ms"
-        } else {
-            "Failed to swipe from ($startX, $startY) to ($endX, $endY)"
+            return "Opened $action. This is the shade:\n" +
+                    describeUiHierarchy(accessibilityService, windowId = shade.id)
         }
     }
 
-    @Tool(
-        name = "scrollBrowse",
-        description = "Scroll up a screen's worth from the current position and return the UI hierarchy at that new position. " +
-                "Use this to navigate through content while examining the UI one screen at a time.",
-        parameters = [
-            ParameterDef(
-                name = "scroll_duration",
-                type = "integer",
-                description = "Duration of the scroll in milliseconds (default is 300)",
-                required = false
-            ),
-            ParameterDef(
-                name = "pause_after_scroll",
-                type = "integer",
-                description = "Time to pause after scrolling in milliseconds (default is 1000)",
-                required = false
+    val takeScreenshot = object : AgentTool(
+        name = "takeScreenshot",
+        description = "Capture the screen and look at it. The image is attached to the next " +
+                "message. Use this when the UI hierarchy doesn't show what you need, such as " +
+                "in games, maps, drawings and other canvas-based apps, or to visually check " +
+                "that a task is done. With marks, every element you can interact with gets " +
+                "a numbered box and a legend, so you can act on what you see with tapMark.",
+        parameters = toolParameters {
+            integer(
+                "max_size",
+                "Longest side of the image in pixels. Smaller images are cheaper",
+                default = ScreenCapture.DEFAULT_MAX_DIMENSION
             )
-        ],
-        requiresAccessibility = true
-    )
-    fun scrollBrowse(accessibilityService: AccessibilityService, args: JSONObject): String {
-        val scrollDuration =
-            if (args.has("scroll_duration")) args.getInt("scroll_duration") else 300
-        val pauseAfterScroll =
-            if (args.has("pause_after_scroll")) args.getInt("pause_after_scroll") else 1000
-
-        // Get screen dimensions for calculating scroll coordinates
-        val displayMetrics = accessibilityService.resources.displayMetrics
-        val screenWidth = displayMetrics.widthPixels
-        val screenHeight = displayMetrics.heightPixels
-
-        // Center X coordinate
-        val centerX = screenWidth / 2
-
-        // Calculate scroll coordinates (from bottom third to top third)
-        val startY = (screenHeight * 0.7).toInt()
-        val endY = (screenHeight * 0.3).toInt()
-
-        // Perform swipe gesture
-        val swipePath = Path()
-        swipePath.moveTo(centerX.toFloat(), startY.toFloat())
-        swipePath.lineTo(centerX.toFloat(), endY.toFloat())
-
-        val gestureBuilder = GestureDescription.Builder()
-        gestureBuilder.addStroke(
-            GestureDescription.StrokeDescription(
-                swipePath,
-                0,
-                scrollDuration.toLong()
+            boolean(
+                "marks",
+                "Draw numbered boxes on interactable elements. Useful when the UI hierarchy " +
+                        "is sparse or hard to match to the screen, such as in WebViews",
+                default = false
             )
-        )
-
-        val swipeResult = performGesture(gestureBuilder.build(), accessibilityService)
+        },
+        requiresAccessibility = true
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            if (Build.VERSION.SDK_INT < Build.VERSION_CODES.R) {
+                return "Error: taking screenshots needs Android 11 or later"
+            }
 
-        if (!swipeResult) {
-            return "Failed to scroll the screen"
+            val maxSize = args.getInt("max_size").coerceIn(256, 2048)
+            val screenshot = ScreenCapture.capture(ctx.accessibilityService)
+            val (marks, refsRefreshed) = if (args.getBoolean("marks")) {
+                collectMarks(ctx.accessibilityService, screenshot)
+            } else {
+                emptyList<ScreenMark>() to false
+            }
+            val annotated = if (marks.isNotEmpty()) ScreenMarks.draw(screenshot, marks) else screenshot
+            val scaled = ScreenCapture.downscale(annotated, maxSize)
+
+            ctx.attach(Content.Text(text = "Screenshot taken with takeScreenshot:"))
+            ctx.attach(Content.ImageUrl(imageUrl = Image(url = ScreenCapture.toDataUrl(scaled))))
+
+            // Locale.US so the model always sees a decimal point
+            val factor = String.format(Locale.US, "%.2f", screenshot.width.toFloat() / scaled.width)
+            val result = "Captured a ${screenshot.width}x${screenshot.height} screenshot, scaled to " +
+                    "${scaled.width}x${scaled.height}. It is attached to the next message; " +
+                    "multiply its coordinates by $factor " +
+                    "to get screen coordinates."
+            val refreshNote = if (refsRefreshed) {
+                " The screen changed since you last read the hierarchy, so refs were refreshed: " +
+                        "use the refs below, and getUiChanges now compares against this screen."
+            } else {
+                ""
+            }
+            return when {
+                !args.getBoolean("marks") -> result
+                marks.isEmpty() -> "$result No interactable elements were found to mark.$refreshNote"
+                else -> "$result$refreshNote Marked ${marks.size} elements " +
+                        "(mark: [ref] element (center)):\n${ScreenMarks.legend(marks)}"
+            }
         }
+    }
 
-        // Wait for content to settle
-        Thread.sleep(pauseAfterScroll.toLong())
-
-        // Get UI hierarchy at this position
-        return try {
-            val activeWindow = accessibilityService.rootInActiveWindow
-                ?: return "ERROR: No active window found after scrolling"
+    /**
+     * Numbers the interactable elements of the latest hierarchy snapshot, so the legend uses
+     * refs the model already has and getUiChanges keeps its baseline. Only when most of that
+     * snapshot is gone from the screen, or there is none yet, is a new one taken the way
+     * getUiHierarchy would; the second value tells whether that happened.
+     */
+    private fun collectMarks(
+        accessibilityService: AccessibilityService,
+        screenshot: Bitmap
+    ): Pair<List<ScreenMark>, Boolean> {
+        val screen = Rect(0, 0, screenshot.width, screenshot.height)
+
+        val current = ElementRefs.currentNodes()
+        val live = current.filterValues { it.refresh() }
+        if (live.isNotEmpty() && live.size * 2 >= current.size) {
+            return ScreenMarks.collect(live, screen) to false
+        }
 
-            val appInfo = "App: ${activeWindow.packageName}"
-            val hierarchyText = buildCompactHierarchy(activeWindow)
-            "$appInfo $coordinateHint\n$hierarchyText"
-        } catch (e: Exception) {
-            "ERROR: Failed to get UI hierarchy after scrolling: ${e.message}"
+        val capture = captureWindows(accessibilityService, null, false)
+            ?: return emptyList<ScreenMark>() to false
+        capture.selected.forEach { window ->
+            window.root?.let { buildCompactHierarchy(it, capture.snapshot, window.id) }
         }
+        return ScreenMarks.collect(ElementRefs.currentNodes(), screen) to true
     }
 
-    private fun findEditableNode(
-        root: AccessibilityNodeInfo?,
-        maxDepth: Int = 5
-    ): AccessibilityNodeInfo? {
-        if (root == null || maxDepth <= 0) return null
-
-        // Check if current node is editable
-        if (root.isEditable) {
-            return root
-        }
+    val tapMark = object : AgentTool(
+        name = "tapMark",
+        description = "Tap the middle of an element by its number on the latest screenshot " +
+                "taken with marks.",
+        parameters = toolParameters {
+            integer("mark", "Number of the mark on the screenshot, e.g. 7")
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val mark = ScreenMarks.resolve(args.getInt("mark"))
+
+            // Follow the element if it moved since the screenshot, otherwise tap where it was
+            val bounds = try {
+                boundsOf(ElementRefs.resolve(mark.ref))
+            } catch (e: ToolArgumentException) {
+                mark.bounds
+            }
 
-        // Recursively check children
-        for (i in 0 until root.childCount) {
-            val child = root.getChild(i) ?: continue
-            val editableNode = findEditableNode(child, maxDepth - 1)
-            if (editableNode != null) {
-                return editableNode
+            val x = bounds.centerX()
+            val y = bounds.centerY()
+            return if (performClickGesture(x, y, ctx.accessibilityService)) {
+                "Tapped mark ${mark.number} (${mark.label}) at ($x, $y)"
+            } else {
+                "Failed to tap mark ${mark.number} (${mark.label}) at ($x, $y)"
             }
         }
-
-        return null
     }
 
-    @RequiresApi(Build.VERSION_CODES.R)
-    @Tool(
-        name = "enterText",
-        description = "Enter text into the a text field. Make sure the field you want the " +
-                "text to enter into is focused. Click it if needed, don't assume.",
-        parameters = [
-            ParameterDef(
-                name = "text",
-                type = "string",
-                description = "Text to enter"
-            ),
-            ParameterDef(
-                name = "submit",
-                type = "boolean",
-                description = "Whether to submit the text after entering it. " +
-                        "This doesn't always work. If there is a button to click directly, use that",
-                required = true
+    val startApp = object : AgentTool(
+        name = "startApp",
+        description = "Start an application by its package name",
+        parameters = toolParameters {
+            string("package_name", "Full package name of the app to start")
+        },
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            val packageName = args.getString("package_name")
+            val launchIntent = context.packageManager.getLaunchIntentForPackage(packageName)
+                ?: return "Error: App $packageName not found."
+
+            launchIntent.addFlags(
+                Intent.FLAG_ACTIVITY_NEW_TASK or
+                        Intent.FLAG_ACTIVITY_CLEAR_TASK or
+                        Intent.FLAG_ACTIVITY_CLEAR_TOP or
+                        Intent.FLAG_ACTIVITY_RESET_TASK_IF_NEEDED
             )
-        ],
-        requiresAccessibility = true
-    )
-    fun enterText(accessibilityService: AccessibilityService, args: JSONObject): String {
-        val text = args.getString("text")
 
-        val targetNode = if (args.has("id")) {
-            val id = args.getString("id")
-            accessibilityService.rootInActiveWindow?.findAccessibilityNodeInfosByViewId(id)
-                ?.firstOrNull()
-        } else {
-            accessibilityService.rootInActiveWindow?.findFocus(AccessibilityNodeInfo.FOCUS_INPUT)
-        }
+            context.startActivity(launchIntent)
 
-        if (targetNode == null) {
-            Log.d("Tools", "enterText: No targetable input field found")
-            return "Error: No targetable input field found"
-        }
+            // Return once the app is on screen and has stopped drawing, not after a fixed delay
+            val loaded = if (ctx.hasAccessibility) {
+                ScreenWaiter.waitFor(
+                    ctx.accessibilityService,
+                    listOf(WaitCondition.Package(packageName), WaitCondition.Idle()),
+                    appLoadTimeWait
+                ) { ctx.isCancelled }.satisfied
+            } else {
+                true
+            }
 
-        // If it's not already focused and it's clickable, try clicking it first
-        if (!targetNode.isFocused && targetNode.isClickable) {
-            targetNode.performAction(AccessibilityNodeInfo.ACTION_CLICK)
-            Thread.sleep(100) // Small delay to allow focus to set
+            val appInstruction = AppInstructions.getInstructions(packageName)
+            val status = if (loaded) "Started app" else "Starting app (still loading)"
+            val result = "$status: $packageName $appInstruction"
+            return result
         }
+    }
 
-        // If it's not focused after click (or wasn't clickable), try explicit focus
-        if (!targetNode.isFocused) {
-            targetNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
-            Thread.sleep(100) // Small delay to ensure focus is set
-        }
+    val click = object : AgentTool(
+        name = "click",
+        description = "Click at specific coordinates on the device screen",
+        parameters = toolParameters {
+            integer("x", "X coordinate to click")
+            integer("y", "Y coordinate to click")
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
 
-        // If the node isn't directly editable, try to find an editable node in its hierarchy
-        val editableNode = if (!targetNode.isEditable) {
-            findEditableNode(targetNode)
-        } else {
-            targetNode
-        }
+            val x = args.getInt("x")
+            val y = args.getInt("y")
 
-        if (editableNode == null) {
-            Log.d("Tools", "enterText: No editable nodes found in hierarchy")
-            return "Error: No editable field found"
+            val clickResult = performClickGesture(x, y, accessibilityService)
+            return if (clickResult) "Clicked at coordinates ($x, $y)" else "Failed to click at coordinates ($x, $y)"
         }
+    }
 
-        // If we found a different editable node, make sure it's focused
-        if (editableNode != targetNode && !editableNode.isFocused) {
-            editableNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
-            Thread.sleep(100)
-        }
+    private fun supportsAction(node: AccessibilityNodeInfo, action: Int): Boolean {
+        return node.actionList.any { it.id == action }
+    }
 
-        val arguments = Bundle()
-        arguments.putCharSequence(
-            AccessibilityNodeInfo.ACTION_ARGUMENT_SET_TEXT_CHARSEQUENCE,
-            text
-        )
+    private fun describeElement(ref: String, node: AccessibilityNodeInfo): String {
+        val label = node.text?.toString()?.takeIf { it.isNotBlank() }
+            ?: node.contentDescription?.toString()?.takeIf { it.isNotBlank() }
+            ?: node.className?.toString()?.substringAfterLast('.')
+            ?: "element"
+        return "$ref (\"$label\")"
+    }
+
+    private fun boundsOf(node: AccessibilityNodeInfo): Rect {
+        return Rect().also { node.getBoundsInScreen(it) }
+    }
 
-        val setTextResult =
-            editableNode.performAction(AccessibilityNodeInfo.ACTION_SET_TEXT, arguments)
+    // Waits for a node that was just tapped or asked to focus to report that it has focus
+    private fun awaitFocus(node: AccessibilityNodeInfo, ctx: ToolContext): Boolean {
+        return ScreenWaiter.waitForFocus(isCancelled = { ctx.isCancelled }) {
+            node.takeIf { it.refresh() && it.isFocused }
+        } != null
+    }
 
-        if (args.optBoolean("submit") && setTextResult) {
-            if (!editableNode.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)) {
-                Runtime.getRuntime().exec(arrayOf("input", "keyevent", "66"))
+    val clickElement = object : AgentTool(
+        name = "clickElement",
+        description = "Click a UI element by the ref shown for it in the UI hierarchy. " +
+                "Prefer this over click since it keeps working when the layout shifts.",
+        parameters = toolParameters {
+            string("ref", "Ref of the element from the latest UI hierarchy, e.g. e12")
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val ref = args.getString("ref")
+            val node = ElementRefs.resolve(ref)
+            val element = describeElement(ref, node)
+
+            if (supportsAction(node, AccessibilityNodeInfo.ACTION_CLICK)) {
+                return if (node.performAction(AccessibilityNodeInfo.ACTION_CLICK)) {
+                    "Clicked $element"
+                } else {
+                    "Failed to click $element"
+                }
             }
-        }
 
-        return if (setTextResult) {
-            "Entered text: \"$text\". IMPORTANT: consider if keyboard is visible, will need to swipe up clicking on next thing."
-        } else {
-            Log.d("Tools", "enterText: Failed to enter text")
-            "Failed to enter text"
+            val bounds = boundsOf(node)
+            val clickResult =
+                performClickGesture(bounds.centerX(), bounds.centerY(), accessibilityService)
+            return if (clickResult) {
+                "Clicked $element by tapping at (${bounds.centerX()}, ${bounds.centerY()})"
+            } else {
+                "Failed to click $element"
+            }
         }
     }
 
-    @RequiresApi(Build.VERSION_CODES.R)
-    @Tool(
-        name = "enterTextByDescription",
-        description = "Enter text into a text field. You must specify either the field's ID or " +
-                "provide enough information to find it (like text content or description - " +
-                "ensure email goes in email, phone goes in phone, etc.). After entering text, " +
-                "focus will be cleared to allow entering text in another field.",
-        parameters = [
-            ParameterDef(
-                name = "text",
-                type = "string",
-                description = "Text to enter"
-            ),
-            ParameterDef(
-                name = "id",
-                type = "string",
-                description = "The resource ID of the text field to target. If not provided, will try to find the field by other means.",
-                required = false
-            ),
-            ParameterDef(
-                name = "description",
-                type = "string",
-                description = "The content description or hint text of the field to target. Use this to find fields without IDs.",
-                required = true
-            ),
-            ParameterDef(
-                name = "submit",
-                type = "boolean",
-                description = "Whether to submit the text after entering it. This doesn't always work. If there is a button to click directly, use that",
-                required = true
-            ),
-        ],
+    val longPressElement = object : AgentTool(
+        name = "longPressElement",
+        description = "Long press a UI element by the ref shown for it in the UI hierarchy, " +
+                "for example to open a context menu or start selecting text.",
+        parameters = toolParameters {
+            string("ref", "Ref of the element from the latest UI hierarchy, e.g. e12")
+        },
         requiresAccessibility = true,
-        requiresContext = true
-    )
-    fun enterTextByDescription(
-        accessibilityService: AccessibilityService,
-        context: Context,
-        args: JSONObject
-    ): String {
-        // Temporarily disable touch handling on the overlay
-        OverlayService.getInstance()?.setTouchDisabled(true)
-
-        try {
-            val text = args.getString("text")
-
-            val rootNode = accessibilityService.rootInActiveWindow
-            if (rootNode == null) {
-                Log.d("Tools", "enterTextByDescription: No active window found")
-                return "Error: No active window found"
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val ref = args.getString("ref")
+            val node = ElementRefs.resolve(ref)
+            val element = describeElement(ref, node)
+
+            if (supportsAction(node, AccessibilityNodeInfo.ACTION_LONG_CLICK)) {
+                return if (node.performAction(AccessibilityNodeInfo.ACTION_LONG_CLICK)) {
+                    "Long pressed $element"
+                } else {
+                    "Failed to long press $element"
+                }
             }
 
-            val targetNode = when {
-                args.has("id") -> {
-                    val id = args.getString("id")
-                    rootNode.findAccessibilityNodeInfosByViewId(id)?.firstOrNull()
-                }
+            val bounds = boundsOf(node)
+            val pressPath = Path()
+            pressPath.moveTo(bounds.centerX().toFloat(), bounds.centerY().toFloat())
 
-                args.has("description") -> {
-                    val description = args.getString("description")
-                    findNodeByDescription(rootNode, description)
-                }
+            val gestureBuilder = GestureDescription.Builder()
+            gestureBuilder.addStroke(GestureDescription.StrokeDescription(pressPath, 0, 800))
 
-                else -> {
-                    rootNode.findFocus(AccessibilityNodeInfo.FOCUS_INPUT)
-                }
+            return if (performGesture(gestureBuilder.build(), accessibilityService)) {
+                "Long pressed $element at (${bounds.centerX()}, ${bounds.centerY()})"
+            } else {
+                "Failed to long press $element"
             }
+        }
+    }
 
-            if (targetNode == null) {
-                Log.d("Tools", "enterTextByDescription: Could not find the target text field")
-                return "Error: Could not find the target text field"
-            }
+    val setTextOnElement = object : AgentTool(
+        name = "setTextOnElement",
+        description = "Replace the text of an editable UI element by the ref shown for it in " +
+                "the UI hierarchy.",
+        parameters = toolParameters {
+            string("ref", "Ref of the element from the latest UI hierarchy, e.g. e12")
+            string("text", "Text to put in the field")
+            boolean(
+                "submit",
+                "Whether to submit the text after entering it. " +
+                        "This doesn't always work. If there is a button to click directly, use that",
+                default = false
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
 
-            if (!targetNode.isFocused) {
-                targetNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
-                Thread.sleep(100) // Small delay to ensure focus is set
+            val ref = args.getString("ref")
+            val text = args.getString("text")
+            val node = ElementRefs.resolve(ref)
+            val element = describeElement(ref, node)
+
+            // Without ACTION_SET_TEXT on the node itself, tap it and use whatever takes focus
+            val target = if (supportsAction(node, AccessibilityNodeInfo.ACTION_SET_TEXT)) {
+                node
+            } else {
+                val bounds = boundsOf(node)
+                performClickGesture(bounds.centerX(), bounds.centerY(), accessibilityService)
+                val focusedField = {
+                    accessibilityService.rootInActiveWindow
+                        ?.findFocus(AccessibilityNodeInfo.FOCUS_INPUT)
+                        ?.takeIf { supportsAction(it, AccessibilityNodeInfo.ACTION_SET_TEXT) }
+                }
+                // Another field may still have focus until the tap lands, so wait for one
+                // under the tap and only then settle for whichever field has it
+                ScreenWaiter.waitForFocus(isCancelled = { ctx.isCancelled }) {
+                    focusedField()?.takeIf { field ->
+                        boundsOf(field).contains(bounds.centerX(), bounds.centerY())
+                    }
+                } ?: focusedField() ?: return "Error: $element does not accept text"
             }
 
             val arguments = Bundle()
@@ -665,153 +867,936 @@ object ToolHandler {
                 AccessibilityNodeInfo.ACTION_ARGUMENT_SET_TEXT_CHARSEQUENCE,
                 text
             )
-
-            val setTextResult =
-                targetNode.performAction(AccessibilityNodeInfo.ACTION_SET_TEXT, arguments)
-
-            if (!setTextResult) {
-                Log.d("Tools", "enterTextByDescription: Failed to enter text")
-                return "Failed to enter text"
+            if (!target.performAction(AccessibilityNodeInfo.ACTION_SET_TEXT, arguments)) {
+                return "Failed to enter text into $element"
             }
 
-            if (args.optBoolean("submit")) {
-                if (!targetNode.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)) {
+            if (args.getBoolean("submit")) {
+                if (Build.VERSION.SDK_INT < Build.VERSION_CODES.R ||
+                    !target.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)
+                ) {
                     Runtime.getRuntime().exec(arrayOf("input", "keyevent", "66"))
                 }
-            } else {
-                targetNode.performAction(AccessibilityNodeInfo.ACTION_CLEAR_FOCUS)
             }
 
-            hideKeyboard(context)
-            return "Entered text: \"$text\""
-        } finally {
-            // Re-enable touch handling on the overlay
-            OverlayService.getInstance()?.setTouchDisabled(false)
+            return "Entered text \"$text\" into $element"
         }
     }
 
-    private fun findNodeByDescription(
-        root: AccessibilityNodeInfo,
-        description: String
-    ): AccessibilityNodeInfo? {
-        // Function to check if a node matches our criteria
-        fun checkNode(node: AccessibilityNodeInfo?): Boolean {
-            if (node == null) return false
-            return node.contentDescription?.toString()
-                ?.contains(description, ignoreCase = true) == true ||
-                    node.text?.toString()?.contains(description, ignoreCase = true) == true
-        }
-
-        // Function to find the best editable ancestor of a node
-        fun findBestEditableAncestor(node: AccessibilityNodeInfo?): AccessibilityNodeInfo? {
-            var current = node
-            var bestMatch: AccessibilityNodeInfo? = null
+    val scrollElement = object : AgentTool(
+        name = "scrollElement",
+        description = "Scroll a scrollable UI element (a list, page or carousel) by the ref " +
+                "shown for it in the UI hierarchy.",
+        parameters = toolParameters {
+            string("ref", "Ref of the scrollable element from the latest UI hierarchy, e.g. e12")
+            string(
+                "direction",
+                "forward shows content further down (or to the right), backward goes back",
+                enum = listOf("forward", "backward"),
+                default = "forward"
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val ref = args.getString("ref")
+            val direction = args.getString("direction")
+            val node = ElementRefs.resolve(ref)
+            val element = describeElement(ref, node)
+
+            val action = if (direction == "forward") {
+                AccessibilityNodeInfo.ACTION_SCROLL_FORWARD
+            } else {
+                AccessibilityNodeInfo.ACTION_SCROLL_BACKWARD
+            }
 
-            while (current != null) {
-                // If this node is editable, it's our best match so far
-                if (current.isEditable) {
-                    bestMatch = current
-                    break // Stop at the first editable ancestor
+            if (supportsAction(node, action)) {
+                return if (node.performAction(action)) {
+                    "Scrolled $element $direction"
+                } else {
+                    "Could not scroll $element $direction, it may already be at the end"
                 }
+            }
 
-                try {
-                    current = current.parent
-                } catch (e: Exception) {
-                    break
-                }
+            // Swipe across the middle of the element, against the scroll direction
+            val bounds = boundsOf(node)
+            val className = node.className?.toString().orEmpty()
+            val horizontal = className.contains("Horizontal") || className.contains("ViewPager")
+            val forward = direction == "forward"
+            val swipePath = Path()
+            if (horizontal) {
+                val y = bounds.centerY().toFloat()
+                val from = bounds.left + bounds.width() * if (forward) 0.8f else 0.2f
+                val to = bounds.left + bounds.width() * if (forward) 0.2f else 0.8f
+                swipePath.moveTo(from, y)
+                swipePath.lineTo(to, y)
+            } else {
+                val x = bounds.centerX().toFloat()
+                val from = bounds.top + bounds.height() * if (forward) 0.8f else 0.2f
+                val to = bounds.top + bounds.height() * if (forward) 0.2f else 0.8f
+                swipePath.moveTo(x, from)
+                swipePath.lineTo(x, to)
             }
 
-            return bestMatch ?: node // Return the original node if no editable ancestor found
-        }
+            val gestureBuilder = GestureDescription.Builder()
+            gestureBuilder.addStroke(GestureDescription.StrokeDescription(swipePath, 0, 300))
 
-        // First check if the current node matches
-        if (checkNode(root)) {
-            val bestNode = findBestEditableAncestor(root)
-            if (bestNode?.isEditable == true) {
-                return bestNode
+            return if (performGesture(gestureBuilder.build(), accessibilityService)) {
+                "Scrolled $element $direction by swiping"
+            } else {
+                "Failed to scroll $element"
             }
         }
+    }
 
-        // If no match at current node, search children
-        for (i in 0 until root.childCount) {
-            val child = root.getChild(i) ?: continue
-            val result = findNodeByDescription(child, description)
-            if (result != null) {
-                // If we found a match in a child, check if it or its ancestors are better
-                val bestNode = findBestEditableAncestor(result)
-                if (bestNode?.isEditable == true) {
-                    return bestNode
-                }
-                return result
+    val swipe = object : AgentTool(
+        name = "swipe",
+        description = "Swipe from one point to another on the screen for example to scroll.",
+        parameters = toolParameters {
+            integer("start_x", "Starting X coordinate")
+            integer("start_y", "Starting Y coordinate")
+            integer("end_x", "Ending X coordinate")
+            integer("end_y", "Ending Y coordinate")
+            integer(
+                "duration",
+                "Duration of swipe in milliseconds. Use longer duration (500+) for text selection",
+                default = 300
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val startX = args.getInt("start_x")
+            val startY = args.getInt("start_y")
+            val endX = args.getInt("end_x")
+            val endY = args.getInt("end_y")
+            val duration = args.getInt("duration")
+
+            val swipePath = Path()
+            swipePath.moveTo(startX.toFloat(), startY.toFloat())
+            swipePath.lineTo(endX.toFloat(), endY.toFloat())
+
+            val gestureBuilder = GestureDescription.Builder()
+            gestureBuilder.addStroke(
+                GestureDescription.StrokeDescription(
+                    swipePath,
+                    0,
+                    duration.toLong()
+                )
+            )
+
+            val swipeResult = performGesture(gestureBuilder.build(), accessibilityService, duration)
+            return if (swipeResult) {
+                "Swiped from ($startX, $startY) to ($endX, $endY) over $duration ms"
+            } else {
+                "Failed to swipe from ($startX, $startY) to ($endX, $endY)"
             }
         }
+    }
 
-        return null
+    private fun ToolSchemaBuilder.point(prefix: String, what: String) {
+        string("${prefix}ref", "Ref of the element to $what, from the latest UI hierarchy", required = false)
+        integer("${prefix}x", "X coordinate to $what, if no ref is given", required = false)
+        integer("${prefix}y", "Y coordinate to $what, if no ref is given", required = false)
     }
 
-    @Tool(
-        name = "checkSetup",
-        description = "Check how goose mobile is currently setup. Start helping the user make the recommendations happen.",
-        parameters = [],
-        requiresContext = true,
-        requiresAccessibility = true
-    )
-    fun checkSetup(
+    // Centre of the element given by <prefix>ref, or the <prefix>x/<prefix>y coordinates
+    private fun pointArgument(args: ToolArguments, prefix: String = ""): Pair<Float, Float> {
+        val refName = "${prefix}ref"
+        if (args.has(refName)) {
+            val bounds = boundsOf(ElementRefs.resolve(args.getString(refName)))
+            return bounds.exactCenterX() to bounds.exactCenterY()
+        }
+        if (!args.has("${prefix}x") || !args.has("${prefix}y")) {
+            throw ToolArgumentException(
+                field = refName,
+                message = "Give either '$refName' or both '${prefix}x' and '${prefix}y'"
+            )
+        }
+        return args.getInt("${prefix}x").toFloat() to args.getInt("${prefix}y").toFloat()
+    }
+
+    private fun formatPoint(point: Pair<Float, Float>): String {
+        return "(${point.first.toInt()}, ${point.second.toInt()})"
+    }
+
+    val longPress = object : AgentTool(
+        name = "longPress",
+        description = "Press and hold on an element or point for a given time, for example to " +
+                "pick up a list item, open a context menu or drop a pin on a map.",
+        parameters = toolParameters {
+            point("", "press")
+            integer("duration", "How long to hold in milliseconds", default = 800)
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val point = pointArgument(args)
+            val duration = args.getInt("duration").coerceAtLeast(1)
+
+            val pressPath = Path()
+            pressPath.moveTo(point.first, point.second)
+
+            val gestureBuilder = GestureDescription.Builder()
+            gestureBuilder.addStroke(
+                GestureDescription.StrokeDescription(pressPath, 0, duration.toLong())
+            )
+
+            return if (performGesture(gestureBuilder.build(), ctx.accessibilityService, duration)) {
+                "Long pressed at ${formatPoint(point)} for $duration ms"
+            } else {
+                "Failed to long press at ${formatPoint(point)}"
+            }
+        }
+    }
+
+    val doubleTap = object : AgentTool(
+        name = "doubleTap",
+        description = "Tap twice in quick succession on an element or point, for example to " +
+                "zoom in on a map or photo or to like a post.",
+        parameters = toolParameters {
+            point("", "double tap")
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val point = pointArgument(args)
+
+            val gestureBuilder = GestureDescription.Builder()
+            for (startTime in listOf(0L, 150L)) {
+                val tapPath = Path()
+                tapPath.moveTo(point.first, point.second)
+                gestureBuilder.addStroke(GestureDescription.StrokeDescription(tapPath, startTime, 50))
+            }
+
+            return if (performGesture(gestureBuilder.build(), ctx.accessibilityService)) {
+                "Double tapped at ${formatPoint(point)}"
+            } else {
+                "Failed to double tap at ${formatPoint(point)}"
+            }
+        }
+    }
+
+    val pinch = object : AgentTool(
+        name = "pinch",
+        description = "Two-finger pinch around a centre point. Fingers move apart to zoom in " +
+                "and together to zoom out.",
+        parameters = toolParameters {
+            point("center_", "pinch around")
+            string("direction", "Whether to zoom in or out", enum = listOf("in", "out"))
+            integer(
+                "distance",
+                "How far apart the fingers are at the wide end of the pinch, in pixels",
+                default = 600
+            )
+            integer("duration", "Duration of the pinch in milliseconds", default = 400)
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val center = pointArgument(args, "center_")
+            val zoomIn = args.getString("direction") == "in"
+            val wide = args.getInt("distance").coerceAtLeast(100) / 2f
+            val narrow = wide / 6
+            val duration = args.getInt("duration").coerceAtLeast(1).toLong()
+
+            val (from, to) = if (zoomIn) narrow to wide else wide to narrow
+            val gestureBuilder = GestureDescription.Builder()
+            for (side in listOf(-1, 1)) {
+                val fingerPath = Path()
+                fingerPath.moveTo(center.first + side * from, center.second)
+                fingerPath.lineTo(center.first + side * to, center.second)
+                gestureBuilder.addStroke(GestureDescription.StrokeDescription(fingerPath, 0, duration))
+            }
+
+            return if (performGesture(gestureBuilder.build(), ctx.accessibilityService, duration.toInt())) {
+                "Pinched ${if (zoomIn) "out to zoom in" else "in to zoom out"} around ${formatPoint(center)}"
+            } else {
+                "Failed to pinch around ${formatPoint(center)}"
+            }
+        }
+    }
+
+    val dragAndDrop = object : AgentTool(
+        name = "dragAndDrop",
+        description = "Press and hold an element, drag it to another element or point and let " +
+                "go. Use this to reorder lists, move icons or sliders and drag map pins.",
+        parameters = toolParameters {
+            point("from_", "drag")
+            point("to_", "drop on")
+            integer("hold", "How long to hold before dragging in milliseconds", default = 600)
+            integer("duration", "How long the drag itself takes in milliseconds", default = 800)
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val from = pointArgument(args, "from_")
+            val to = pointArgument(args, "to_")
+            val hold = args.getInt("hold").coerceAtLeast(1).toLong()
+            val duration = args.getInt("duration").coerceAtLeast(1).toLong()
+
+            // The hold and the move are one continued stroke, so the finger never lifts
+            val holdPath = Path()
+            holdPath.moveTo(from.first, from.second)
+            val holdStroke = GestureDescription.StrokeDescription(holdPath, 0, hold, true)
+
+            val movePath = Path()
+            movePath.moveTo(from.first, from.second)
+            movePath.lineTo(to.first, to.second)
+            val moveStroke = holdStroke.continueStroke(movePath, 0, duration, false)
+
+            val held = performGesture(
+                GestureDescription.Builder().addStroke(holdStroke).build(),
+                accessibilityService,
+                hold.toInt()
+            )
+            val moved = held && performGesture(
+                GestureDescription.Builder().addStroke(moveStroke).build(),
+                accessibilityService,
+                duration.toInt()
+            )
+
+            return if (moved) {
+                "Dragged from ${formatPoint(from)} to ${formatPoint(to)}"
+            } else {
+                "Failed to drag from ${formatPoint(from)} to ${formatPoint(to)}"
+            }
+        }
+    }
+
+    val gesture = object : AgentTool(
+        name = "gesture",
+        description = "Perform a custom gesture made of one or more finger strokes, each " +
+                "following a path of points. Strokes with overlapping times are performed " +
+                "together, as with several fingers. Use this for drawing, signatures, curved " +
+                "swipes or anything the other gesture tools can't do.",
+        parameters = toolParameters {
+            array(
+                "strokes",
+                "The strokes of the gesture, at most ${GestureDescription.getMaxStrokeCount()}",
+                items = objectParameter {
+                    array(
+                        "points",
+                        "Points the finger passes through, in order. One point is a tap or hold.",
+                        items = objectParameter {
+                            integer("x", "X coordinate")
+                            integer("y", "Y coordinate")
+                        }
+                    )
+                    integer("start_time", "When the stroke starts, in milliseconds from the start", default = 0)
+                    integer("duration", "How long the stroke takes in milliseconds", default = 300)
+                }
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val strokes = args.getObjectList("strokes")
+            if (strokes.isEmpty() || strokes.size > GestureDescription.getMaxStrokeCount()) {
+                throw ToolArgumentException(
+                    field = "strokes",
+                    message = "Give between 1 and ${GestureDescription.getMaxStrokeCount()} strokes"
+                )
+            }
+
+            val gestureBuilder = GestureDescription.Builder()
+            var end = 0L
+            strokes.forEachIndexed { i, stroke ->
+                val points = stroke.getObjectList("points")
+                if (points.isEmpty()) {
+                    throw ToolArgumentException(
+                        field = "strokes[$i].points",
+                        message = "Stroke $i needs at least one point"
+                    )
+                }
+
+                val strokePath = Path()
+                points.forEachIndexed { j, point ->
+                    val x = point.getInt("x").toFloat()
+                    val y = point.getInt("y").toFloat()
+                    if (j == 0) strokePath.moveTo(x, y) else strokePath.lineTo(x, y)
+                }
+
+                val startTime = stroke.getInt("start_time").coerceAtLeast(0).toLong()
+                val duration = stroke.getInt("duration").coerceAtLeast(1).toLong()
+                end = maxOf(end, startTime + duration)
+                gestureBuilder.addStroke(
+                    GestureDescription.StrokeDescription(strokePath, startTime, duration)
+                )
+            }
+
+            if (end > GestureDescription.getMaxGestureDuration()) {
+                throw ToolArgumentException(
+                    field = "strokes",
+                    message = "The gesture may last at most ${GestureDescription.getMaxGestureDuration()} ms"
+                )
+            }
+
+            return if (performGesture(gestureBuilder.build(), ctx.accessibilityService, end.toInt())) {
+                "Performed a gesture with ${strokes.size} stroke(s) over $end ms"
+            } else {
+                "Failed to perform the gesture"
+            }
+        }
+    }
+
+    val scrollBrowse = object : AgentTool(
+        name = "scrollBrowse",
+        description = "Scroll up a screen's worth from the current position and return the UI hierarchy at that new position. " +
+                "Use this to navigate through content while examining the UI one screen at a time.",
+        parameters = toolParameters {
+            integer("scroll_duration", "Duration of the scroll in milliseconds", default = 300)
+            integer(
+                "pause_after_scroll",
+                "Longest time to wait for the content to settle after scrolling, in milliseconds",
+                default = 1000
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val scrollDuration = args.getInt("scroll_duration")
+            val pauseAfterScroll = args.getInt("pause_after_scroll")
+
+            // Get screen dimensions for calculating scroll coordinates
+            val displayMetrics = accessibilityService.resources.displayMetrics
+            val screenWidth = displayMetrics.widthPixels
+            val screenHeight = displayMetrics.heightPixels
+
+            // Center X coordinate
+            val centerX = screenWidth / 2
+
+            // Calculate scroll coordinates (from bottom third to top third)
+            val startY = (screenHeight * 0.7).toInt()
+            val endY = (screenHeight * 0.3).toInt()
+
+            // Perform swipe gesture
+            val swipePath = Path()
+            swipePath.moveTo(centerX.toFloat(), startY.toFloat())
+            swipePath.lineTo(centerX.toFloat(), endY.toFloat())
+
+            val gestureBuilder = GestureDescription.Builder()
+            gestureBuilder.addStroke(
+                GestureDescription.StrokeDescription(
+                    swipePath,
+                    0,
+                    scrollDuration.toLong()
+                )
+            )
+
+            val swipeResult = performGesture(gestureBuilder.build(), accessibilityService)
+
+            if (!swipeResult) {
+                return "Failed to scroll the screen"
+            }
+
+            // Wait for content to settle
+            ScreenWaiter.waitForSettled(accessibilityService, pauseAfterScroll.toLong()) {
+                ctx.isCancelled
+            }
+
+            // Get UI hierarchy at this position
+            return try {
+                val activeWindow = accessibilityService.rootInActiveWindow
+                    ?: return "ERROR: No active window found after scrolling"
+
+                val appInfo = "App: ${activeWindow.packageName}"
+                val hierarchyText = buildCompactHierarchy(activeWindow)
+                "$appInfo $coordinateHint\n$hierarchyText"
+            } catch (e: Exception) {
+                "ERROR: Failed to get UI hierarchy after scrolling: ${e.message}"
+            }
+        }
+    }
+
+    private fun findEditableNode(
+        root: AccessibilityNodeInfo?,
+        maxDepth: Int = 5
+    ): AccessibilityNodeInfo? {
+        if (root == null || maxDepth <= 0) return null
+
+        // Check if current node is editable
+        if (root.isEditable) {
+            return root
+        }
+
+        // Recursively check children
+        for (i in 0 until root.childCount) {
+            val child = root.getChild(i) ?: continue
+            val editableNode = findEditableNode(child, maxDepth - 1)
+            if (editableNode != null) {
+                return editableNode
+            }
+        }
+
+        return null
+    }
+
+    val enterText = object : AgentTool(
+        name = "enterText",
+        description = "Enter text into the a text field. Make sure the field you want the " +
+                "text to enter into is focused. Click it if needed, don't assume.",
+        parameters = toolParameters {
+            string("text", "Text to enter")
+            boolean(
+                "submit",
+                "Whether to submit the text after entering it. " +
+                        "This doesn't always work. If there is a button to click directly, use that"
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        @RequiresApi(Build.VERSION_CODES.R)
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+
+            val text = args.getString("text")
+
+            val targetNode =
+                accessibilityService.rootInActiveWindow?.findFocus(AccessibilityNodeInfo.FOCUS_INPUT)
+
+            if (targetNode == null) {
+                Log.d("Tools", "enterText: No targetable input field found")
+                return "Error: No targetable input field found"
+            }
+
+            // If it's not already focused and it's clickable, try clicking it first
+            if (!targetNode.isFocused && targetNode.isClickable) {
+                targetNode.performAction(AccessibilityNodeInfo.ACTION_CLICK)
+                awaitFocus(targetNode, ctx)
+            }
+
+            // If it's not focused after click (or wasn't clickable), try explicit focus
+            if (!targetNode.isFocused) {
+                targetNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
+                awaitFocus(targetNode, ctx)
+            }
+
+            // If the node isn't directly editable, try to find an editable node in its hierarchy
+            val editableNode = if (!targetNode.isEditable) {
+                findEditableNode(targetNode)
+            } else {
+                targetNode
+            }
+
+            if (editableNode == null) {
+                Log.d("Tools", "enterText: No editable nodes found in hierarchy")
+                return "Error: No editable field found"
+            }
+
+            // If we found a different editable node, make sure it's focused
+            if (editableNode != targetNode && !editableNode.isFocused) {
+                editableNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
+                awaitFocus(editableNode, ctx)
+            }
+
+            val arguments = Bundle()
+            arguments.putCharSequence(
+                AccessibilityNodeInfo.ACTION_ARGUMENT_SET_TEXT_CHARSEQUENCE,
+                text
+            )
+
+            val setTextResult =
+                editableNode.performAction(AccessibilityNodeInfo.ACTION_SET_TEXT, arguments)
+
+            if (args.getBoolean("submit") && setTextResult) {
+                if (!editableNode.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)) {
+                    Runtime.getRuntime().exec(arrayOf("input", "keyevent", "66"))
+                }
+            }
+
+            return if (setTextResult) {
+                "Entered text: \"$text\". IMPORTANT: consider if keyboard is visible, will need to swipe up clicking on next thing."
+            } else {
+                Log.d("Tools", "enterText: Failed to enter text")
+                "Failed to enter text"
+            }
+        }
+    }
+
+    val enterTextByDescription = object : AgentTool(
+        name = "enterTextByDescription",
+        description = "Enter text into a text field. You must specify either the field's ID or " +
+                "provide enough information to find it (like text content or description - " +
+                "ensure email goes in email, phone goes in phone, etc.). After entering text, " +
+                "focus will be cleared to allow entering text in another field.",
+        parameters = toolParameters {
+            string("text", "Text to enter")
+            string(
+                "id",
+                "The resource ID of the text field to target. If not provided, will try to " +
+                        "find the field by other means.",
+                required = false
+            )
+            string(
+                "description",
+                "The label, hint text or content description of the field to target. " +
+                        "Use this to find fields without IDs."
+            )
+            boolean(
+                "submit",
+                "Whether to submit the text after entering it. This doesn't always work. " +
+                        "If there is a button to click directly, use that"
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        @RequiresApi(Build.VERSION_CODES.R)
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+            val context = ctx.context
+
+            // Temporarily disable touch handling on the overlay
+            OverlayService.getInstance()?.setTouchDisabled(true)
+
+            try {
+                val text = args.getString("text")
+
+                val rootNode = accessibilityService.rootInActiveWindow
+                if (rootNode == null) {
+                    Log.d("Tools", "enterTextByDescription: No active window found")
+                    return "Error: No active window found"
+                }
+
+                val targetNode = when {
+                    args.has("id") -> {
+                        val id = args.getString("id")
+                        rootNode.findAccessibilityNodeInfosByViewId(id)?.firstOrNull()
+                    }
+
+                    args.has("description") -> {
+                        val description = args.getString("description")
+                        when (val result = ElementMatcher.findTextField(rootNode, description)) {
+                            is MatchResult.Found -> result.match.node
+                            is MatchResult.Ambiguous -> return describeAmbiguousFields(
+                                description,
+                                result.candidates
+                            )
+
+                            MatchResult.NotFound -> null
+                        }
+                    }
+
+                    else -> {
+                        rootNode.findFocus(AccessibilityNodeInfo.FOCUS_INPUT)
+                    }
+                }
+
+                if (targetNode == null) {
+                    Log.d("Tools", "enterTextByDescription: Could not find the target text field")
+                    return "Error: Could not find the target text field"
+                }
+
+                if (!targetNode.isFocused) {
+                    targetNode.performAction(AccessibilityNodeInfo.ACTION_FOCUS)
+                    awaitFocus(targetNode, ctx)
+                }
+
+                val arguments = Bundle()
+                arguments.putCharSequence(
+                    AccessibilityNodeInfo.ACTION_ARGUMENT_SET_TEXT_CHARSEQUENCE,
+                    text
+                )
+
+                val setTextResult =
+                    targetNode.performAction(AccessibilityNodeInfo.ACTION_SET_TEXT, arguments)
+
+                if (!setTextResult) {
+                    Log.d("Tools", "enterTextByDescription: Failed to enter text")
+                    return "Failed to enter text"
+                }
+
+                if (args.getBoolean("submit")) {
+                    if (!targetNode.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)) {
+                        Runtime.getRuntime().exec(arrayOf("input", "keyevent", "66"))
+                    }
+                } else {
+                    targetNode.performAction(AccessibilityNodeInfo.ACTION_CLEAR_FOCUS)
+                }
+
+                hideKeyboard(context)
+                return "Entered text: \"$text\""
+            } finally {
+                // Re-enable touch handling on the overlay
+                OverlayService.getInstance()?.setTouchDisabled(false)
+            }
+        }
+    }
+
+    /**
+     * The field a text editing tool works on: the element behind the optional `ref` argument,
+     * or otherwise the field that has input focus in any window.
+     */
+    private fun textTarget(
         accessibilityService: AccessibilityService,
-        context: Context,
-        args: JSONObject
+        args: ToolArguments
+    ): Pair<AccessibilityNodeInfo, String> {
+        args.optString("ref")?.let { ref ->
+            val node = ElementRefs.resolve(ref)
+            return node to describeElement(ref, node)
+        }
+
+        val focused = UiWindows.list(accessibilityService)
+            .firstNotNullOfOrNull { it.root?.findFocus(AccessibilityNodeInfo.FOCUS_INPUT) }
+            ?: throw ToolArgumentException(
+                field = "ref",
+                message = "No text field has focus. Pass the ref of the field or click it first."
+            )
+        val label = focused.text?.takeUnless { focused.isShowingHintText }?.let { "\"$it\"" }
+            ?: focused.className?.toString()?.substringAfterLast('.')
+            ?: "field"
+        return focused to "the focused field ($label)"
+    }
+
+    private fun ToolSchemaBuilder.textTargetRef() = string(
+        "ref",
+        "Ref of the text field from the latest UI hierarchy. Defaults to the focused field",
+        required = false
+    )
+
+    private fun currentText(node: AccessibilityNodeInfo): String {
+        return if (node.isShowingHintText) "" else node.text?.toString().orEmpty()
+    }
+
+    val getClipboard = object : AgentTool(
+        name = "getClipboard",
+        description = "Read the text on the clipboard."
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val clipboard = ctx.context.getSystemService(ClipboardManager::class.java)
+                ?: return "Error: clipboard not available"
+
+            // Since Android 10 only the app in front (or the keyboard) may read the clipboard
+            val clip = clipboard.primaryClip
+                ?: return "The clipboard is empty, or Android does not let Gosling read it " +
+                        "right now. To get copied text, paste it into a field and read the " +
+                        "UI hierarchy."
+            val text = (0 until clip.itemCount)
+                .mapNotNull { clip.getItemAt(it).coerceToText(ctx.context)?.toString() }
+                .filter { it.isNotEmpty() }
+                .joinToString("\n")
+            return if (text.isEmpty()) "The clipboard holds no text" else "Clipboard: \"$text\""
+        }
+    }
+
+    val setClipboard = object : AgentTool(
+        name = "setClipboard",
+        description = "Put text on the clipboard, for example a code or tracking number read " +
+                "from the UI hierarchy, so it can be pasted into another app.",
+        parameters = toolParameters {
+            string("text", "Text to put on the clipboard")
+        }
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val clipboard = ctx.context.getSystemService(ClipboardManager::class.java)
+                ?: return "Error: clipboard not available"
+
+            val text = args.getString("text")
+            clipboard.setPrimaryClip(ClipData.newPlainText("Gosling", text))
+            return "Put \"$text\" on the clipboard"
+        }
+    }
+
+    val selectText = object : AgentTool(
+        name = "selectText",
+        description = "Select a range of text in a text field, or place the cursor when start " +
+                "and end are equal. Use it before copyPasteText to copy or cut part of the " +
+                "text, or to paste at a specific position without replacing the rest.",
+        parameters = toolParameters {
+            textTargetRef()
+            integer("start", "Index of the first selected character", default = 0)
+            integer(
+                "end",
+                "Index after the last selected character. -1 means the end of the text",
+                default = -1
+            )
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val (node, element) = textTarget(ctx.accessibilityService, args)
+            val length = currentText(node).length
+
+            val end = args.getInt("end").let { if (it < 0) length else it }
+            val start = args.getInt("start")
+            if (start < 0 || start > end || end > length) {
+                throw ToolArgumentException(
+                    field = "start",
+                    message = "Range $start..$end doesn't fit the text of $element, which " +
+                            "has $length characters"
+                )
+            }
+
+            val arguments = Bundle()
+            arguments.putInt(AccessibilityNodeInfo.ACTION_ARGUMENT_SELECTION_START_INT, start)
+            arguments.putInt(AccessibilityNodeInfo.ACTION_ARGUMENT_SELECTION_END_INT, end)
+            if (!node.performAction(AccessibilityNodeInfo.ACTION_SET_SELECTION, arguments)) {
+                return "Failed to select text in $element"
+            }
+
+            return if (start == end) {
+                "Placed the cursor at $start in $element"
+            } else {
+                "Selected \"${currentText(node).substring(start, end)}\" in $element"
+            }
+        }
+    }
+
+    val copyPasteText = object : AgentTool(
+        name = "copyPasteText",
+        description = "Copy or cut the selected text of a field, or paste the clipboard at " +
+                "its cursor. Pasting keeps the rest of the text in the field.",
+        parameters = toolParameters {
+            string("action", "What to do", enum = listOf("copy", "cut", "paste"))
+            textTargetRef()
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val (node, element) = textTarget(ctx.accessibilityService, args)
+
+            val verb = args.getString("action")
+            val (action, done) = when (verb) {
+                "copy" -> AccessibilityNodeInfo.ACTION_COPY to "Copied the selection of"
+                "cut" -> AccessibilityNodeInfo.ACTION_CUT to "Cut the selection of"
+                else -> AccessibilityNodeInfo.ACTION_PASTE to "Pasted the clipboard into"
+            }
+
+            if (action != AccessibilityNodeInfo.ACTION_PASTE &&
+                node.textSelectionStart == node.textSelectionEnd
+            ) {
+                return "Error: nothing is selected in $element. Call selectText first."
+            }
+
+            return if (node.performAction(action)) {
+                node.refresh()
+                "$done $element, which now reads \"${currentText(node)}\""
+            } else {
+                "Failed to $verb in $element"
+            }
+        }
+    }
+
+    val pressImeAction = object : AgentTool(
+        name = "pressImeAction",
+        description = "Press the action key of the keyboard (Search, Send, Go, Next or Done) " +
+                "for a text field, without changing its text.",
+        parameters = toolParameters {
+            textTargetRef()
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val (node, element) = textTarget(ctx.accessibilityService, args)
+
+            if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.R &&
+                node.performAction(AccessibilityNodeInfo.AccessibilityAction.ACTION_IME_ENTER.id)
+            ) {
+                return "Pressed the keyboard action for $element"
+            }
+
+            Runtime.getRuntime().exec(arrayOf("input", "keyevent", "66"))
+            return "Sent the enter key for $element"
+        }
+    }
+
+    private fun describeAmbiguousFields(
+        description: String,
+        candidates: List<ElementMatch>
     ): String {
-        val appKinds = IntentAppKinds.allCategories.toString()
-        return "IMPORTANT: you can check on the following on behalf of the user: Please check that goose mobile has a variety of apps, and be logged in to them. eg that the calendar is configured, email is setup and accounts logged in, messaging and more. " +
-                "Check if there are applications installed for things like ecommerce, " +
-                " Can suggest just a few apps if not there, eg amazon, ebay, afterpay and so on. Suggest other apps if you can sense what the user may benefit from. Some ideas to compare with what is available:" + appKinds ;
+        val refs = ElementRefs.currentNodes()
+        val fields = candidates.joinToString("\n") { match ->
+            val ref = refs.entries.find { it.value == match.node }?.key
+            val id = match.node.viewIdResourceName?.substringAfter(":id/")
+            val center = boundsOf(match.node).let { "(${it.centerX()},${it.centerY()})" }
+            listOfNotNull(
+                ref?.let { "[$it]" },
+                "${match.matchedBy} \"${match.label}\"",
+                id?.let { "id=$it" },
+                center
+            ).joinToString(" ")
+        }
+        return "Error: \"$description\" matches several fields equally well, nothing was " +
+                "entered. Use a more specific description, the id, or setTextOnElement with " +
+                "a ref:\n$fields"
+    }
+
+    val checkSetup = object : AgentTool(
+        name = "checkSetup",
+        description = "Check how goose mobile is currently setup. Start helping the user make the recommendations happen.",
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val appKinds = IntentAppKinds.allCategories.toString()
+            return "IMPORTANT: you can check on the following on behalf of the user: Please check that goose mobile has a variety of apps, and be logged in to them. eg that the calendar is configured, email is setup and accounts logged in, messaging and more. " +
+                    "Check if there are applications installed for things like ecommerce, " +
+                    " Can suggest just a few apps if not there, eg amazon, ebay, afterpay and so on. Suggest other apps if you can sense what the user may benefit from. Some ideas to compare with what is available:" + appKinds ;
 
+        }
     }
 
-    @Tool(
+    val webSearch = object : AgentTool(
         name = "webSearch",
         description = "Perform a web search using the default search engine." +
                 "If you don't see a clear result, use the click or scrollBrowser tools along with getUIHierarchy to look further.",
-        parameters = [
-            ParameterDef(
-                name = "query",
-                type = "string",
-                description = "What to search for"
-            )
-        ],
-        requiresContext = true,
-        requiresAccessibility = true
-    )
-    fun webSearch(
-        accessibilityService: AccessibilityService,
-        context: Context,
-        args: JSONObject
-    ): String {
-        val query = args.getString("query")
+        parameters = toolParameters {
+            string("query", "What to search for")
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+            val context = ctx.context
 
-        try {
-            val intent = Intent(Intent.ACTION_WEB_SEARCH)
-            intent.putExtra(SearchManager.QUERY, query)
-            intent.addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
-            context.startActivity(intent)
+            val query = args.getString("query")
 
-            Thread.sleep(appLoadTimeWait)
+            try {
+                val intent = Intent(Intent.ACTION_WEB_SEARCH)
+                intent.putExtra(SearchManager.QUERY, query)
+                intent.addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
+                context.startActivity(intent)
 
-            val activeWindow = accessibilityService.rootInActiveWindow
-                ?: return "The search is done, but no active window. " +
-                        "Check the UI hierarchy to see what happened."
+                ScreenWaiter.waitForSettled(accessibilityService, appLoadTimeWait) { ctx.isCancelled }
 
-            val hierarchy = buildCompactHierarchy(activeWindow)
+                val activeWindow = accessibilityService.rootInActiveWindow
+                    ?: return "The search is done, but no active window. " +
+                            "Check the UI hierarchy to see what happened."
 
-            return "The search is done. What follows are the results " +
-                    "(${coordinateHint}):\n\n${hierarchy}"
+                val hierarchy = buildCompactHierarchy(activeWindow)
 
-        } catch (e: Exception) {
-            return "Failed to perform web search: ${e.message}"
+                return "The search is done. What follows are the results " +
+                        "(${coordinateHint}):\n\n${hierarchy}"
+
+            } catch (e: Exception) {
+                return "Failed to perform web search: ${e.message}"
+            }
         }
     }
 
-    @Tool(
+    val openUrl = object : AgentTool(
         name = "openUrl",
         description = "Open a URL. IMPORTANT: When opening URLs in specific apps, you MUST use the app's URL scheme format, not a regular web URL. " +
                 "For example, for Google Maps, use 'geo:0,0?q=LOCATION' instead of 'https://www.google.com/maps/search/...'. " +
@@ -821,788 +1806,805 @@ object ToolHandler {
                 "- Navigation: 'google.navigation:q=DESTINATION' for directions\n" +
                 "- Street View: 'google.streetview:cbll=LAT,LONG' for street view\n" +
                 "When in doubt, check the error message which will list valid URL schemes for the specified app.",
-        parameters = [
-            ParameterDef(
-                name = "url",
-                type = "string",
-                description = "The URL to open. Must use the correct URL scheme if package_name is specified."
-            ),
-            ParameterDef(
-                name = "package_name",
-                type = "string",
-                description = "The package name of the app to open the URL in. If provided, the URL scheme will be validated against known schemes for that app.",
+        parameters = toolParameters {
+            string("url", "The URL to open. Must use the correct URL scheme if package_name is specified.")
+            string(
+                "package_name",
+                "The package name of the app to open the URL in. If provided, the URL scheme " +
+                        "will be validated against known schemes for that app.",
                 required = false
             )
-        ],
-        requiresContext = true,
-        requiresAccessibility = true
-    )
-    fun openUrl(
-        accessibilityService: AccessibilityService,
-        context: Context,
-        args: JSONObject
-    ): String {
-        val url = args.getString("url")
-        val packageName = args.optString("package_name", "")
-
-        Log.d("Tools", "openUrl called with url: $url, packageName: $packageName")
-        // If a package name is provided, validate the URL scheme
-        if (packageName.isNotEmpty()) {
-            val validSchemes = AppInstructions.getUrlSchemes(packageName)
-            if (validSchemes.isNotEmpty()) {
-                val urlScheme = url.substringBefore(":")
-
-                val isValid =
-                    validSchemes.any { scheme -> urlScheme == scheme.substringBefore(":") }
-
-                if (!isValid) {
-                    val error =
-                        "Error: Invalid URL scheme for app $packageName. Valid schemes are: ${
-                            validSchemes.joinToString(", ")
-                        }"
-                    Log.e("Tools", error)
-                    return error
+        },
+        requiresAccessibility = true,
+        concurrency = ToolConcurrency.UI
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val accessibilityService = ctx.accessibilityService
+            val context = ctx.context
+
+            val url = args.getString("url")
+            val packageName = args.optString("package_name").orEmpty()
+
+            Log.d("Tools", "openUrl called with url: $url, packageName: $packageName")
+            // If a package name is provided, validate the URL scheme
+            if (packageName.isNotEmpty()) {
+                val validSchemes = AppInstructions.getUrlSchemes(packageName)
+                if (validSchemes.isNotEmpty()) {
+                    val urlScheme = url.substringBefore(":")
+
+                    val isValid =
+                        validSchemes.any { scheme -> urlScheme == scheme.substringBefore(":") }
+
+                    if (!isValid) {
+                        val error =
+                            "Error: Invalid URL scheme for app $packageName. Valid schemes are: ${
+                                validSchemes.joinToString(", ")
+                            }"
+                        Log.e("Tools", error)
+                        return error
+                    }
                 }
             }
-        }
-
-        return try {
-            val intent = Intent(Intent.ACTION_VIEW, url.toUri())
-            intent.addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
-            context.startActivity(intent)
 
-            Thread.sleep(appLoadTimeWait)
-
-            val activeWindow = accessibilityService.rootInActiveWindow
-                ?: return "URL opened, but no active window. " +
-                        "Check the UI hierarchy to see what happened."
+            return try {
+                val intent = Intent(Intent.ACTION_VIEW, url.toUri())
+                intent.addFlags(Intent.FLAG_ACTIVITY_NEW_TASK)
+                context.startActivity(intent)
 
-            val appInfo = "App: ${activeWindow.packageName}"
-            val hierarchy = buildCompactHierarchy(activeWindow)
+                ScreenWaiter.waitForSettled(accessibilityService, appLoadTimeWait) { ctx.isCancelled }
 
-            return "The URL has been opened. ${appInfo}. What follows is the contents. " +
-                    "(${coordinateHint}):\n\n${hierarchy}"
-        } catch (e: Exception) {
-            Log.e("Tools", "Failed to open URL", e)
-            "Failed to open URL: ${e.message}"
-        }
-    }
+                val activeWindow = accessibilityService.rootInActiveWindow
+                    ?: return "URL opened, but no active window. " +
+                            "Check the UI hierarchy to see what happened."
 
-    @Tool(
-        name = "getCalendarEvents",
-        description = "Simply retrieves upcoming calendar events from the user's calendar. " +
-                "Can filter by date range and/or search terms." +
-                "If this doesn't work use open the calendar app and control it (and use the app for more sophisticated control)",
-        parameters = [
-            ParameterDef(
-                name = "days_ahead",
-                type = "integer",
-                description = "Number of days ahead to look for events (default: 7)",
-                required = false
-            ),
-            ParameterDef(
-                name = "search_term",
-                type = "string",
-                description = "Optional search term to filter events by title or description",
-                required = false
-            )
-        ],
-        requiresContext = true
-    )
-    fun getCalendarEvents(context: Context, args: JSONObject): String {
-        // Check if we have calendar permission
-        if (ContextCompat.checkSelfPermission(context, Manifest.permission.READ_CALENDAR) != PackageManager.PERMISSION_GRANTED) {
-            return "Calendar permission not granted. Please grant the Calendar permission in the app settings."
-        }
+                val appInfo = "App: ${activeWindow.packageName}"
+                val hierarchy = buildCompactHierarchy(activeWindow)
 
-        try {
-            val daysAhead = args.optInt("days_ahead", 7)
-            val searchTerm = args.optString("search_term", "").lowercase()
-
-            // Define time range
-            val startMillis = System.currentTimeMillis()
-            val endMillis = startMillis + (daysAhead * 24 * 60 * 60 * 1000L)
-
-            // Event projection
-            val projection = arrayOf(
-                android.provider.CalendarContract.Events._ID,
-                android.provider.CalendarContract.Events.TITLE,
-                android.provider.CalendarContract.Events.DESCRIPTION,
-                android.provider.CalendarContract.Events.DTSTART,
-                android.provider.CalendarContract.Events.DTEND,
-                android.provider.CalendarContract.Events.EVENT_LOCATION,
-                android.provider.CalendarContract.Events.ALL_DAY
-            )
+                return "The URL has been opened. ${appInfo}. What follows is the contents. " +
+                        "(${coordinateHint}):\n\n${hierarchy}"
+            } catch (e: Exception) {
+                Log.e("Tools", "Failed to open URL", e)
+                "Failed to open URL: ${e.message}"
+            }
+        }
+    }
 
-            // Query conditions
-            val selection = "${android.provider.CalendarContract.Events.DTSTART} >= ? AND " +
-                    "${android.provider.CalendarContract.Events.DTSTART} <= ?"
-            val selectionArgs = arrayOf(startMillis.toString(), endMillis.toString())
-
-            // Query the calendar
-            val uri = android.provider.CalendarContract.Events.CONTENT_URI
-            val cursor = context.contentResolver.query(
-                uri,
-                projection,
-                selection,
-                selectionArgs,
-                "${android.provider.CalendarContract.Events.DTSTART} ASC"
+    val getCalendarEvents = object : AgentTool(
+        name = "getCalendarEvents",
+        description = "Simply retrieves upcoming calendar events from the user's calendar. " +
+                "Can filter by date range and/or search terms." +
+                "If this doesn't work use open the calendar app and control it (and use the app for more sophisticated control)",
+        parameters = toolParameters {
+            integer("days_ahead", "Number of days ahead to look for events", default = 7)
+            string(
+                "search_term",
+                "Optional search term to filter events by title or description",
+                required = false
             )
-
-            if (cursor == null) {
-                return "Unable to access calendar data."
+        },
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            // Check if we have calendar permission
+            if (ContextCompat.checkSelfPermission(context, Manifest.permission.READ_CALENDAR) != PackageManager.PERMISSION_GRANTED) {
+                return "Calendar permission not granted. Please grant the Calendar permission in the app settings."
             }
 
-            val events = mutableListOf<Map<String, Any>>()
+            try {
+                val daysAhead = args.getInt("days_ahead")
+                val searchTerm = args.optString("search_term").orEmpty().lowercase()
+
+                // Define time range
+                val startMillis = System.currentTimeMillis()
+                val endMillis = startMillis + (daysAhead * 24 * 60 * 60 * 1000L)
+
+                // Event projection
+                val projection = arrayOf(
+                    android.provider.CalendarContract.Events._ID,
+                    android.provider.CalendarContract.Events.TITLE,
+                    android.provider.CalendarContract.Events.DESCRIPTION,
+                    android.provider.CalendarContract.Events.DTSTART,
+                    android.provider.CalendarContract.Events.DTEND,
+                    android.provider.CalendarContract.Events.EVENT_LOCATION,
+                    android.provider.CalendarContract.Events.ALL_DAY
+                )
 
-            // Column indices
-            val idIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events._ID)
-            val titleIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.TITLE)
-            val descIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DESCRIPTION)
-            val startIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DTSTART)
-            val endIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DTEND)
-            val locIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.EVENT_LOCATION)
-            val allDayIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.ALL_DAY)
+                // Query conditions
+                val selection = "${android.provider.CalendarContract.Events.DTSTART} >= ? AND " +
+                        "${android.provider.CalendarContract.Events.DTSTART} <= ?"
+                val selectionArgs = arrayOf(startMillis.toString(), endMillis.toString())
+
+                // Query the calendar
+                val uri = android.provider.CalendarContract.Events.CONTENT_URI
+                val cursor = context.contentResolver.query(
+                    uri,
+                    projection,
+                    selection,
+                    selectionArgs,
+                    "${android.provider.CalendarContract.Events.DTSTART} ASC"
+                )
 
-            // Process results
-            while (cursor.moveToNext()) {
-                val title = cursor.getString(titleIdx) ?: "Untitled Event"
-                val description = cursor.getString(descIdx) ?: ""
-                
-                // Apply search filter if provided
-                if (searchTerm.isNotEmpty() &&
-                    !title.lowercase().contains(searchTerm) &&
-                    !description.lowercase().contains(searchTerm)) {
-                    continue
+                if (cursor == null) {
+                    return "Unable to access calendar data."
                 }
+
+                val events = mutableListOf<Map<String, Any>>()
+
+                // Column indices
+                val idIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events._ID)
+                val titleIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.TITLE)
+                val descIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DESCRIPTION)
+                val startIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DTSTART)
+                val endIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.DTEND)
+                val locIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.EVENT_LOCATION)
+                val allDayIdx = cursor.getColumnIndex(android.provider.CalendarContract.Events.ALL_DAY)
+
+                // Process results
+                while (cursor.moveToNext()) {
+                    val title = cursor.getString(titleIdx) ?: "Untitled Event"
+                    val description = cursor.getString(descIdx) ?: ""
+                
+                    // Apply search filter if provided
+                    if (searchTerm.isNotEmpty() &&
+                        !title.lowercase().contains(searchTerm) &&
+                        !description.lowercase().contains(searchTerm)) {
+                        continue
+                    }
                 
-                val startTime = cursor.getLong(startIdx)
-                val endTime = cursor.getLong(endIdx)
-                val location = cursor.getString(locIdx) ?: ""
-                val isAllDay = cursor.getInt(allDayIdx) == 1
+                    val startTime = cursor.getLong(startIdx)
+                    val endTime = cursor.getLong(endIdx)
+                    val location = cursor.getString(locIdx) ?: ""
+                    val isAllDay = cursor.getInt(allDayIdx) == 1
                 
-                events.add(
-                    mapOf(
-                        "title" to title,
-                        "description" to description,
-                        "startTime" to startTime,
-                        "endTime" to endTime,
-                        "location" to location,
-                        "isAllDay" to isAllDay
+                    events.add(
+                        mapOf(
+                            "title" to title,
+                            "description" to description,
+                            "startTime" to startTime,
+                            "endTime" to endTime,
+                            "location" to location,
+                            "isAllDay" to isAllDay
+                        )
                     )
-                )
-            }
+                }
             
-            cursor.close()
+                cursor.close()
             
-            // Format the results
-            val sdf = java.text.SimpleDateFormat("EEE, MMM d, yyyy 'at' h:mm a", java.util.Locale.getDefault())
-            val resultBuilder = StringBuilder()
+                // Format the results
+                val sdf = java.text.SimpleDateFormat("EEE, MMM d, yyyy 'at' h:mm a", java.util.Locale.getDefault())
+                val resultBuilder = StringBuilder()
             
-            if (events.isEmpty()) {
-                resultBuilder.append("No events found")
-                if (searchTerm.isNotEmpty()) {
-                    resultBuilder.append(" matching '${searchTerm}'")
-                }
-                resultBuilder.append(" in the next $daysAhead days.")
-            } else {
-                resultBuilder.append("Found ${events.size} events")
-                if (searchTerm.isNotEmpty()) {
-                    resultBuilder.append(" matching '${searchTerm}'")
-                }
-                resultBuilder.append(" in the next $daysAhead days:\n\n")
+                if (events.isEmpty()) {
+                    resultBuilder.append("No events found")
+                    if (searchTerm.isNotEmpty()) {
+                        resultBuilder.append(" matching '${searchTerm}'")
+                    }
+                    resultBuilder.append(" in the next $daysAhead days.")
+                } else {
+                    resultBuilder.append("Found ${events.size} events")
+                    if (searchTerm.isNotEmpty()) {
+                        resultBuilder.append(" matching '${searchTerm}'")
+                    }
+                    resultBuilder.append(" in the next $daysAhead days:\n\n")
                 
-                var currentDate = ""
+                    var currentDate = ""
                 
-                events.forEach { event ->
-                    val startDate = java.util.Date(event["startTime"] as Long)
-                    val dateStr = sdf.format(startDate).substringBefore("at").trim()
+                    events.forEach { event ->
+                        val startDate = java.util.Date(event["startTime"] as Long)
+                        val dateStr = sdf.format(startDate).substringBefore("at").trim()
                     
-                    // Add date header if it's a new date
-                    if (dateStr != currentDate) {
-                        currentDate = dateStr
-                        resultBuilder.append("=== $currentDate ===\n")
-                    }
+                        // Add date header if it's a new date
+                        if (dateStr != currentDate) {
+                            currentDate = dateStr
+                            resultBuilder.append("=== $currentDate ===\n")
+                        }
                     
-                    // Format time
-                    val timeStr = if (event["isAllDay"] as Boolean) {
-                        "All day"
-                    } else {
-                        val startTimeStr = sdf.format(startDate).substringAfter("at").trim()
-                        val endTimeStr = sdf.format(java.util.Date(event["endTime"] as Long)).substringAfter("at").trim()
-                        "$startTimeStr - $endTimeStr"
-                    }
+                        // Format time
+                        val timeStr = if (event["isAllDay"] as Boolean) {
+                            "All day"
+                        } else {
+                            val startTimeStr = sdf.format(startDate).substringAfter("at").trim()
+                            val endTimeStr = sdf.format(java.util.Date(event["endTime"] as Long)).substringAfter("at").trim()
+                            "$startTimeStr - $endTimeStr"
+                        }
                     
-                    // Add event details
-                    resultBuilder.append("• ${event["title"]}\n")
-                    resultBuilder.append("  Time: $timeStr\n")
+                        // Add event details
+                        resultBuilder.append("• ${event["title"]}\n")
+                        resultBuilder.append("  Time: $timeStr\n")
                     
-                    if ((event["location"] as String).isNotEmpty()) {
-                        resultBuilder.append("  Location: ${event["location"]}\n")
-                    }
+                        if ((event["location"] as String).isNotEmpty()) {
+                            resultBuilder.append("  Location: ${event["location"]}\n")
+                        }
                     
-                    if ((event["description"] as String).isNotEmpty()) {
-                        val desc = event["description"] as String
-                        val shortDesc = if (desc.length > 100) desc.substring(0, 97) + "..." else desc
-                        resultBuilder.append("  Description: $shortDesc\n")
-                    }
+                        if ((event["description"] as String).isNotEmpty()) {
+                            val desc = event["description"] as String
+                            val shortDesc = if (desc.length > 100) desc.substring(0, 97) + "..." else desc
+                            resultBuilder.append("  Description: $shortDesc\n")
+                        }
                     
-                    resultBuilder.append("\n")
+                        resultBuilder.append("\n")
+                    }
                 }
-            }
             
-            return resultBuilder.toString().trim()
+                return resultBuilder.toString().trim()
             
-        } catch (e: Exception) {
-            Log.e("CalendarTool", "Error accessing calendar: ${e.message}")
-            return "Error accessing calendar: ${e.message}"
+            } catch (e: Exception) {
+                Log.e("CalendarTool", "Error accessing calendar: ${e.message}")
+                return "Error accessing calendar: ${e.message}"
+            }
         }
     }
 
-    @Tool(
+    val searchContacts = object : AgentTool(
         name = "searchContacts",
         description = "simply searches the user's contacts by name, phone number, or email. " +
                 "Requires contacts permission. If this doesn't work you can open the contacts app and control it for more access",
-        parameters = [
-            ParameterDef(
-                name = "query",
-                type = "string",
-                description = "Search term to find in contacts (name, phone, email)"
-            ),
-            ParameterDef(
-                name = "limit",
-                type = "integer",
-                description = "Maximum number of contacts to return (default: 10)",
-                required = false
-            )
-        ],
-        requiresContext = true
-    )
-    fun searchContacts(context: Context, args: JSONObject): String {
-        // Check if we have contacts permission
-        if (ContextCompat.checkSelfPermission(context, Manifest.permission.READ_CONTACTS) != PackageManager.PERMISSION_GRANTED) {
-            return "Contacts permission not granted. Please grant the Contacts permission in the app settings."
-        }
+        parameters = toolParameters {
+            string("query", "Search term to find in contacts (name, phone, email)")
+            integer("limit", "Maximum number of contacts to return", default = 10)
+        },
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            // Check if we have contacts permission
+            if (ContextCompat.checkSelfPermission(context, Manifest.permission.READ_CONTACTS) != PackageManager.PERMISSION_GRANTED) {
+                return "Contacts permission not granted. Please grant the Contacts permission in the app settings."
+            }
 
-        try {
-            val query = args.getString("query")
-            val limit = args.optInt("limit", 10)
+            try {
+                val query = args.getString("query")
+                val limit = args.getInt("limit")
             
-            if (query.isBlank()) {
-                return "Please provide a search term to find contacts."
-            }
+                if (query.isBlank()) {
+                    return "Please provide a search term to find contacts."
+                }
             
-            // Define the columns we want
-            val projection = arrayOf(
-                android.provider.ContactsContract.Contacts._ID,
-                android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY,
-                android.provider.ContactsContract.Contacts.HAS_PHONE_NUMBER
-            )
+                // Define the columns we want
+                val projection = arrayOf(
+                    android.provider.ContactsContract.Contacts._ID,
+                    android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY,
+                    android.provider.ContactsContract.Contacts.HAS_PHONE_NUMBER
+                )
             
-            // Query conditions - search by display name
-            val selection = "${android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY} LIKE ?"
-            val selectionArgs = arrayOf("%$query%")
+                // Query conditions - search by display name
+                val selection = "${android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY} LIKE ?"
+                val selectionArgs = arrayOf("%$query%")
             
-            // Query the contacts
-            val cursor = context.contentResolver.query(
-                android.provider.ContactsContract.Contacts.CONTENT_URI,
-                projection,
-                selection,
-                selectionArgs,
-                "${android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY} ASC LIMIT $limit"
-            )
+                // Query the contacts
+                val cursor = context.contentResolver.query(
+                    android.provider.ContactsContract.Contacts.CONTENT_URI,
+                    projection,
+                    selection,
+                    selectionArgs,
+                    "${android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY} ASC LIMIT $limit"
+                )
             
-            if (cursor == null) {
-                return "Unable to access contacts data."
-            }
+                if (cursor == null) {
+                    return "Unable to access contacts data."
+                }
             
-            val contacts = mutableListOf<Map<String, Any>>()
+                val contacts = mutableListOf<Map<String, Any>>()
             
-            // Column indices
-            val idIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts._ID)
-            val nameIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY)
-            val hasPhoneIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts.HAS_PHONE_NUMBER)
+                // Column indices
+                val idIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts._ID)
+                val nameIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts.DISPLAY_NAME_PRIMARY)
+                val hasPhoneIdx = cursor.getColumnIndex(android.provider.ContactsContract.Contacts.HAS_PHONE_NUMBER)
             
-            // Process results
-            while (cursor.moveToNext()) {
-                val id = cursor.getString(idIdx)
-                val name = cursor.getString(nameIdx) ?: "Unknown"
-                val hasPhone = cursor.getInt(hasPhoneIdx) > 0
+                // Process results
+                while (cursor.moveToNext()) {
+                    val id = cursor.getString(idIdx)
+                    val name = cursor.getString(nameIdx) ?: "Unknown"
+                    val hasPhone = cursor.getInt(hasPhoneIdx) > 0
                 
-                val phoneNumbers = mutableListOf<String>()
-                val emails = mutableListOf<String>()
+                    val phoneNumbers = mutableListOf<String>()
+                    val emails = mutableListOf<String>()
                 
-                // Get phone numbers if available
-                if (hasPhone) {
-                    val phoneCursor = context.contentResolver.query(
-                        android.provider.ContactsContract.CommonDataKinds.Phone.CONTENT_URI,
-                        arrayOf(android.provider.ContactsContract.CommonDataKinds.Phone.NUMBER),
-                        "${android.provider.ContactsContract.CommonDataKinds.Phone.CONTACT_ID} = ?",
-                        arrayOf(id),
-                        null
-                    )
+                    // Get phone numbers if available
+                    if (hasPhone) {
+                        val phoneCursor = context.contentResolver.query(
+                            android.provider.ContactsContract.CommonDataKinds.Phone.CONTENT_URI,
+                            arrayOf(android.provider.ContactsContract.CommonDataKinds.Phone.NUMBER),
+                            "${android.provider.ContactsContract.CommonDataKinds.Phone.CONTACT_ID} = ?",
+                            arrayOf(id),
+                            null
+                        )
                     
-                    phoneCursor?.use { pc ->
-                        val phoneIdx = pc.getColumnIndex(android.provider.ContactsContract.CommonDataKinds.Phone.NUMBER)
-                        while (pc.moveToNext()) {
-                            pc.getString(phoneIdx)?.let { phoneNumbers.add(it) }
+                        phoneCursor?.use { pc ->
+                            val phoneIdx = pc.getColumnIndex(android.provider.ContactsContract.CommonDataKinds.Phone.NUMBER)
+                            while (pc.moveToNext()) {
+                                pc.getString(phoneIdx)?.let { phoneNumbers.add(it) }
+                            }
                         }
                     }
-                }
                 
-                // Get email addresses
-                val emailCursor = context.contentResolver.query(
-                    android.provider.ContactsContract.CommonDataKinds.Email.CONTENT_URI,
-                    arrayOf(android.provider.ContactsContract.CommonDataKinds.Email.DATA),
-                    "${android.provider.ContactsContract.CommonDataKinds.Email.CONTACT_ID} = ?",
-                    arrayOf(id),
-                    null
-                )
+                    // Get email addresses
+                    val emailCursor = context.contentResolver.query(
+                        android.provider.ContactsContract.CommonDataKinds.Email.CONTENT_URI,
+                        arrayOf(android.provider.ContactsContract.CommonDataKinds.Email.DATA),
+                        "${android.provider.ContactsContract.CommonDataKinds.Email.CONTACT_ID} = ?",
+                        arrayOf(id),
+                        null
+                    )
                 
-                emailCursor?.use { ec ->
-                    val emailIdx = ec.getColumnIndex(android.provider.ContactsContract.CommonDataKinds.Email.DATA)
-                    while (ec.moveToNext()) {
-                        ec.getString(emailIdx)?.let { emails.add(it) }
+                    emailCursor?.use { ec ->
+                        val emailIdx = ec.getColumnIndex(android.provider.ContactsContract.CommonDataKinds.Email.DATA)
+                        while (ec.moveToNext()) {
+                            ec.getString(emailIdx)?.let { emails.add(it) }
+                        }
                     }
-                }
                 
-                // Only add contact if it matches the query in name, phone, or email
-                val matchesPhone = phoneNumbers.any { it.contains(query) }
-                val matchesEmail = emails.any { it.lowercase().contains(query.lowercase()) }
+                    // Only add contact if it matches the query in name, phone, or email
+                    val matchesPhone = phoneNumbers.any { it.contains(query) }
+                    val matchesEmail = emails.any { it.lowercase().contains(query.lowercase()) }
                 
-                if (name.lowercase().contains(query.lowercase()) || matchesPhone || matchesEmail) {
-                    contacts.add(
-                        mapOf(
-                            "name" to name,
-                            "phones" to phoneNumbers,
-                            "emails" to emails
+                    if (name.lowercase().contains(query.lowercase()) || matchesPhone || matchesEmail) {
+                        contacts.add(
+                            mapOf(
+                                "name" to name,
+                                "phones" to phoneNumbers,
+                                "emails" to emails
+                            )
                         )
-                    )
-                }
+                    }
                 
-                // Stop if we've reached the limit
-                if (contacts.size >= limit) {
-                    break
+                    // Stop if we've reached the limit
+                    if (contacts.size >= limit) {
+                        break
+                    }
                 }
-            }
             
-            cursor.close()
+                cursor.close()
             
-            // Format the results
-            val resultBuilder = StringBuilder()
+                // Format the results
+                val resultBuilder = StringBuilder()
             
-            if (contacts.isEmpty()) {
-                resultBuilder.append("No contacts found matching '$query'.")
-            } else {
-                resultBuilder.append("Found ${contacts.size} contacts matching '$query':\n\n")
+                if (contacts.isEmpty()) {
+                    resultBuilder.append("No contacts found matching '$query'.")
+                } else {
+                    resultBuilder.append("Found ${contacts.size} contacts matching '$query':\n\n")
                 
-                contacts.forEachIndexed { index, contact ->
-                    resultBuilder.append("${index + 1}. ${contact["name"]}\n")
+                    contacts.forEachIndexed { index, contact ->
+                        resultBuilder.append("${index + 1}. ${contact["name"]}\n")
                     
-                    val phones = contact["phones"] as List<String>
-                    if (phones.isNotEmpty()) {
-                        resultBuilder.append("   Phone: ${phones.first()}")
-                        if (phones.size > 1) {
-                            resultBuilder.append(" (+${phones.size - 1} more)")
+                        val phones = contact["phones"] as List<String>
+                        if (phones.isNotEmpty()) {
+                            resultBuilder.append("   Phone: ${phones.first()}")
+                            if (phones.size > 1) {
+                                resultBuilder.append(" (+${phones.size - 1} more)")
+                            }
+                            resultBuilder.append("\n")
                         }
-                        resultBuilder.append("\n")
-                    }
                     
-                    val emails = contact["emails"] as List<String>
-                    if (emails.isNotEmpty()) {
-                        resultBuilder.append("   Email: ${emails.first()}")
-                        if (emails.size > 1) {
-                            resultBuilder.append(" (+${emails.size - 1} more)")
+                        val emails = contact["emails"] as List<String>
+                        if (emails.isNotEmpty()) {
+                            resultBuilder.append("   Email: ${emails.first()}")
+                            if (emails.size > 1) {
+                                resultBuilder.append(" (+${emails.size - 1} more)")
+                            }
+                            resultBuilder.append("\n")
                         }
+                    
                         resultBuilder.append("\n")
                     }
-                    
-                    resultBuilder.append("\n")
                 }
-            }
             
-            return resultBuilder.toString().trim()
+                return resultBuilder.toString().trim()
             
-        } catch (e: Exception) {
-            Log.e("ContactsTool", "Error accessing contacts: ${e.message}")
-            return "Error accessing contacts: ${e.message}"
+            } catch (e: Exception) {
+                Log.e("ContactsTool", "Error accessing contacts: ${e.message}")
+                return "Error accessing contacts: ${e.message}"
+            }
         }
     }
 
-    @Tool(
+    val searchPastConversations = object : AgentTool(
         name = "searchPastConversations",
         description = "Search through past conversations to retrieve conversation history. When query is provided, searches for specific text. When query is omitted, returns recent conversation history, only use if getLastConversationContext didn't help.",
-        parameters = [
-            ParameterDef(
-                name = "query",
-                type = "string",
-                description = "Optional search term to look for in past conversations. If omitted, returns recent conversation history without searching.",
-                required = false
-            ),
-            ParameterDef(
-                name = "max_conversations",
-                type = "integer",
-                description = "Maximum number of conversations to retrieve or search through (default: 4)",
-                required = false
-            ),
-            ParameterDef(
-                name = "max_messages_per_conversation",
-                type = "integer",
-                description = "Maximum number of messages to retrieve per conversation (default: 5)",
+        parameters = toolParameters {
+            string(
+                "query",
+                "Optional search term to look for in past conversations. If omitted, returns " +
+                        "recent conversation history without searching.",
                 required = false
             )
-        ],
-        requiresContext = true
-    )
-    fun searchPastConversations(context: Context, args: JSONObject): String {
-        val hasQuery = args.has("query")
-        val query = if (hasQuery) args.getString("query").lowercase() else ""
-        val maxConversations = args.optInt("max_conversations", 4)
-        val maxMessagesPerConversation = args.optInt("max_messages_per_conversation", 5)
+            integer(
+                "max_conversations",
+                "Maximum number of conversations to retrieve or search through",
+                default = 4
+            )
+            integer(
+                "max_messages_per_conversation",
+                "Maximum number of messages to retrieve per conversation",
+                default = 5
+            )
+        },
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val hasQuery = args.has("query")
+            val query = if (hasQuery) args.getString("query").lowercase() else ""
+            val maxConversations = args.getInt("max_conversations")
+            val maxMessagesPerConversation = args.getInt("max_messages_per_conversation")
         
-        val agent = Agent.getInstance() ?: return "Error: Agent not available"
-        val conversationManager = agent.conversationManager
-        val allConversations = conversationManager.conversations.value
+            val agent = Agent.getInstance() ?: return "Error: Agent not available"
+            val conversationManager = agent.conversationManager
+            val allConversations = conversationManager.conversations.value
         
-        if (allConversations.isEmpty()) {
-            return "No past conversations found."
-        }
+            if (allConversations.isEmpty()) {
+                return "No past conversations found."
+            }
         
-        // Get conversations - either search by query or just get recent ones
-        val selectedConversations = if (hasQuery) {
-            // Search for conversations containing the query
-            allConversations
-                .filter { conversation ->
-                    conversation.messages.any { message ->
-                        // Only search in user and assistant messages
-                        (message.role == "user" || message.role == "assistant") &&
-                        message.content?.filterIsInstance<Content.Text>()?.any { 
-                            it.text.lowercase().contains(query) 
-                        } == true
+            // Get conversations - either search by query or just get recent ones
+            val selectedConversations = if (hasQuery) {
+                // Search for conversations containing the query
+                allConversations
+                    .filter { conversation ->
+                        conversation.messages.any { message ->
+                            // Only search in user and assistant messages
+                            (message.role == "user" || message.role == "assistant") &&
+                            message.content?.filterIsInstance<Content.Text>()?.any { 
+                                it.text.lowercase().contains(query) 
+                            } == true
+                        }
                     }
+                    .take(maxConversations)
+            } else {
+                // Just get the most recent conversations
+                allConversations
+                    .sortedByDescending { it.startTime }
+                    .take(maxConversations)
+            }
+        
+            if (selectedConversations.isEmpty()) {
+                return if (hasQuery) {
+                    "No conversations found matching query: \"$query\""
+                } else {
+                    "No conversations found. Consider using no query or a different query or looking further."
                 }
-                .take(maxConversations)
-        } else {
-            // Just get the most recent conversations
-            allConversations
-                .sortedByDescending { it.startTime }
-                .take(maxConversations)
-        }
+            }
         
-        if (selectedConversations.isEmpty()) {
-            return if (hasQuery) {
-                "No conversations found matching query: \"$query\""
+            val resultBuilder = StringBuilder()
+            if (hasQuery) {
+                resultBuilder.append("Found ${selectedConversations.size} conversations matching \"$query\":\n\n")
             } else {
-                "No conversations found. Consider using no query or a different query or looking further."
+                resultBuilder.append("Retrieved ${selectedConversations.size} most recent conversations:\n\n")
             }
-        }
-        
-        val resultBuilder = StringBuilder()
-        if (hasQuery) {
-            resultBuilder.append("Found ${selectedConversations.size} conversations matching \"$query\":\n\n")
-        } else {
-            resultBuilder.append("Retrieved ${selectedConversations.size} most recent conversations:\n\n")
-        }
         
-        // Process each selected conversation
-        selectedConversations.forEachIndexed { index, conversation ->
-            // Get conversation title
-            val title = xyz.block.gosling.features.agent.getConversationTitle(conversation)
-            val formattedDate = java.text.SimpleDateFormat(
-                "MMM d, yyyy 'at' h:mm a", 
-                java.util.Locale.getDefault()
-            ).format(java.util.Date(conversation.startTime))
+            // Process each selected conversation
+            selectedConversations.forEachIndexed { index, conversation ->
+                // Get conversation title
+                val title = xyz.block.gosling.features.agent.getConversationTitle(conversation)
+                val formattedDate = java.text.SimpleDateFormat(
+                    "MMM d, yyyy 'at' h:mm a", 
+                    java.util.Locale.getDefault()
+                ).format(java.util.Date(conversation.startTime))
             
-            resultBuilder.append("--- Conversation ${index + 1}: $title ($formattedDate) ---\n")
+                resultBuilder.append("--- Conversation ${index + 1}: $title ($formattedDate) ---\n")
             
-            // Get relevant messages (filter out system and tool messages, focus on user-assistant exchange)
-            val relevantMessages = conversation.messages
-                .filter { it.role == "user" || it.role == "assistant" }
-                .filter { message ->
-                    message.content?.filterIsInstance<Content.Text>()?.isNotEmpty() == true
-                }
-                .takeLast(maxMessagesPerConversation)
+                // Get relevant messages (filter out system and tool messages, focus on user-assistant exchange)
+                val relevantMessages = conversation.messages
+                    .filter { it.role == "user" || it.role == "assistant" }
+                    .filter { message ->
+                        message.content?.filterIsInstance<Content.Text>()?.isNotEmpty() == true
+                    }
+                    .takeLast(maxMessagesPerConversation)
             
-            if (relevantMessages.isEmpty()) {
-                resultBuilder.append("No relevant messages found in this conversation.\n\n")
-                return@forEachIndexed
-            }
+                if (relevantMessages.isEmpty()) {
+                    resultBuilder.append("No relevant messages found in this conversation.\n\n")
+                    return@forEachIndexed
+                }
             
-            // Format and add messages
-            relevantMessages.forEach { message ->
-                val role = if (message.role == "user") "User" else "Assistant"
-                val messageText = xyz.block.gosling.features.agent.firstText(message)
+                // Format and add messages
+                relevantMessages.forEach { message ->
+                    val role = if (message.role == "user") "User" else "Assistant"
+                    val messageText = xyz.block.gosling.features.agent.firstText(message)
                 
-                // Highlight query matches in the text if we're searching
-                val highlightedText = if (hasQuery && messageText.lowercase().contains(query)) {
-                    val startIndex = messageText.lowercase().indexOf(query)
-                    val endIndex = startIndex + query.length
-                    val before = messageText.substring(0, startIndex)
-                    val match = messageText.substring(startIndex, endIndex)
-                    val after = messageText.substring(endIndex)
-                    "$before[$match]$after"
-                } else {
-                    messageText
-                }
+                    // Highlight query matches in the text if we're searching
+                    val highlightedText = if (hasQuery && messageText.lowercase().contains(query)) {
+                        val startIndex = messageText.lowercase().indexOf(query)
+                        val endIndex = startIndex + query.length
+                        val before = messageText.substring(0, startIndex)
+                        val match = messageText.substring(startIndex, endIndex)
+                        val after = messageText.substring(endIndex)
+                        "$before[$match]$after"
+                    } else {
+                        messageText
+                    }
                 
-                resultBuilder.append("$role: $highlightedText\n")
-            }
+                    resultBuilder.append("$role: $highlightedText\n")
+                }
             
-            resultBuilder.append("\n")
-        }
+                resultBuilder.append("\n")
+            }
         
-        return resultBuilder.toString().trim()
+            return resultBuilder.toString().trim()
+        }
     }
 
-    @Tool(
+    val getLastConversationContext = object : AgentTool(
         name = "getLastConversationContext",
         description = "Use this if the user is asking something which implies past context or continuing on, this will retrieve the last user message and assistant reply from the previous conversation for context. Useful when continuing a conversation, and you may often want to use it to be sure.",
-        parameters = [],
-        requiresContext = true
-    )
-    fun getLastConversationContext(context: Context, args: JSONObject): String {
-        val agent = Agent.getInstance() ?: return "Error: Agent not available"
-        val conversationManager = agent.conversationManager
-        val allConversations = conversationManager.conversations.value
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            val agent = Agent.getInstance() ?: return "Error: Agent not available"
+            val conversationManager = agent.conversationManager
+            val allConversations = conversationManager.conversations.value
         
-        if (allConversations.isEmpty()) {
-            return "No previous conversations found."
-        }
+            if (allConversations.isEmpty()) {
+                return "No previous conversations found."
+            }
         
-        // Get the most recent conversation (excluding current one if possible)
-        val sortedConversations = allConversations.sortedByDescending { it.startTime }
-        val lastConversation = if (sortedConversations.size > 1) {
-            // If there's more than one conversation, get the second most recent
-            // (assuming the first is the current one)
-            sortedConversations[1]
-        } else {
-            // Otherwise just get the most recent one
-            sortedConversations[0]
-        }
+            // Get the most recent conversation (excluding current one if possible)
+            val sortedConversations = allConversations.sortedByDescending { it.startTime }
+            val lastConversation = if (sortedConversations.size > 1) {
+                // If there's more than one conversation, get the second most recent
+                // (assuming the first is the current one)
+                sortedConversations[1]
+            } else {
+                // Otherwise just get the most recent one
+                sortedConversations[0]
+            }
         
-        // Get conversation details
-        val title = xyz.block.gosling.features.agent.getConversationTitle(lastConversation)
-        val formattedDate = java.text.SimpleDateFormat(
-            "MMM d, yyyy 'at' h:mm a", 
-            java.util.Locale.getDefault()
-        ).format(java.util.Date(lastConversation.startTime))
+            // Get conversation details
+            val title = xyz.block.gosling.features.agent.getConversationTitle(lastConversation)
+            val formattedDate = java.text.SimpleDateFormat(
+                "MMM d, yyyy 'at' h:mm a", 
+                java.util.Locale.getDefault()
+            ).format(java.util.Date(lastConversation.startTime))
         
-        // Find the last user and assistant messages
-        val userMessages = lastConversation.messages.filter { it.role == "user" }
-        val assistantMessages = lastConversation.messages.filter { it.role == "assistant" }
+            // Find the last user and assistant messages
+            val userMessages = lastConversation.messages.filter { it.role == "user" }
+            val assistantMessages = lastConversation.messages.filter { it.role == "assistant" }
         
-        if (userMessages.isEmpty() && assistantMessages.isEmpty()) {
-            return "Previous conversation found ($title, $formattedDate) but it contains no user or assistant messages."
-        }
+            if (userMessages.isEmpty() && assistantMessages.isEmpty()) {
+                return "Previous conversation found ($title, $formattedDate) but it contains no user or assistant messages."
+            }
         
-        val resultBuilder = StringBuilder()
-        resultBuilder.append("Context from previous conversation ($title, $formattedDate):\n\n")
+            val resultBuilder = StringBuilder()
+            resultBuilder.append("Context from previous conversation ($title, $formattedDate):\n\n")
         
-        // Get the last user message if available
-        val lastUserMessage = userMessages.lastOrNull()
-        if (lastUserMessage != null) {
-            val userText = xyz.block.gosling.features.agent.firstText(lastUserMessage)
-            resultBuilder.append("Last user message: $userText\n\n")
-        } else {
-            resultBuilder.append("No user message in the previous conversation.\n\n")
-        }
+            // Get the last user message if available
+            val lastUserMessage = userMessages.lastOrNull()
+            if (lastUserMessage != null) {
+                val userText = xyz.block.gosling.features.agent.firstText(lastUserMessage)
+                resultBuilder.append("Last user message: $userText\n\n")
+            } else {
+                resultBuilder.append("No user message in the previous conversation.\n\n")
+            }
         
-        // Get the last assistant message if available
-        val lastAssistantMessage = assistantMessages.lastOrNull()
-        if (lastAssistantMessage != null) {
-            val assistantText = xyz.block.gosling.features.agent.firstText(lastAssistantMessage)
-            resultBuilder.append("Last assistant response: $assistantText")
-        } else {
-            resultBuilder.append("No assistant response in the previous conversation.")
-        }
+            // Get the last assistant message if available
+            val lastAssistantMessage = assistantMessages.lastOrNull()
+            if (lastAssistantMessage != null) {
+                val assistantText = xyz.block.gosling.features.agent.firstText(lastAssistantMessage)
+                resultBuilder.append("Last assistant response: $assistantText")
+            } else {
+                resultBuilder.append("No assistant response in the previous conversation.")
+            }
         
-        return resultBuilder.toString().trim() + "\n Also consider using searchPastConversations if more context needed."
+            return resultBuilder.toString().trim() + "\n Also consider using searchPastConversations if more context needed."
+        }
     }
 
-    @Tool(
+    val notificationHandler = object : AgentTool(
         name = "notificationHandler",
         description = "Use this when user wants to configure automatic notification/message handling to take actions when events happen. Enable or disable notification processing and set rules for how notifications (such as messages, calendar events, app notifications) should be handled.",
-        parameters = [
-            ParameterDef(
-                name = "enable",
-                type = "boolean",
-                description = "Whether to enable or disable automatic notification processing"
-            ),
-            ParameterDef(
-                name = "rules",
-                type = "string",
-                description = "Rules for handling notifications. These are instructions for how to process different types of notifications/events as they come in.",
-                required = false
-            ),
-            ParameterDef(
-                name = "replaceRules",
-                type = "boolean",
-                description = "If true, the provided rules will replace existing rules. If false, the rules will be appended to existing rules.",
+        parameters = toolParameters {
+            boolean("enable", "Whether to enable or disable automatic notification processing")
+            string(
+                "rules",
+                "Rules for handling notifications. These are instructions for how to process " +
+                        "different types of notifications/events as they come in.",
                 required = false
             )
-        ],
-        requiresContext = true
-    )
-    fun notificationHandler(context: Context, args: JSONObject): String {
-        val enable = args.getBoolean("enable")
-        val rules = if (args.has("rules")) args.getString("rules") else null
-        val replaceRules = args.optBoolean("replaceRules", true) // Default to replacing rules
+            boolean(
+                "replaceRules",
+                "If true, the provided rules will replace existing rules. If false, the rules " +
+                        "will be appended to existing rules.",
+                default = true
+            )
+        }
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            val enable = args.getBoolean("enable")
+            val rules = args.optString("rules")
+            val replaceRules = args.getBoolean("replaceRules")
         
-        val settings = xyz.block.gosling.features.settings.SettingsStore(context)
+            val settings = xyz.block.gosling.features.settings.SettingsStore(context)
         
-        // Update notification processing setting
-        settings.shouldProcessNotifications = enable
+            // Update notification processing setting
+            settings.shouldProcessNotifications = enable
         
-        // Update rules if provided
-        if (rules != null) {
-            if (replaceRules) {
-                // Replace existing rules
-                settings.messageHandlingPreferences = rules
-            } else {
-                // Append to existing rules
-                val existingRules = settings.messageHandlingPreferences
-                val updatedRules = if (existingRules.isBlank()) {
-                    rules
+            // Update rules if provided
+            if (rules != null) {
+                if (replaceRules) {
+                    // Replace existing rules
+                    settings.messageHandlingPreferences = rules
                 } else {
-                    "$existingRules\n\n$rules"
+                    // Append to existing rules
+                    val existingRules = settings.messageHandlingPreferences
+                    val updatedRules = if (existingRules.isBlank()) {
+                        rules
+                    } else {
+                        "$existingRules\n\n$rules"
+                    }
+                    settings.messageHandlingPreferences = updatedRules
                 }
-                settings.messageHandlingPreferences = updatedRules
             }
-        }
         
-        val currentRules = settings.messageHandlingPreferences
-        val rulesStatus = if (currentRules.isBlank()) {
-            "No specific handling rules are configured."
-        } else {
-            "Current rules: $currentRules"
-        }
+            val currentRules = settings.messageHandlingPreferences
+            val rulesStatus = if (currentRules.isBlank()) {
+                "No specific handling rules are configured."
+            } else {
+                "Current rules: $currentRules"
+            }
         
-        val actionTaken = if (rules != null) {
-            if (replaceRules) "Rules have been replaced." else "Rules have been appended."
-        } else {
-            "Rules were not modified."
-        }
+            val actionTaken = if (rules != null) {
+                if (replaceRules) "Rules have been replaced." else "Rules have been appended."
+            } else {
+                "Rules were not modified."
+            }
         
-        return if (enable) {
-            "Notification handling has been enabled. $actionTaken $rulesStatus"
-        } else {
-            "Notification handling has been disabled. $actionTaken Rules are preserved but will not be applied."
+            return if (enable) {
+                "Notification handling has been enabled. $actionTaken $rulesStatus"
+            } else {
+                "Notification handling has been disabled. $actionTaken Rules are preserved but will not be applied."
+            }
+        }
+    }
+
+    val listResources = object : AgentTool(
+        name = "listResources",
+        description = "List data that installed apps share through extensions, such as " +
+                "orders, notes or playlists. Read one with readResource. Reading is much " +
+                "faster than opening the app.",
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val settings = xyz.block.gosling.features.settings.SettingsStore(ctx.context)
+            if (!settings.enableAppExtensions) {
+                return "App extensions are turned off in settings."
+            }
+
+            val mcps = McpCatalog.servers(ctx.context).filter { it.resources.isNotEmpty() }
+            if (mcps.isEmpty()) return "No installed app shares any resources."
+
+            return mcps.joinToString("\n\n") { mcp ->
+                "${mcp.info.name} (server ${mcp.localId}):\n" +
+                        mcp.resources.joinToString("\n") { resource ->
+                            val description = resource.description.ifEmpty { null }
+                                ?.let { " - $it" }.orEmpty()
+                            "- ${resource.uri}: ${resource.name}$description"
+                        }
+            }
+        }
+    }
+
+    val readResource = object : AgentTool(
+        name = "readResource",
+        description = "Read a resource listed by listResources.",
+        parameters = toolParameters {
+            string("server", "Server id shown by listResources, e.g. ab1")
+            string("uri", "URI of the resource")
+        },
+        concurrency = ToolConcurrency.CONCURRENT
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val uri = args.getString("uri")
+            return when (val result = MobileMCP.readResource(ctx.context, args.getString("server"), uri)) {
+                is McpResult.Success -> result.text
+                is McpResult.Error -> toolError(
+                    tool = name,
+                    type = result.code.wireName,
+                    message = result.message
+                )
+            }
         }
     }
 
-    @Tool(
+    val storeMemory = object : AgentTool(
         name = "storeMemory",
         description = "Store a fact or preference about the user that should be remembered across conversations.",
-        parameters = [
-            ParameterDef(
-                name = "memory",
-                type = "string",
-                description = "The information to store, keep it extremely brief"
-            ),
-            ParameterDef(
-                name = "overwrite",
-                type = "boolean",
-                description = "Whether to overwrite existing memories (true) or append to them (false)",
-                required = false
+        parameters = toolParameters {
+            string("memory", "The information to store, keep it extremely brief")
+            boolean(
+                "overwrite",
+                "Whether to overwrite existing memories (true) or append to them (false)",
+                default = false
             )
-        ],
-        requiresContext = true
-    )
-    fun storeMemory(context: Context, args: JSONObject): String {
-        val memory = args.getString("memory")
-        val overwrite = args.optBoolean("overwrite", false) // Default to appending
+        }
+    ) {
+        override fun execute(ctx: ToolContext, args: ToolArguments): String {
+            val context = ctx.context
+
+            val memory = args.getString("memory")
+            val overwrite = args.getBoolean("overwrite")
         
-        val settings = xyz.block.gosling.features.settings.SettingsStore(context)
+            val settings = xyz.block.gosling.features.settings.SettingsStore(context)
         
-        // First, fetch existing memories
-        val existingMemories = settings.userMemories
+            // First, fetch existing memories
+            val existingMemories = settings.userMemories
 
-        // Store or append memory
-        if (overwrite || existingMemories.isEmpty()) {
-            if (memory.length > 200) {
-                return "Memory should be under 200 chars, please compress it as best you can to what is important and try again. Current length: " + memory.length
-            }
-            settings.userMemories = memory
-            return "Memory stored. This will be included in future conversations."
-        } else {
-            if ((memory+settings.userMemories).length > 200) {
-                return "Memory should be under 200 chars, please combine existing memory prefs with the new and compress, or overwrite, and try again, overwriting next time to be under 200 chars. Existing memory:\n" + settings.userMemories
-            }
+            // Store or append memory
+            if (overwrite || existingMemories.isEmpty()) {
+                if (memory.length > 200) {
+                    return "Memory should be under 200 chars, please compress it as best you can to what is important and try again. Current length: " + memory.length
+                }
+                settings.userMemories = memory
+                return "Memory stored. This will be included in future conversations."
+            } else {
+                if ((memory+settings.userMemories).length > 200) {
+                    return "Memory should be under 200 chars, please combine existing memory prefs with the new and compress, or overwrite, and try again, overwriting next time to be under 200 chars. Existing memory:\n" + settings.userMemories
+                }
 
-            // Append to existing memories
+                // Append to existing memories
 
-            settings.userMemories = "$existingMemories\n$memory"
-            return "Memory appended to existing memories. This will be included in future conversations."
+                settings.userMemories = "$existingMemories\n$memory"
+                return "Memory appended to existing memories. This will be included in future conversations."
+            }
         }
     }
 
-    fun getSerializableToolDefinitions(
-        context: Context,
-        provider: ModelProvider
-    ): SerializableToolDefinitions {
-        val methods = ToolHandler::class.java.methods
-            .filter { it.isAnnotationPresent(Tool::class.java) }
-
-        // Get the regular tool definitions first
-        val regularToolDefinitions = when (provider) {
-            ModelProvider.OPENAI -> {
-                methods.mapNotNull { method ->
-                    val tool = method.getAnnotation(Tool::class.java) ?: return@mapNotNull null
-
-                    // Always create a ToolParametersObject, even for tools with no parameters
-                    val toolParameters = ToolParametersObject(
-                        properties = tool.parameters.associate { param ->
-                            param.name to ToolParameter(
-                                type = param.type,
-                                description = param.description
-                            )
-                        },
-                        required = tool.parameters
-                            .filter { it.required }
-                            .map { it.name }
-                    )
+    val registry = ToolRegistry(
+        listOf(
+            getUiHierarchy,
+            getUiChanges,
+            waitFor,
+            home,
+            globalAction,
+            takeScreenshot,
+            tapMark,
+            startApp,
+            click,
+            clickElement,
+            longPressElement,
+            setTextOnElement,
+            scrollElement,
+            swipe,
+            longPress,
+            doubleTap,
+            pinch,
+            dragAndDrop,
+            gesture,
+            scrollBrowse,
+            enterText,
+            enterTextByDescription,
+            selectText,
+            copyPasteText,
+            pressImeAction,
+            getClipboard,
+            setClipboard,
+            checkSetup,
+            webSearch,
+            openUrl,
+            getCalendarEvents,
+            searchContacts,
+            searchPastConversations,
+            getLastConversationContext,
+            notificationHandler,
+            listResources,
+            readResource,
+            storeMemory
+        )
+    )
 
-                    ToolDefinition(
-                        function = ToolFunctionDefinition(
-                            name = tool.name,
-                            description = tool.description,
-                            parameters = toolParameters
-                        )
-                    )
-                }
-            }
+    // Schemas of the extension tools last shown to the model, to validate their arguments
+    private val mcpToolParameters = ConcurrentHashMap<String, ToolParametersObject>()
 
-            ModelProvider.GEMINI -> {
-                methods.mapNotNull { method ->
-                    val tool = method.getAnnotation(Tool::class.java) ?: return@mapNotNull null
-                    val toolParameters = ToolParametersObject(
-                        properties = tool.parameters.associate { param ->
-                            param.name to ToolParameter(
-                                type = when (param.type.lowercase(Locale.getDefault())) {
-                                    "integer" -> "string" // Use string for integers
-                                    "boolean" -> "boolean"
-                                    "string" -> "string"
-                                    "double", "float" -> "number"
-                                    else -> "string"
-                                },
-                                description = param.description
-                            )
-                        },
-                        required = tool.parameters
-                            .filter { it.required }
-                            .map { it.name }
-                    )
+    fun concurrencyOf(toolName: String): ToolConcurrency {
+        return registry[toolName]?.concurrency ?: ToolConcurrency.SERIAL
+    }
 
-                    ToolDefinition(
-                        function = ToolFunctionDefinition(
-                            name = tool.name,
-                            description = tool.description,
-                            parameters = toolParameters
-                        )
-                    )
-                }
-            }
-        }
+    fun getToolDefinitions(context: Context): List<ToolDefinition> {
+        val regularToolDefinitions = registry.definitions()
 
         val settings = xyz.block.gosling.features.settings.SettingsStore(context)
         val enableAppExtensions = settings.enableAppExtensions
@@ -1610,51 +2612,22 @@ object ToolHandler {
         val mcpTools = mutableListOf<ToolDefinition>()
         if (enableAppExtensions) {
             try {
-                val mcps = MobileMCP.discoverMCPs(context)
+                val mcps = McpCatalog.servers(context)
 
                 for (mcp in mcps) {
-                    val localId = mcp["localId"] as String
-
-                    @Suppress("UNCHECKED_CAST")
-                    val tools = mcp["tools"] as Map<String, Map<String, String>>
-
-                    for ((toolName, toolInfo) in tools) {
-                        val toolDescription = toolInfo["description"] ?: ""
-
-                        // Parse the parameters JSON string into a proper structure
-                        val parametersJson = toolInfo["parameters"] ?: "{}"
-                        val parametersObj = JSONObject(parametersJson)
-                        val paramProperties = mutableMapOf<String, ToolParameter>()
-                        val requiredParams = mutableListOf<String>()
-
-                        // Extract parameters from the JSON
-                        parametersObj.keys().forEach { paramName ->
-                            val paramType = "string" // Default to string type for simplicity
-
-                            paramProperties[paramName] = ToolParameter(
-                                type = paramType,
-                                description = "Parameter for $toolName"
-                            )
-
-                            // Assume all parameters are required for now
-                            requiredParams.add(paramName)
-                        }
-
-                        // Create the tool parameters object
-                        val toolParameters = ToolParametersObject(
-                            properties = paramProperties,
-                            required = requiredParams
-                        )
+                    for (tool in mcp.tools) {
+                        val toolParameters = jsonSchemaToParameters(tool.inputSchema)
 
                         // Create the tool definition with a special name format to identify it as an MCP tool
                         // we use a localId which is compact to save on space for toolName as there are limits
-                        val mcpToolName = "mcp_${localId}_${toolName}"
+                        val mcpToolName = "mcp_${mcp.localId}_${tool.name}"
+                        mcpToolParameters[mcpToolName] = toolParameters
 
                         mcpTools.add(
                             ToolDefinition(
                                 function = ToolFunctionDefinition(
                                     name = mcpToolName,
-                                    description = toolDescription,
+                                    description = tool.description,
                                     parameters = toolParameters
                                 )
                             )
@@ -1667,92 +2640,91 @@ object ToolHandler {
         }
 
         // Combine regular tools and MCP tools
-        val allTools = regularToolDefinitions + mcpTools
-
-        return when (provider) {
-            ModelProvider.OPENAI -> SerializableToolDefinitions.OpenAITools(allTools)
-            ModelProvider.GEMINI -> {
-                // For Gemini, we need to convert the tool definitions to Gemini format
-                val functionDeclarations = allTools.map { toolDef ->
-                    GeminiFunctionDeclaration(
-                        name = toolDef.function.name,
-                        description = toolDef.function.description,
-                        parameters = if (toolDef.function.parameters.properties.isEmpty()) null else toolDef.function.parameters
-                    )
-                }
-
-                SerializableToolDefinitions.GeminiTools(
-                    listOf(
-                        GeminiTool(
-                            functionDeclarations = functionDeclarations
-                        )
-                    )
-                )
-            }
-        }
+        return regularToolDefinitions + mcpTools
     }
 
     fun callTool(
         toolCall: InternalToolCall,
         context: Context,
-        accessibilityService: AccessibilityService?
+        accessibilityService: AccessibilityService?,
+        onProgress: (String) -> Unit = {},
+        onAttachment: (Content) -> Unit = {}
     ): String {
         if (Agent.getInstance()?.isCancelled() == true) {
             return "Operation cancelled by user"
         }
 
         if (!toolCall.name.startsWith("mcp_")) {
-            val toolMethod = ToolHandler::class.java.methods
-                .firstOrNull {
-                    it.isAnnotationPresent(Tool::class.java) &&
-                            it.getAnnotation(Tool::class.java)?.name == toolCall.name
-                }
-                ?: return "Unknown tool call: ${toolCall.name}"
+            val tool = registry[toolCall.name]
+                ?: return toolError(
+                    tool = toolCall.name,
+                    type = "unknown_tool",
+                    message = "There is no tool named '${toolCall.name}'. " +
+                            "Available tools: ${registry.names.joinToString()}"
+                )
 
-            val toolAnnotation = toolMethod.getAnnotation(Tool::class.java)
-                ?: return "Tool annotation not found for: ${toolCall.name}"
+            if (tool.requiresAccessibility && accessibilityService == null) {
+                return "Accessibility service not available."
+            }
 
-            return try {
-                if (Agent.getInstance()?.isCancelled() == true) {
-                    return "Operation cancelled by user"
-                }
+            val validation = ToolArgumentValidator.validate(toolCall.arguments, tool.parameters)
+            if (!validation.isValid) {
+                Log.w(TAG, "Rejected arguments for ${tool.name}: ${validation.problems}")
+                return invalidArgumentsError(tool.name, validation.problems)
+            }
 
-                if (toolAnnotation.requiresAccessibility) {
-                    if (accessibilityService == null) {
-                        return "Accessibility service not available."
-                    }
-                    if (toolAnnotation.requiresContext) {
-                        return toolMethod.invoke(
-                            ToolHandler,
-                            accessibilityService,
-                            context,
-                            toolCall.arguments
-                        ) as String
-                    }
-                    return toolMethod.invoke(
-                        ToolHandler,
-                        accessibilityService,
-                        toolCall.arguments
-                    ) as String
-                }
-                if (toolAnnotation.requiresContext) {
-                    return toolMethod.invoke(ToolHandler, context, toolCall.arguments) as String
-                }
-                return toolMethod.invoke(ToolHandler, toolCall.arguments) as String
+            val args = ToolArguments(validation.arguments, tool.parameters.properties)
+
+            val ctx = ToolContext(
+                context,
+                accessibilityService,
+                cancellation = { Agent.getInstance()?.isCancelled() == true },
+                attachments = onAttachment
+            )
+
+            return try {
+                tool.execute(ctx, args)
+            } catch (e: ToolArgumentException) {
+                toolError(
+                    tool = tool.name,
+                    type = "invalid_argument",
+                    message = e.message ?: "Invalid argument",
+                    field = e.field,
+                    expected = e.expected
+                )
             } catch (e: Exception) {
                 "Error executing ${toolCall.name}: ${e.message}"
             }
 
         } else {
+            var arguments = toolCall.arguments
+            mcpToolParameters[toolCall.name]?.let { parameters ->
+                val validation = ToolArgumentValidator.validate(arguments, parameters)
+                if (!validation.isValid) {
+                    Log.w(TAG, "Rejected arguments for ${toolCall.name}: ${validation.problems}")
+                    return invalidArgumentsError(toolCall.name, validation.problems)
+                }
+                arguments = validation.arguments
+            }
+
             val nameParts = toolCall.name.split("_", limit = 3)
             val result = MobileMCP.invokeTool(
                 context,
                 nameParts[1],
                 nameParts[2],
-                toolCall.arguments.toString()
+                arguments,
+                onProgress = onProgress,
+                isCancelled = { Agent.getInstance()?.isCancelled() == true }
             )
             Log.d(TAG, "TOOL CALL RESULT: $result")
-            return "" + result
+            return when (result) {
+                is McpResult.Success -> result.text
+                is McpResult.Error -> toolError(
+                    tool = toolCall.name,
+                    type = result.code.wireName,
+                    message = result.message
+                )
+            }
 
         }
 
@@ -1774,7 +2746,9 @@ object ToolHandler {
                 InternalToolCall(
                     name = functionCall.getString("name"),
                     arguments = functionCall.optJSONObject("args") ?: JSONObject(),
-                    toolId = json.optString("id", newToolCallId())
+                    toolId = functionCall.optString("id").ifEmpty {
+                        json.optString("id", newToolCallId())
+                    }
                 )
             }
 
@@ -0,0 +1,182 @@
+package xyz.block.gosling.features.agent
+
+import android.graphics.Rect
+import android.view.accessibility.AccessibilityNodeInfo
+import kotlinx.serialization.SerialName
+import kotlinx.serialization.Serializable
+import kotlinx.serialization.encodeToString
+import kotlinx.serialization.json.Json
+
+/**
+ * One element of the structured UI hierarchy. State flags are only written when set, so a
+ * plain label costs little more than its text and bounds.
+ */
+@Serializable
+data class UiNode(
+    val ref: String,
+    @SerialName("class") val className: String,
+    val text: String? = null,
+    val desc: String? = null,
+    @SerialName("resource_id") val resourceId: String? = null,
+    val bounds: List<Int>,
+    val clickable: Boolean = false,
+    val editable: Boolean = false,
+    val scrollable: Boolean = false,
+    val checked: Boolean = false,
+    val selected: Boolean = false,
+    val focused: Boolean = false,
+    val children: List<UiNode> = emptyList()
+) {
+    fun differingFields(other: UiNode): List<String> = listOfNotNull(
+        "class".takeIf { className != other.className },
+        "text".takeIf { text != other.text },
+        "desc".takeIf { desc != other.desc },
+        "resource_id".takeIf { resourceId != other.resourceId },
+        "bounds".takeIf { bounds != other.bounds },
+        "clickable".takeIf { clickable != other.clickable },
+        "editable".takeIf { editable != other.editable },
+        "scrollable".takeIf { scrollable != other.scrollable },
+        "checked".takeIf { checked != other.checked },
+        "selected".takeIf { selected != other.selected },
+        "focused".takeIf { focused != other.focused }
+    )
+
+    companion object {
+        fun from(node: AccessibilityNodeInfo, ref: String, children: List<UiNode> = emptyList()): UiNode {
+            val bounds = Rect().also { node.getBoundsInScreen(it) }
+            return UiNode(
+                ref = ref,
+                className = node.className?.toString()?.substringAfterLast('.') ?: "View",
+                text = node.text?.toString()?.takeIf { it.isNotEmpty() },
+                desc = node.contentDescription?.toString()?.takeIf { it.isNotEmpty() },
+                resourceId = node.viewIdResourceName?.takeIf { it.isNotEmpty() },
+                bounds = listOf(bounds.left, bounds.top, bounds.right, bounds.bottom),
+                clickable = node.isClickable,
+                editable = node.isEditable,
+                scrollable = node.isScrollable,
+                checked = node.isChecked,
+                selected = node.isSelected,
+                focused = node.isFocused,
+                children = children
+            )
+        }
+    }
+}
+
+@Serializable
+data class UiWindowTree(
+    val id: Int,
+    val type: String,
+    @SerialName("package") val packageName: String? = null,
+    val title: String? = null,
+    val active: Boolean = false,
+    val focused: Boolean = false,
+    @SerialName("permission_prompt") val permissionPrompt: Boolean = false,
+    val root: UiNode? = null
+)
+
+@Serializable
+data class UiChange(
+    val node: UiNode,
+    val fields: List<String>
+)
+
+/**
+ * Elements added, removed or changed between two snapshots, matched by ref. Nodes are listed
+ * without their children.
+ */
+@Serializable
+data class UiDiff(
+    val added: List<UiNode> = emptyList(),
+    val removed: List<UiNode> = emptyList(),
+    val changed: List<UiChange> = emptyList()
+) {
+    val isEmpty: Boolean
+        get() = added.isEmpty() && removed.isEmpty() && changed.isEmpty()
+}
+
+object UiTree {
+    val json = Json {
+        explicitNulls = false
+        encodeDefaults = false
+    }
+
+    /**
+     * Builds the typed tree under [root]. Layout containers without any content or state of
+     * their own are left out and their children moved up, matching what the compact text
+     * format skips. Refs are the same ones the compact format would print.
+     */
+    fun build(window: UiWindow, snapshot: ElementRefs.Snapshot): UiWindowTree {
+        return UiWindowTree(
+            id = window.id,
+            type = window.type,
+            packageName = window.packageName,
+            title = window.title?.takeIf { it.isNotBlank() },
+            active = window.isActive,
+            focused = window.isFocused,
+            permissionPrompt = window.isPermissionPrompt,
+            root = window.root?.let { root ->
+                val path = rootPath(window.id, root)
+                UiNode.from(root, snapshot.register(root, path), buildChildren(root, path, snapshot))
+            }
+        )
+    }
+
+    /**
+     * Where paths of elements under [root] start. They include the window so that two windows
+     * of the same app, such as a dialog over its activity, don't share refs.
+     */
+    fun rootPath(windowId: Int, root: AccessibilityNodeInfo): String {
+        return "$windowId:${root.packageName}/0"
+    }
+
+    private fun buildChildren(
+        node: AccessibilityNodeInfo,
+        path: String,
+        snapshot: ElementRefs.Snapshot
+    ): List<UiNode> {
+        return (0 until node.childCount).flatMap { i ->
+            node.getChild(i)?.let { buildNodes(it, "$path.$i", snapshot) }.orEmpty()
+        }
+    }
+
+    private fun buildNodes(
+        node: AccessibilityNodeInfo,
+        path: String,
+        snapshot: ElementRefs.Snapshot
+    ): List<UiNode> {
+        val children = buildChildren(node, path, snapshot)
+        if (!isMeaningful(node)) return children
+
+        return listOf(UiNode.from(node, snapshot.register(node, path), children))
+    }
+
+    /**
+     * Whether a node carries content or state of its own. Only these are compared between
+     * snapshots, whichever format the snapshot was taken in.
+     */
+    fun isMeaningful(node: AccessibilityNodeInfo): Boolean {
+        val hasContent = node.text?.isNotEmpty() == true ||
+                node.contentDescription?.isNotEmpty() == true ||
+                node.viewIdResourceName?.isNotEmpty() == true
+        val hasState = node.isClickable || node.isEditable || node.isScrollable ||
+                node.isCheckable || node.isSelected || node.isFocused
+        return hasContent || hasState
+    }
+
+    fun diff(previous: Map<String, UiNode>, current: Map<String, UiNode>): UiDiff {
+        return UiDiff(
+            added = current.filterKeys { it !in previous }.values.toList(),
+            removed = previous.filterKeys { it !in current }.values.toList(),
+            changed = current.mapNotNull { (ref, node) ->
+                val before = previous[ref] ?: return@mapNotNull null
+                val fields = node.differingFields(before)
+                if (fields.isEmpty()) null else UiChange(node, fields)
+            }
+        )
+    }
+
+    fun encode(windows: List<UiWindowTree>): String = json.encodeToString(windows)
+
+    fun encode(diff: UiDiff): String = json.encodeToString(diff)
+}
@@ -0,0 +1,98 @@
+package xyz.block.gosling.features.agent
+
+import android.accessibilityservice.AccessibilityService
+import android.view.accessibility.AccessibilityNodeInfo
+import android.view.accessibility.AccessibilityWindowInfo
+
+/**
+ * A window on screen as seen through the accessibility service. Needs
+ * FLAG_RETRIEVE_INTERACTIVE_WINDOWS, which GoslingAccessibilityService requests.
+ */
+data class UiWindow(
+    val id: Int,
+    val type: String,
+    val packageName: String?,
+    val title: String?,
+    val isActive: Boolean,
+    val isFocused: Boolean,
+    val layer: Int,
+    val root: AccessibilityNodeInfo?
+) {
+    // Runtime permission prompts, which block whatever app asked for them
+    val isPermissionPrompt: Boolean
+        get() = packageName in PERMISSION_CONTROLLER_PACKAGES
+
+    val label: String
+        get() = buildString {
+            append("window $id: $type")
+            packageName?.let { append(" $it") }
+            title?.takeIf { it.isNotBlank() }?.let { append(" \"$it\"") }
+
+            val flags = listOfNotNull(
+                "active".takeIf { isActive },
+                "focused".takeIf { isFocused },
+                "permission prompt".takeIf { isPermissionPrompt },
+                "no content".takeIf { root == null }
+            )
+            if (flags.isNotEmpty()) append(" (${flags.joinToString()})")
+        }
+
+    companion object {
+        private val PERMISSION_CONTROLLER_PACKAGES = setOf(
+            "com.android.permissioncontroller",
+            "com.google.android.permissioncontroller",
+            "com.android.packageinstaller",
+            "com.google.android.packageinstaller"
+        )
+    }
+}
+
+object UiWindows {
+    /**
+     * All windows on screen, top-most first.
+     */
+    fun list(accessibilityService: AccessibilityService): List<UiWindow> {
+        val windows = accessibilityService.windows
+        if (windows.isEmpty()) {
+            // Window retrieval can be unavailable for a moment after the service connects
+            val root = accessibilityService.rootInActiveWindow ?: return emptyList()
+            return listOf(
+                UiWindow(
+                    id = root.windowId,
+                    type = typeName(AccessibilityWindowInfo.TYPE_APPLICATION),
+                    packageName = root.packageName?.toString(),
+                    title = null,
+                    isActive = true,
+                    isFocused = true,
+                    layer = 0,
+                    root = root
+                )
+            )
+        }
+
+        return windows
+            .sortedByDescending { it.layer }
+            .map { window ->
+                val root = window.root
+                UiWindow(
+                    id = window.id,
+                    type = typeName(window.type),
+                    packageName = root?.packageName?.toString(),
+                    title = window.title?.toString(),
+                    isActive = window.isActive,
+                    isFocused = window.isFocused,
+                    layer = window.layer,
+                    root = root
+                )
+            }
+    }
+
+    private fun typeName(type: Int): String = when (type) {
+        AccessibilityWindowInfo.TYPE_APPLICATION -> "APPLICATION"
+        AccessibilityWindowInfo.TYPE_INPUT_METHOD -> "KEYBOARD"
+        AccessibilityWindowInfo.TYPE_SYSTEM -> "SYSTEM"
+        AccessibilityWindowInfo.TYPE_ACCESSIBILITY_OVERLAY -> "ACCESSIBILITY_OVERLAY"
+        AccessibilityWindowInfo.TYPE_SPLIT_SCREEN_DIVIDER -> "SPLIT_SCREEN_DIVIDER"
+        else -> "UNKNOWN"
+    }
+}
@@ -46,6 +46,7 @@ import xyz.block.gosling.features.agent.AgentServiceManager
 import xyz.block.gosling.features.agent.Content
 import xyz.block.gosling.features.agent.Conversation
 import xyz.block.gosling.features.agent.Message
+import xyz.block.gosling.features.agent.contentWithText
 import xyz.block.gosling.features.agent.firstText
 import xyz.block.gosling.features.agent.getConversationTitle
 
@@ -108,6 +109,7 @@ fun ConversationScreen(
     val agentServiceManager = remember { AgentServiceManager(context) }
     var showDeleteConfirmation by remember { mutableStateOf(false) }
     var agent by remember { mutableStateOf<xyz.block.gosling.features.agent.Agent?>(null) }
+    var streamingReply by remember { mutableStateOf<String?>(null) }
 
     LaunchedEffect(conversationId) {
         agentServiceManager.bindAndStartAgent { boundAgent ->
@@ -119,6 +121,15 @@ fun ConversationScreen(
                     // Just display the conversation details
                 }
             }
+            scope.launch(Dispatchers.Main) {
+                val conversationManager = boundAgent.conversationManager
+                conversationManager.streamingReply.collect { text ->
+                    // Only the conversation the agent is working on can have a reply in flight
+                    streamingReply = text?.takeIf {
+                        conversationManager.currentConversation.value?.id == conversationId
+                    }
+                }
+            }
         }
     }
 
@@ -225,6 +236,11 @@ fun ConversationScreen(
                     verticalArrangement = Arrangement.spacedBy(8.dp),
                     reverseLayout = true
                 ) {
+                    streamingReply?.let { text ->
+                        item {
+                            MessageCard(message = Message(role = "assistant", content = contentWithText(text)))
+                        }
+                    }
                     items(filteredMessages.asReversed()) { message ->
                         MessageCard(message = message)
                     }
@@ -79,13 +79,17 @@ import androidx.core.content.FileProvider
 import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.launch
+import kotlinx.coroutines.withContext
 import xyz.block.gosling.R
 import xyz.block.gosling.features.agent.Agent
 import xyz.block.gosling.features.agent.AgentStatus
 import xyz.block.gosling.features.agent.Conversation
+import xyz.block.gosling.features.agent.ExtensionPrompt
+import xyz.block.gosling.features.agent.MobileMCP
 import xyz.block.gosling.features.agent.getConversationTitle
 import xyz.block.gosling.features.overlay.OverlayService
 import xyz.block.gosling.features.settings.SettingsStore
+import xyz.block.gosling.mmcp.McpResult
 import xyz.block.gosling.shared.services.VoiceRecognitionService
 import java.io.File
 import java.text.SimpleDateFormat
@@ -128,6 +132,7 @@ fun MainScreen(
     var showPresetQueries by remember { mutableStateOf(false) }
     var isRecording by remember { mutableStateOf(false) }
     var photoUri by remember { mutableStateOf<Uri?>(null) }
+    var extensionPrompts by remember { mutableStateOf<List<ExtensionPrompt>>(emptyList()) }
 
     val pulseAnim = rememberInfiniteTransition()
     val scale by pulseAnim.animateFloat(
@@ -463,6 +468,17 @@ fun MainScreen(
                                 modifier = Modifier.fillMaxWidth(0.8f)
                             )
                         }
+                        extensionPrompts.forEach { extensionPrompt ->
+                            SuggestionBubble(
+                                text = "${extensionPrompt.label} (${extensionPrompt.appName})",
+                                onClick = {
+                                    loadExtensionPrompt(context, extensionPrompt) {
+                                        textInput = it
+                                    }
+                                },
+                                modifier = Modifier.fillMaxWidth(0.8f)
+                            )
+                        }
                     }
                 }
             } else {
@@ -638,6 +654,15 @@ fun MainScreen(
         }
     }
 
+    // Offer the prompts installed apps suggest next to the built-in suggestions
+    LaunchedEffect(Unit) {
+        if (SettingsStore(context).enableAppExtensions) {
+            extensionPrompts = withContext(Dispatchers.IO) {
+                MobileMCP.suggestedPrompts(context)
+            }
+        }
+    }
+
     // Collect conversations from the agent
     LaunchedEffect(Unit) {
         val agent = activity.currentAgent
@@ -720,6 +745,30 @@ private fun startVoiceRecognition(
     )
 }
 
+/**
+ * Fetches the text of an extension's prompt and hands it to [onLoaded] to put in the input
+ * field, so the user reads what the app asks for before sending it to the agent.
+ */
+private fun loadExtensionPrompt(
+    context: Context,
+    extensionPrompt: ExtensionPrompt,
+    onLoaded: (String) -> Unit
+) {
+    CoroutineScope(Dispatchers.Main).launch {
+        val result = withContext(Dispatchers.IO) {
+            MobileMCP.getPrompt(context, extensionPrompt.localId, extensionPrompt.prompt.name)
+        }
+        when (result) {
+            is McpResult.Success -> onLoaded(result.text)
+            is McpResult.Error -> Toast.makeText(
+                context,
+                "${extensionPrompt.appName}: ${result.message}",
+                Toast.LENGTH_SHORT
+            ).show()
+        }
+    }
+}
+
 private fun processAgentCommand(
     context: Context,
     command: String,
@@ -751,8 +800,11 @@ private fun processAgentCommand(
                 android.os.Handler(context.mainLooper).post {
                     onMessageReceived?.invoke(status.message, false)
 
-                    statusToast.setText(status.message)
-                    statusToast.show()
+                    // Streaming text updates several times a second; keep it out of toasts
+                    if (!status.isPartial) {
+                        statusToast.setText(status.message)
+                        statusToast.show()
+                    }
 
                     OverlayService.getInstance()?.updateStatus(status)
                 }
@@ -810,6 +862,17 @@ private fun processAgentCommand(
                     OverlayService.getInstance()?.setIsPerformingAction(false)
                 }
             }
+
+            is AgentStatus.Stopped -> {
+                android.os.Handler(context.mainLooper).post {
+                    onMessageReceived?.invoke(status.message, false)
+
+                    Toast.makeText(context, status.message, Toast.LENGTH_LONG).show()
+
+                    OverlayService.getInstance()?.updateStatus(status)
+                    OverlayService.getInstance()?.setIsPerformingAction(false)
+                }
+            }
         }
     }
 
@@ -131,6 +131,7 @@ class AssistantActivity : ComponentActivity() {
                 when (status) {
                     is AgentStatus.Processing -> {
                         if (status.message.isEmpty() || status.message == "null") return@setStatusListener
+                        if (status.isPartial) return@setStatusListener
                         runOnUiThread {
                             statusToast.setText(status.message)
                             statusToast.show()
@@ -154,6 +155,15 @@ class AssistantActivity : ComponentActivity() {
                         }
                         finish()
                     }
+
+                    is AgentStatus.Stopped -> {
+                        runOnUiThread {
+                            statusToast.setText(status.message)
+                            statusToast.show()
+                            OverlayService.getInstance()?.setIsPerformingAction(false)
+                        }
+                        finish()
+                    }
                 }
             }
 
@@ -79,6 +79,7 @@ class AssistantSessionService : VoiceInteractionSessionService() {
                     when (status) {
                         is AgentStatus.Processing -> {
                             if (status.message.isEmpty() || status.message == "null") return@setStatusListener
+                            if (status.isPartial) return@setStatusListener
                             Log.d(TAG, "Processing: ${status.message}")
                         }
 
@@ -89,6 +90,10 @@ class AssistantSessionService : VoiceInteractionSessionService() {
                         is AgentStatus.Error -> {
                             Log.d(TAG, "Error: ${status.message}")
                         }
+
+                        is AgentStatus.Stopped -> {
+                            Log.d(TAG, "Stopped: ${status.message}")
+                        }
                     }
                 }
 
@@ -9,6 +9,7 @@ import androidx.compose.foundation.clickable
 import androidx.compose.foundation.gestures.Orientation
 import androidx.compose.foundation.gestures.draggable
 import androidx.compose.foundation.gestures.rememberDraggableState
+import androidx.compose.foundation.horizontalScroll
 import androidx.compose.foundation.layout.Arrangement
 import androidx.compose.foundation.layout.Column
 import androidx.compose.foundation.layout.Row
@@ -31,6 +32,7 @@ import androidx.compose.material3.CardDefaults
 import androidx.compose.material3.ExperimentalMaterial3Api
 import androidx.compose.material3.MaterialTheme
 import androidx.compose.material3.SheetValue
+import androidx.compose.material3.SuggestionChip
 import androidx.compose.material3.Surface
 import androidx.compose.material3.Text
 import androidx.compose.material3.rememberBottomSheetScaffoldState
@@ -53,11 +55,16 @@ import kotlinx.coroutines.CoroutineScope
 import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.delay
 import kotlinx.coroutines.launch
+import kotlinx.coroutines.withContext
 import xyz.block.gosling.features.agent.Agent
 import xyz.block.gosling.features.agent.AgentServiceManager
 import xyz.block.gosling.features.agent.AgentStatus
 import xyz.block.gosling.features.agent.Content
+import xyz.block.gosling.features.agent.ExtensionPrompt
+import xyz.block.gosling.features.agent.MobileMCP
 import xyz.block.gosling.features.overlay.OverlayService
+import xyz.block.gosling.features.settings.SettingsStore
+import xyz.block.gosling.mmcp.McpResult
 import xyz.block.gosling.shared.services.VoiceRecognitionService
 import xyz.block.gosling.shared.theme.GoslingTheme
 
@@ -89,6 +96,9 @@ fun LauncherScreen() {
     // State for persisted command results
     var lastCommandResult by remember { mutableStateOf<CommandResult?>(null) }
 
+    // Prompts installed apps suggest, offered as chips above the input options
+    var extensionPrompts by remember { mutableStateOf<List<ExtensionPrompt>>(emptyList()) }
+
     // Update the clock using system broadcasts instead of polling
     val timeTickReceiver = remember { 
         object : android.content.BroadcastReceiver() {
@@ -116,6 +126,14 @@ fun LauncherScreen() {
         }
     }
 
+    LaunchedEffect(Unit) {
+        if (SettingsStore(context).enableAppExtensions) {
+            extensionPrompts = withContext(Dispatchers.IO) {
+                MobileMCP.suggestedPrompts(context)
+            }
+        }
+    }
+
     // Load installed apps
     LaunchedEffect(Unit) {
         val pm = context.packageManager
@@ -222,6 +240,48 @@ fun LauncherScreen() {
 
                 Spacer(modifier = Modifier.weight(0.6f))  // Reduced weight to give more space to the card
 
+                if (extensionPrompts.isNotEmpty()) {
+                    Row(
+                        modifier = Modifier
+                            .fillMaxWidth()
+                            .horizontalScroll(rememberScrollState()),
+                        horizontalArrangement = Arrangement.spacedBy(8.dp)
+                    ) {
+                        extensionPrompts.forEach { extensionPrompt ->
+                            SuggestionChip(
+                                onClick = {
+                                    coroutineScope.launch {
+                                        val result = withContext(Dispatchers.IO) {
+                                            MobileMCP.getPrompt(
+                                                context,
+                                                extensionPrompt.localId,
+                                                extensionPrompt.prompt.name
+                                            )
+                                        }
+                                        when (result) {
+                                            // Shown in the input drawer so the user sees what
+                                            // the app asks for before the agent acts on it
+                                            is McpResult.Success -> {
+                                                textInput = result.text
+                                                showKeyboardDrawer = true
+                                            }
+
+                                            is McpResult.Error -> Toast.makeText(
+                                                context,
+                                                "${extensionPrompt.appName}: ${result.message}",
+                                                Toast.LENGTH_SHORT
+                                            ).show()
+                                        }
+                                    }
+                                },
+                                label = { Text(extensionPrompt.label) }
+                            )
+                        }
+                    }
+
+                    Spacer(modifier = Modifier.height(16.dp))
+                }
+
                 InputOptions(
                     onMicrophoneClick = {
                         // Start voice recognition for agent commands
@@ -430,9 +490,11 @@ private fun processAgentCommand(
                 is AgentStatus.Processing -> {
                     if (status.message.isEmpty() || status.message == "null") return@setStatusListener
                     android.os.Handler(context.mainLooper).post {
-                        statusToast.setText(status.message)
-                        statusToast.show()
-                        
+                        if (!status.isPartial) {
+                            statusToast.setText(status.message)
+                            statusToast.show()
+                        }
+
                         // Update the card with the current processing status
                         onResultReceived(CommandResult(
                             command = command,
@@ -496,6 +558,21 @@ private fun processAgentCommand(
                         OverlayService.getInstance()?.setIsPerformingAction(false)
                     }
                 }
+
+                is AgentStatus.Stopped -> {
+                    android.os.Handler(context.mainLooper).post {
+                        statusToast.setText(status.message)
+                        statusToast.show()
+
+                        onResultReceived(CommandResult(
+                            command = command,
+                            response = status.message,
+                            isError = true
+                        ))
+
+                        OverlayService.getInstance()?.setIsPerformingAction(false)
+                    }
+                }
             }
         }
 
@@ -34,6 +34,10 @@ import androidx.compose.ui.text.input.KeyboardType
 import androidx.compose.ui.text.input.PasswordVisualTransformation
 import androidx.compose.ui.unit.dp
 import xyz.block.gosling.features.agent.AiModel
+import xyz.block.gosling.features.agent.ModelProvider
+import xyz.block.gosling.features.settings.CUSTOM_MODEL_ID
+import xyz.block.gosling.features.settings.CustomModelFields
+import xyz.block.gosling.features.settings.EndpointFields
 import xyz.block.gosling.features.settings.QRCodeScannerDialog
 import xyz.block.gosling.features.settings.SettingsStore
 
@@ -44,13 +48,23 @@ fun LLMConfigStep(
     onComplete: () -> Unit
 ) {
     var llmModel by remember { mutableStateOf(settingsStore.llmModel) }
-    var currentModel by remember { mutableStateOf(AiModel.fromIdentifier(llmModel)) }
+    var llmProvider by remember { mutableStateOf(settingsStore.llmProvider) }
+    var isCustomModel by remember { mutableStateOf(!AiModel.isKnown(llmModel)) }
+    val currentModel = AiModel.fromIdentifier(llmModel, llmProvider)
     var apiKey by remember { mutableStateOf(settingsStore.getApiKey(currentModel.provider)) }
+    var baseUrl by remember { mutableStateOf(settingsStore.getBaseUrl(currentModel.provider)) }
+    var extraHeaders by remember { mutableStateOf(settingsStore.getExtraHeaders(currentModel.provider)) }
     var expanded by remember { mutableStateOf(false) }
     var showQRScanner by remember { mutableStateOf(false) }
 
     val models = AiModel.AVAILABLE_MODELS.map {
         it.identifier to it.displayName
+    } + (CUSTOM_MODEL_ID to "Custom model…")
+
+    fun loadProviderSettings(provider: ModelProvider) {
+        apiKey = settingsStore.getApiKey(provider)
+        baseUrl = settingsStore.getBaseUrl(provider)
+        extraHeaders = settingsStore.getExtraHeaders(provider)
     }
 
     Box(modifier = Modifier.fillMaxSize()) {
@@ -76,7 +90,8 @@ fun LLMConfigStep(
                         onExpandedChange = { expanded = it }
                     ) {
                         OutlinedTextField(
-                            value = models.find { it.first == llmModel }?.second ?: llmModel,
+                            value = if (isCustomModel) "Custom model…" else
+                                models.find { it.first == llmModel }?.second ?: llmModel,
                             onValueChange = {},
                             readOnly = true,
                             modifier = Modifier
@@ -93,9 +108,18 @@ fun LLMConfigStep(
                                 DropdownMenuItem(
                                     text = { Text(displayName) },
                                     onClick = {
-                                        llmModel = modelId
-                                        currentModel = AiModel.fromIdentifier(modelId)
-                                        apiKey = settingsStore.getApiKey(currentModel.provider)
+                                        if (modelId == CUSTOM_MODEL_ID) {
+                                            if (!isCustomModel) {
+                                                llmModel = ""
+                                            }
+                                            isCustomModel = true
+                                        } else {
+                                            isCustomModel = false
+                                            llmModel = modelId
+                                        }
+                                        loadProviderSettings(
+                                            AiModel.fromIdentifier(llmModel, llmProvider).provider
+                                        )
                                         expanded = false
                                     }
                                 )
@@ -103,6 +127,25 @@ fun LLMConfigStep(
                         }
                     }
                 }
+                if (isCustomModel) {
+                    CustomModelFields(
+                        modelIdentifier = llmModel,
+                        onModelIdentifierChange = { llmModel = it },
+                        provider = llmProvider,
+                        onProviderChange = {
+                            llmProvider = it
+                            loadProviderSettings(it)
+                        }
+                    )
+                }
+                EndpointFields(
+                    provider = currentModel.provider,
+                    baseUrl = baseUrl,
+                    onBaseUrlChange = { baseUrl = it },
+                    extraHeaders = extraHeaders,
+                    onExtraHeadersChange = { extraHeaders = it },
+                    apiKey = apiKey
+                )
                 Column(
                     modifier = Modifier.fillMaxWidth(),
                     verticalArrangement = Arrangement.spacedBy(8.dp)
@@ -152,8 +195,11 @@ fun LLMConfigStep(
 
         Button(
             onClick = {
-                settingsStore.llmModel = llmModel
+                settingsStore.llmModel = llmModel.trim()
+                settingsStore.llmProvider = llmProvider
                 settingsStore.setApiKey(currentModel.provider, apiKey)
+                settingsStore.setBaseUrl(currentModel.provider, baseUrl)
+                settingsStore.setExtraHeaders(currentModel.provider, extraHeaders)
                 onComplete()
             },
             modifier = Modifier
@@ -162,7 +208,8 @@ fun LLMConfigStep(
                 .padding(bottom = 16.dp)
                 .navigationBarsPadding()
                 .imePadding(),
-            enabled = llmModel.isNotEmpty() && apiKey.isNotEmpty(),
+            // Self-hosted endpoints often run without an API key
+            enabled = llmModel.isNotBlank() && (apiKey.isNotEmpty() || baseUrl.isNotBlank()),
         ) {
             Text("Complete Setup")
         }
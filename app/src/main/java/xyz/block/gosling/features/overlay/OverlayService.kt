@@ -191,6 +191,7 @@ class OverlayService : Service() {
 
             is AgentStatus.Success -> Pair(status.message, true)
             is AgentStatus.Error -> Pair(status.message, true)
+            is AgentStatus.Stopped -> Pair(status.message, true)
         }
 
         android.util.Log.d(TAG, "updateStatus called with status: $status, isDone: $isDone")
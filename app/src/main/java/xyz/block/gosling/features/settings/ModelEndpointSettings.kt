@@ -0,0 +1,156 @@
+package xyz.block.gosling.features.settings
+
+import android.net.Uri
+import androidx.compose.foundation.layout.Arrangement
+import androidx.compose.foundation.layout.Column
+import androidx.compose.foundation.layout.fillMaxWidth
+import androidx.compose.material3.DropdownMenuItem
+import androidx.compose.material3.ExperimentalMaterial3Api
+import androidx.compose.material3.ExposedDropdownMenuBox
+import androidx.compose.material3.ExposedDropdownMenuDefaults
+import androidx.compose.material3.MaterialTheme
+import androidx.compose.material3.MenuAnchorType
+import androidx.compose.material3.OutlinedTextField
+import androidx.compose.material3.Text
+import androidx.compose.runtime.Composable
+import androidx.compose.runtime.getValue
+import androidx.compose.runtime.mutableStateOf
+import androidx.compose.runtime.remember
+import androidx.compose.runtime.setValue
+import androidx.compose.ui.Modifier
+import androidx.compose.ui.unit.dp
+import xyz.block.gosling.features.agent.ModelProvider
+
+// Entry in the model dropdowns that switches to a free-text model identifier
+const val CUSTOM_MODEL_ID = "custom"
+
+// Hosts network_security_config.xml allows plain HTTP to
+private val CLEARTEXT_HOSTS = setOf("localhost", "127.0.0.1", "10.0.2.2")
+
+@OptIn(ExperimentalMaterial3Api::class)
+@Composable
+fun CustomModelFields(
+    modelIdentifier: String,
+    onModelIdentifierChange: (String) -> Unit,
+    provider: ModelProvider,
+    onProviderChange: (ModelProvider) -> Unit,
+) {
+    var expanded by remember { mutableStateOf(false) }
+
+    Column(
+        modifier = Modifier.fillMaxWidth(),
+        verticalArrangement = Arrangement.spacedBy(8.dp)
+    ) {
+        Text(text = "API format")
+        ExposedDropdownMenuBox(
+            expanded = expanded,
+            onExpandedChange = { expanded = it }
+        ) {
+            OutlinedTextField(
+                value = provider.displayName,
+                onValueChange = {},
+                readOnly = true,
+                modifier = Modifier
+                    .fillMaxWidth()
+                    .menuAnchor(MenuAnchorType.PrimaryNotEditable, true),
+                trailingIcon = { ExposedDropdownMenuDefaults.TrailingIcon(expanded = expanded) }
+            )
+
+            ExposedDropdownMenu(
+                expanded = expanded,
+                onDismissRequest = { expanded = false }
+            ) {
+                ModelProvider.entries.forEach { option ->
+                    DropdownMenuItem(
+                        text = { Text(option.displayName) },
+                        onClick = {
+                            onProviderChange(option)
+                            expanded = false
+                        }
+                    )
+                }
+            }
+        }
+
+        Text(text = "Model identifier")
+        OutlinedTextField(
+            value = modelIdentifier,
+            onValueChange = onModelIdentifierChange,
+            modifier = Modifier.fillMaxWidth(),
+            singleLine = true,
+            placeholder = { Text("e.g. llama3.1:8b") }
+        )
+    }
+}
+
+@Composable
+fun EndpointFields(
+    provider: ModelProvider,
+    baseUrl: String,
+    onBaseUrlChange: (String) -> Unit,
+    extraHeaders: String,
+    onExtraHeadersChange: (String) -> Unit,
+    apiKey: String,
+) {
+    Column(
+        modifier = Modifier.fillMaxWidth(),
+        verticalArrangement = Arrangement.spacedBy(8.dp)
+    ) {
+        Text(text = "Base URL")
+        OutlinedTextField(
+            value = baseUrl,
+            onValueChange = onBaseUrlChange,
+            modifier = Modifier.fillMaxWidth(),
+            singleLine = true,
+            placeholder = { Text(provider.defaultBaseUrl) }
+        )
+        Text(
+            text = "Leave empty to use the public API. Point it at a self-hosted or proxied " +
+                    "server, e.g. https://llm.example.com/v1, or http://10.0.2.2:11434/v1 for " +
+                    "Ollama on the emulator's host.",
+            style = MaterialTheme.typography.bodySmall,
+            color = MaterialTheme.colorScheme.onSurfaceVariant
+        )
+        cleartextWarning(baseUrl, apiKey)?.let { warning ->
+            Text(
+                text = warning,
+                style = MaterialTheme.typography.bodySmall,
+                color = MaterialTheme.colorScheme.error
+            )
+        }
+
+        Text(text = "Extra headers")
+        OutlinedTextField(
+            value = extraHeaders,
+            onValueChange = onExtraHeadersChange,
+            modifier = Modifier.fillMaxWidth(),
+            minLines = 2,
+            maxLines = 4,
+            placeholder = { Text("Header-Name: value") }
+        )
+        Text(
+            text = "One header per line, sent with every request.",
+            style = MaterialTheme.typography.bodySmall,
+            color = MaterialTheme.colorScheme.onSurfaceVariant
+        )
+    }
+}
+
+/**
+ * Explains what goes wrong with a plain HTTP [baseUrl]: the request is blocked unless it goes to
+ * the device or the emulator's host, and any [apiKey] travels unencrypted.
+ */
+private fun cleartextWarning(baseUrl: String, apiKey: String): String? {
+    val uri = Uri.parse(baseUrl.trim())
+    if (!uri.scheme.equals("http", ignoreCase = true)) return null
+
+    if (uri.host?.lowercase() !in CLEARTEXT_HOSTS) {
+        return "Plain HTTP is only allowed to localhost, 127.0.0.1 and 10.0.2.2. Use https:// " +
+                "for other servers."
+    }
+    if (apiKey.isNotBlank()) {
+        return "This URL doesn't use HTTPS, so your API key is sent unencrypted. Clear the key " +
+                "if the server doesn't need one."
+    }
+    return null
+}
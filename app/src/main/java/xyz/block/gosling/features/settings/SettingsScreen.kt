@@ -57,10 +57,15 @@ import androidx.compose.ui.text.input.KeyboardType
 import androidx.compose.ui.text.input.PasswordVisualTransformation
 import androidx.compose.ui.unit.dp
 import androidx.core.content.ContextCompat
+import kotlinx.coroutines.Dispatchers
 import kotlinx.coroutines.launch
+import kotlinx.coroutines.withContext
+import xyz.block.gosling.features.agent.Agent
 import xyz.block.gosling.features.agent.AgentServiceManager
 import xyz.block.gosling.features.agent.AiModel
 import xyz.block.gosling.features.agent.AppUsageStats
+import xyz.block.gosling.features.agent.McpCatalog
+import xyz.block.gosling.features.agent.RunLimits
 
 @OptIn(ExperimentalMaterial3Api::class)
 @Composable
@@ -73,9 +78,17 @@ fun SettingsScreen(
     val context = LocalContext.current
     var isAssistantEnabled by remember { mutableStateOf(false) }
     var llmModel by remember { mutableStateOf(settingsStore.llmModel) }
-    var currentModel by remember { mutableStateOf(AiModel.fromIdentifier(llmModel)) }
+    var llmProvider by remember { mutableStateOf(settingsStore.llmProvider) }
+    var isCustomModel by remember { mutableStateOf(!AiModel.isKnown(llmModel)) }
+    var currentModel by remember { mutableStateOf(AiModel.fromIdentifier(llmModel, llmProvider)) }
     var apiKey by remember { mutableStateOf(settingsStore.getApiKey(currentModel.provider)) }
+    var baseUrl by remember { mutableStateOf(settingsStore.getBaseUrl(currentModel.provider)) }
+    var extraHeaders by remember { mutableStateOf(settingsStore.getExtraHeaders(currentModel.provider)) }
     var enableAppExtensions by remember { mutableStateOf(settingsStore.enableAppExtensions) }
+    var isRefreshingExtensions by remember { mutableStateOf(false) }
+    var extensionsStatus by remember { mutableStateOf<String?>(null) }
+    var streamResponses by remember { mutableStateOf(settingsStore.streamResponses) }
+    var toolOutputMaxAge by remember { mutableStateOf(settingsStore.toolOutputMaxAge.toString()) }
     var shouldProcessNotifications by remember { mutableStateOf(settingsStore.shouldProcessNotifications) }
     var messageHandlingPreferences by remember { mutableStateOf(settingsStore.messageHandlingPreferences) }
     var showResetDialog by remember { mutableStateOf(false) }
@@ -173,15 +186,17 @@ fun SettingsScreen(
         }
     }
 
-    // Update API key when model changes
-    LaunchedEffect(llmModel) {
-        currentModel = AiModel.fromIdentifier(llmModel)
+    // Update API key and endpoint when model changes
+    LaunchedEffect(llmModel, llmProvider) {
+        currentModel = AiModel.fromIdentifier(llmModel, llmProvider)
         apiKey = settingsStore.getApiKey(currentModel.provider)
+        baseUrl = settingsStore.getBaseUrl(currentModel.provider)
+        extraHeaders = settingsStore.getExtraHeaders(currentModel.provider)
     }
 
     val models = AiModel.AVAILABLE_MODELS.map {
         it.identifier to it.displayName
-    }
+    } + (CUSTOM_MODEL_ID to "Custom model…")
 
     Scaffold(
         topBar = {
@@ -225,7 +240,8 @@ fun SettingsScreen(
                             onExpandedChange = { expanded = it }
                         ) {
                             OutlinedTextField(
-                                value = models.find { it.first == llmModel }?.second ?: llmModel,
+                                value = if (isCustomModel) "Custom model…" else
+                                    models.find { it.first == llmModel }?.second ?: llmModel,
                                 onValueChange = {},
                                 readOnly = true,
                                 modifier = Modifier
@@ -242,10 +258,16 @@ fun SettingsScreen(
                                     DropdownMenuItem(
                                         text = { Text(displayName) },
                                         onClick = {
-                                            llmModel = modelId
-                                            settingsStore.llmModel = modelId
-                                            currentModel = AiModel.fromIdentifier(modelId)
-                                            apiKey = settingsStore.getApiKey(currentModel.provider)
+                                            if (modelId == CUSTOM_MODEL_ID) {
+                                                if (!isCustomModel) {
+                                                    llmModel = ""
+                                                }
+                                                isCustomModel = true
+                                            } else {
+                                                isCustomModel = false
+                                                llmModel = modelId
+                                                settingsStore.llmModel = modelId
+                                            }
                                             expanded = false
                                         }
                                     )
@@ -254,6 +276,36 @@ fun SettingsScreen(
                         }
                     }
 
+                    if (isCustomModel) {
+                        CustomModelFields(
+                            modelIdentifier = llmModel,
+                            onModelIdentifierChange = {
+                                llmModel = it
+                                settingsStore.llmModel = it.trim()
+                            },
+                            provider = llmProvider,
+                            onProviderChange = {
+                                llmProvider = it
+                                settingsStore.llmProvider = it
+                            }
+                        )
+                    }
+
+                    EndpointFields(
+                        provider = currentModel.provider,
+                        baseUrl = baseUrl,
+                        onBaseUrlChange = {
+                            baseUrl = it
+                            settingsStore.setBaseUrl(currentModel.provider, it)
+                        },
+                        extraHeaders = extraHeaders,
+                        onExtraHeadersChange = {
+                            extraHeaders = it
+                            settingsStore.setExtraHeaders(currentModel.provider, it)
+                        },
+                        apiKey = apiKey
+                    )
+
                     // API Key
                     Column(
                         modifier = Modifier.fillMaxWidth(),
@@ -304,6 +356,51 @@ fun SettingsScreen(
                             }
                         }
                     }
+
+                    Row(
+                        modifier = Modifier.fillMaxWidth(),
+                        horizontalArrangement = Arrangement.SpaceBetween,
+                        verticalAlignment = Alignment.CenterVertically
+                    ) {
+                        Text(
+                            text = "Stream responses as they are generated",
+                            style = MaterialTheme.typography.bodyLarge
+                        )
+                        Switch(
+                            checked = streamResponses,
+                            onCheckedChange = {
+                                streamResponses = it
+                                settingsStore.streamResponses = it
+                            }
+                        )
+                    }
+
+                    Column(
+                        modifier = Modifier.fillMaxWidth(),
+                        verticalArrangement = Arrangement.spacedBy(8.dp)
+                    ) {
+                        Text(text = "Forget tool output after (turns)")
+                        OutlinedTextField(
+                            value = toolOutputMaxAge,
+                            onValueChange = { value ->
+                                toolOutputMaxAge = value.filter { it.isDigit() }
+                                toolOutputMaxAge.toIntOrNull()?.let {
+                                    settingsStore.toolOutputMaxAge = it
+                                }
+                            },
+                            modifier = Modifier.fillMaxWidth(),
+                            singleLine = true,
+                            keyboardOptions = KeyboardOptions(keyboardType = KeyboardType.Number)
+                        )
+                        Text(
+                            text = "Older tool results are replaced with a placeholder to keep long " +
+                                    "tasks within the model's context window.",
+                            style = MaterialTheme.typography.bodySmall,
+                            color = MaterialTheme.colorScheme.onSurfaceVariant
+                        )
+                    }
+
+                    RunLimitsSettings(settingsStore)
                 }
 
                 // Accessibility/Notifications Section
@@ -325,10 +422,41 @@ fun SettingsScreen(
                             onCheckedChange = {
                                 enableAppExtensions = it
                                 settingsStore.enableAppExtensions = it
+                                if (it) McpCatalog.start(context)
                             }
                         )
                     }
 
+                    if (enableAppExtensions) {
+                        Button(
+                            onClick = {
+                                isRefreshingExtensions = true
+                                scope.launch {
+                                    val servers = withContext(Dispatchers.IO) {
+                                        McpCatalog.refresh(context)
+                                    }
+                                    extensionsStatus = if (servers.isEmpty()) {
+                                        "No apps provide extensions"
+                                    } else {
+                                        "Found: ${servers.joinToString { it.info.name }}"
+                                    }
+                                    isRefreshingExtensions = false
+                                }
+                            },
+                            modifier = Modifier.fillMaxWidth(),
+                            enabled = !isRefreshingExtensions
+                        ) {
+                            Text(if (isRefreshingExtensions) "Looking for extensions..." else "Refresh app extensions")
+                        }
+                        extensionsStatus?.let {
+                            Text(
+                                text = it,
+                                style = MaterialTheme.typography.bodySmall,
+                                color = MaterialTheme.colorScheme.onSurfaceVariant
+                            )
+                        }
+                    }
+
                     if (isAccessibilityEnabled) {
                         Button(
                             onClick = showStatsSettings(),
@@ -560,3 +688,76 @@ fun SettingsScreen(
         )
     }
 }
+
+/**
+ * Step, token and time limits for each way a run can be started. 0 turns a limit off.
+ */
+@Composable
+private fun RunLimitsSettings(settingsStore: SettingsStore) {
+    Column(
+        modifier = Modifier.fillMaxWidth(),
+        verticalArrangement = Arrangement.spacedBy(8.dp)
+    ) {
+        Text(text = "Run limits")
+        Text(
+            text = "A run stops when it reaches any of these. Use 0 for no limit.",
+            style = MaterialTheme.typography.bodySmall,
+            color = MaterialTheme.colorScheme.onSurfaceVariant
+        )
+
+        Agent.TriggerType.entries.forEach { triggerType ->
+            var limits by remember { mutableStateOf(settingsStore.getRunLimits(triggerType)) }
+            var maxSteps by remember { mutableStateOf(limits.maxSteps.toString()) }
+            var maxTokens by remember { mutableStateOf(limits.maxTokens.toString()) }
+            var maxDuration by remember { mutableStateOf(limits.maxDurationSeconds.toString()) }
+
+            fun update(changed: RunLimits) {
+                limits = changed
+                settingsStore.setRunLimits(triggerType, changed)
+            }
+
+            Text(
+                text = triggerType.name.lowercase().replaceFirstChar { it.uppercase() },
+                style = MaterialTheme.typography.bodyMedium
+            )
+            Row(
+                modifier = Modifier.fillMaxWidth(),
+                horizontalArrangement = Arrangement.spacedBy(8.dp)
+            ) {
+                OutlinedTextField(
+                    value = maxSteps,
+                    onValueChange = { value ->
+                        maxSteps = value.filter { it.isDigit() }
+                        maxSteps.toIntOrNull()?.let { update(limits.copy(maxSteps = it)) }
+                    },
+                    label = { Text("Steps") },
+                    modifier = Modifier.weight(1f),
+                    singleLine = true,
+                    keyboardOptions = KeyboardOptions(keyboardType = KeyboardType.Number)
+                )
+                OutlinedTextField(
+                    value = maxTokens,
+                    onValueChange = { value ->
+                        maxTokens = value.filter { it.isDigit() }
+                        maxTokens.toIntOrNull()?.let { update(limits.copy(maxTokens = it)) }
+                    },
+                    label = { Text("Tokens") },
+                    modifier = Modifier.weight(1.4f),
+                    singleLine = true,
+                    keyboardOptions = KeyboardOptions(keyboardType = KeyboardType.Number)
+                )
+                OutlinedTextField(
+                    value = maxDuration,
+                    onValueChange = { value ->
+                        maxDuration = value.filter { it.isDigit() }
+                        maxDuration.toIntOrNull()?.let { update(limits.copy(maxDurationSeconds = it)) }
+                    },
+                    label = { Text("Seconds") },
+                    modifier = Modifier.weight(1f),
+                    singleLine = true,
+                    keyboardOptions = KeyboardOptions(keyboardType = KeyboardType.Number)
+                )
+            }
+        }
+    }
+}
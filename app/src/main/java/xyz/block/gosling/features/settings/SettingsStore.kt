@@ -5,8 +5,10 @@ import android.content.SharedPreferences
 import androidx.core.content.edit
 import androidx.security.crypto.EncryptedSharedPreferences
 import androidx.security.crypto.MasterKey
+import xyz.block.gosling.features.agent.Agent
 import xyz.block.gosling.features.agent.AiModel
 import xyz.block.gosling.features.agent.ModelProvider
+import xyz.block.gosling.features.agent.RunLimits
 
 class SettingsStore(context: Context) {
     private val prefs = context.getSharedPreferences(PREFS_NAME, Context.MODE_PRIVATE)
@@ -28,7 +30,10 @@ class SettingsStore(context: Context) {
         private const val SECURE_PREFS_NAME = "gosling_secure_prefs"
         private const val KEY_FIRST_TIME = "first_time"
         private const val KEY_LLM_MODEL = "llm_model"
+        private const val KEY_LLM_PROVIDER = "llm_provider"
         private const val KEY_API_KEY_PREFIX = "api_key_"
+        private const val KEY_BASE_URL_PREFIX = "base_url_"
+        private const val KEY_EXTRA_HEADERS_PREFIX = "extra_headers_"
         private const val KEY_ACCESSIBILITY_ENABLED = "accessibility_enabled"
         private const val KEY_PROCESS_NOTIFICATIONS = "process_notifications"
         private const val KEY_MESSAGE_HANDLING_PREFERENCES = "message_handling_preferences"
@@ -36,6 +41,12 @@ class SettingsStore(context: Context) {
         private const val KEY_HANDLE_SCREENSHOTS = "handle_screenshots"
         private const val KEY_SCREENSHOT_HANDLING_PREFERENCES = "screenshot_handling_preferences"
         private const val KEY_USER_MEMORIES = "user_memories"
+        private const val KEY_STREAM_RESPONSES = "stream_responses"
+        private const val KEY_TOOL_OUTPUT_MAX_AGE = "tool_output_max_age"
+        const val DEFAULT_TOOL_OUTPUT_MAX_AGE = 8
+        private const val KEY_MAX_STEPS_PREFIX = "max_steps_"
+        private const val KEY_MAX_TOKENS_PREFIX = "max_tokens_"
+        private const val KEY_MAX_DURATION_PREFIX = "max_duration_"
         private val DEFAULT_LLM_MODEL = AiModel.AVAILABLE_MODELS.first().identifier
     }
 
@@ -47,6 +58,16 @@ class SettingsStore(context: Context) {
         get() = prefs.getString(KEY_LLM_MODEL, DEFAULT_LLM_MODEL) ?: DEFAULT_LLM_MODEL
         set(value) = prefs.edit { putString(KEY_LLM_MODEL, value) }
 
+    // Only consulted for model identifiers that aren't in AiModel.AVAILABLE_MODELS
+    var llmProvider: ModelProvider
+        get() = prefs.getString(KEY_LLM_PROVIDER, null)
+            ?.let { name -> ModelProvider.entries.find { it.name == name } }
+            ?: ModelProvider.OPENAI
+        set(value) = prefs.edit { putString(KEY_LLM_PROVIDER, value.name) }
+
+    val currentModel: AiModel
+        get() = AiModel.fromIdentifier(llmModel, llmProvider)
+
     fun getApiKey(provider: ModelProvider): String {
         val key = "$KEY_API_KEY_PREFIX${provider.name}"
         return securePrefs.getString(key, "") ?: ""
@@ -57,6 +78,25 @@ class SettingsStore(context: Context) {
         securePrefs.edit { putString(key, value) }
     }
 
+    fun getBaseUrl(provider: ModelProvider): String {
+        return prefs.getString("$KEY_BASE_URL_PREFIX${provider.name}", "") ?: ""
+    }
+
+    fun setBaseUrl(provider: ModelProvider, value: String) {
+        prefs.edit { putString("$KEY_BASE_URL_PREFIX${provider.name}", value.trim()) }
+    }
+
+    // Extra headers often carry gateway credentials, so they live next to the API keys
+    fun getExtraHeaders(provider: ModelProvider): String {
+        val key = "$KEY_EXTRA_HEADERS_PREFIX${provider.name}"
+        return securePrefs.getString(key, "") ?: ""
+    }
+
+    fun setExtraHeaders(provider: ModelProvider, value: String) {
+        val key = "$KEY_EXTRA_HEADERS_PREFIX${provider.name}"
+        securePrefs.edit { putString(key, value) }
+    }
+
     var isAccessibilityEnabled: Boolean
         get() = prefs.getBoolean(KEY_ACCESSIBILITY_ENABLED, false)
         set(value) = prefs.edit { putBoolean(KEY_ACCESSIBILITY_ENABLED, value) }
@@ -80,6 +120,35 @@ class SettingsStore(context: Context) {
     var enableAppExtensions: Boolean
         get() = prefs.getBoolean(KEY_ENABLE_APP_EXTENSIONS, true) // Enabled by default
         set(value) = prefs.edit { putBoolean(KEY_ENABLE_APP_EXTENSIONS, value) }
+
+    var streamResponses: Boolean
+        get() = prefs.getBoolean(KEY_STREAM_RESPONSES, true) // Enabled by default
+        set(value) = prefs.edit { putBoolean(KEY_STREAM_RESPONSES, value) }
+
+    // Number of assistant turns after which tool outputs are dropped from the context
+    var toolOutputMaxAge: Int
+        get() = prefs.getInt(KEY_TOOL_OUTPUT_MAX_AGE, DEFAULT_TOOL_OUTPUT_MAX_AGE)
+        set(value) = prefs.edit { putInt(KEY_TOOL_OUTPUT_MAX_AGE, value) }
+
+    fun getRunLimits(triggerType: Agent.TriggerType): RunLimits {
+        val defaults = RunLimits.defaultFor(triggerType)
+        return RunLimits(
+            maxSteps = prefs.getInt("$KEY_MAX_STEPS_PREFIX${triggerType.name}", defaults.maxSteps),
+            maxTokens = prefs.getInt("$KEY_MAX_TOKENS_PREFIX${triggerType.name}", defaults.maxTokens),
+            maxDurationSeconds = prefs.getInt(
+                "$KEY_MAX_DURATION_PREFIX${triggerType.name}",
+                defaults.maxDurationSeconds
+            )
+        )
+    }
+
+    fun setRunLimits(triggerType: Agent.TriggerType, limits: RunLimits) {
+        prefs.edit {
+            putInt("$KEY_MAX_STEPS_PREFIX${triggerType.name}", limits.maxSteps)
+            putInt("$KEY_MAX_TOKENS_PREFIX${triggerType.name}", limits.maxTokens)
+            putInt("$KEY_MAX_DURATION_PREFIX${triggerType.name}", limits.maxDurationSeconds)
+        }
+    }
         
     var userMemories: String
         get() = prefs.getString(KEY_USER_MEMORIES, "") ?: ""
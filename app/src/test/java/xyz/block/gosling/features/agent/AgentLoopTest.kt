@@ -0,0 +1,139 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.coroutines.runBlocking
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class AgentLoopTest {
+    private val model = AiModel("Test", "test-model", ModelProvider.OPENAI)
+    private val http = ScriptedHttp()
+    private val client = LlmClient(http.client)
+    private val conversation = mutableListOf(
+        Message(role = "user", content = contentWithText("Open settings"))
+    )
+    private var cancelled = false
+
+    private fun loop(
+        provider: LlmProvider,
+        limits: RunLimits = RunLimits(maxSteps = 0, maxTokens = 0, maxDurationSeconds = 0)
+    ) = AgentLoop(
+        budget = RunBudget(limits),
+        messages = { conversation.toList() },
+        append = { conversation.add(it) },
+        callModel = { messages ->
+            client.complete(provider, provider.buildRequest(model, "key", messages, emptyList()))
+        },
+        runTools = { toolCalls, _ ->
+            val calls = toolCalls.orEmpty()
+            calls.map {
+                mapOf("tool_call_id" to it.toolId, "output" to "ran ${it.name}", "name" to it.name)
+            } to calls.map { mapOf("duration" to 0.0) }
+        },
+        isCancelled = { cancelled },
+        onStatus = {},
+        retryDelayMs = { 0L }
+    )
+
+    @Test
+    fun runsToolsUntilTheModelAnswersWithoutCallingAny() = runBlocking {
+        val provider = FakeProvider(
+            listOf(
+                AssistantTurn("Opening it", listOf(toolCall("call_1", "startApp"))),
+                AssistantTurn("Settings are open")
+            )
+        )
+
+        val outcome = loop(provider).run()
+
+        assertEquals(LoopOutcome.Completed("Settings are open"), outcome)
+        assertEquals(listOf("user", "assistant", "tool", "assistant"), conversation.map { it.role })
+        assertEquals("call_1", conversation[1].toolCalls!!.single().id)
+        assertEquals("call_1", conversation[2].toolCallId)
+        assertEquals("ran startApp", firstText(conversation[2]))
+        // The second call saw the tool result
+        assertEquals(3, provider.sentMessages[1].size)
+    }
+
+    @Test
+    fun retriesFailedModelCalls() = runBlocking {
+        http.enqueue(code = 500, body = "overloaded")
+        val provider = FakeProvider(listOf(AssistantTurn("Hello")))
+
+        val outcome = loop(provider).run()
+
+        assertEquals(LoopOutcome.Completed("Hello"), outcome)
+        assertEquals(2, http.requests.size)
+    }
+
+    @Test
+    fun givesUpAfterThreeFailedAttempts() = runBlocking {
+        repeat(3) { http.enqueue(code = 500, body = "overloaded") }
+
+        val outcome = loop(FakeProvider(emptyList())).run()
+
+        assertEquals("Failed after 3 attempts: overloaded", (outcome as LoopOutcome.Failed).message)
+        assertEquals(3, http.requests.size)
+    }
+
+    @Test
+    fun doesNotRetryApiKeyErrors() = runBlocking {
+        http.enqueue(code = 401, body = "")
+
+        val outcome = loop(FakeProvider(emptyList())).run()
+
+        assertTrue((outcome as LoopOutcome.Failed).cause is ApiKeyException)
+        assertEquals(1, http.requests.size)
+    }
+
+    @Test
+    fun stopsAtTheStepLimit() = runBlocking {
+        val provider = FakeProvider(
+            List(5) { AssistantTurn("Looking", listOf(toolCall("call_$it", "getUiHierarchy"))) }
+        )
+
+        val outcome = loop(provider, RunLimits(maxSteps = 2, maxTokens = 0, maxDurationSeconds = 0)).run()
+
+        assertEquals(StopReason.MAX_STEPS, (outcome as LoopOutcome.Stopped).status.reason)
+        assertEquals(2, http.requests.size)
+    }
+
+    @Test
+    fun asksToReplanOnceBeforeStoppingARunThatIsStuck() = runBlocking {
+        val provider = FakeProvider(
+            List(10) { AssistantTurn("Scrolling", listOf(toolCall("call_$it", "home"))) }
+        )
+
+        val outcome = loop(provider).run()
+
+        assertEquals(StopReason.STUCK, (outcome as LoopOutcome.Stopped).status.reason)
+        val replans = conversation.filter { it.role == "user" && firstText(it).startsWith("You appear to be stuck") }
+        assertEquals(1, replans.size)
+    }
+
+    @Test
+    fun endsWhenCancelledWhileToolsRun() = runBlocking {
+        val provider = FakeProvider(
+            List(3) { AssistantTurn("Working", listOf(toolCall("call_$it", "home"))) }
+        )
+        val loop = AgentLoop(
+            budget = RunBudget(RunLimits(maxSteps = 0, maxTokens = 0, maxDurationSeconds = 0)),
+            messages = { conversation.toList() },
+            append = { conversation.add(it) },
+            callModel = { messages ->
+                client.complete(provider, provider.buildRequest(model, "", messages, emptyList()))
+            },
+            runTools = { toolCalls, _ ->
+                cancelled = true
+                toolCalls.orEmpty().map {
+                    mapOf("tool_call_id" to it.toolId, "output" to "", "name" to it.name)
+                } to toolCalls.orEmpty().map { emptyMap() }
+            },
+            isCancelled = { cancelled },
+            onStatus = {}
+        )
+
+        assertEquals(LoopOutcome.Cancelled, loop.run())
+        assertEquals(1, http.requests.size)
+    }
+}
@@ -0,0 +1,79 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertNull
+import org.junit.Test
+
+class AnthropicProviderTest {
+    private val model = AiModel("Claude Sonnet 4", "claude-sonnet-4-20250514", ModelProvider.ANTHROPIC)
+
+    @Test
+    fun encodesTheConversationAsAlternatingBlocks() {
+        val request = AnthropicProvider()
+            .buildRequest(model, "a-key", sampleConversation(), sampleTools)
+        val body = bodyOf(request)
+
+        assertEquals("https://api.anthropic.com/v1/messages", request.url.toString())
+        assertEquals("a-key", request.header("x-api-key"))
+        assertEquals("2023-06-01", request.header("anthropic-version"))
+        assertEquals("You are an agent", body.getString("system"))
+        assertEquals(4096, body.getInt("max_tokens"))
+
+        val messages = body.getJSONArray("messages")
+        assertEquals(3, messages.length())
+
+        val assistant = messages.getJSONObject(1).getJSONArray("content")
+        assertEquals("text", assistant.getJSONObject(0).getString("type"))
+        val toolUse = assistant.getJSONObject(1)
+        assertEquals("tool_use", toolUse.getString("type"))
+        assertEquals("call_1", toolUse.getString("id"))
+        assertEquals("e1", toolUse.getJSONObject("input").getString("ref"))
+
+        // The tool result and the screenshot after it share one user turn
+        val user = messages.getJSONObject(2)
+        assertEquals("user", user.getString("role"))
+        val toolResult = user.getJSONArray("content").getJSONObject(0)
+        assertEquals("tool_result", toolResult.getString("type"))
+        assertEquals("call_1", toolResult.getString("tool_use_id"))
+        val image = user.getJSONArray("content").getJSONObject(1).getJSONObject("source")
+        assertEquals("base64", image.getString("type"))
+        assertEquals("image/png", image.getString("media_type"))
+
+        val tool = body.getJSONArray("tools").getJSONObject(0)
+        assertEquals("clickElement", tool.getString("name"))
+        assertEquals("object", tool.getJSONObject("input_schema").getString("type"))
+    }
+
+    @Test
+    fun decodesTextToolUseAndUsageFromAStream() {
+        val decoder = AnthropicProvider().newStreamDecoder()
+
+        assertNull(decoder.onData("""{"type":"message_start","message":{"usage":{"input_tokens":80,"output_tokens":1}}}"""))
+        assertNull(decoder.onData("""{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"""))
+        assertEquals(
+            "Tapping",
+            decoder.onData("""{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Tapping"}}""")
+        )
+        assertNull(
+            decoder.onData(
+                """{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"clickElement","input":{}}}"""
+            )
+        )
+        decoder.onData("""{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"ref\":"}}""")
+        decoder.onData("""{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"\"e7\"}"}}""")
+        decoder.onData("""{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":25}}""")
+
+        val turn = decoder.finish()
+        assertEquals("Tapping", turn.text)
+        val toolCall = turn.toolCalls!!.single()
+        assertEquals("toolu_1", toolCall.toolId)
+        assertEquals("e7", toolCall.arguments.getString("ref"))
+        assertEquals(TokenUsage(80, 25), turn.usage)
+    }
+
+    @Test(expected = AgentException::class)
+    fun failsOnAnErrorEvent() {
+        AnthropicProvider().newStreamDecoder()
+            .onData("""{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}""")
+    }
+}
@@ -0,0 +1,146 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class ContextCompactorTest {
+    private val model = AiModel("Test", "test-model", ModelProvider.OPENAI, contextWindow = 100_000)
+
+    private fun assistant(id: String, tool: String, arguments: String = "{}") = Message(
+        role = "assistant",
+        content = contentWithText("Calling $tool"),
+        toolCalls = listOf(ToolCall(id = id, function = ToolFunction(tool, arguments)))
+    )
+
+    private fun tool(id: String, name: String, output: String) = Message(
+        role = "tool",
+        toolCallId = id,
+        name = name,
+        content = contentWithText(output)
+    )
+
+    private val start = listOf(
+        Message(role = "system", content = contentWithText("You are an agent")),
+        Message(role = "user", content = contentWithText("Turn on wifi"))
+    )
+
+    private fun compact(messages: List<Message>, maxToolOutputAge: Int = 100) =
+        ContextCompactor(model, maxToolOutputAge).compact(messages)
+
+    @Test
+    fun leavesAShortConversationAlone() {
+        val messages = start +
+                assistant("1", "getUiHierarchy") + tool("1", "getUiHierarchy", "screen")
+
+        val result = compact(messages)
+
+        assertFalse(result.didCompact)
+        assertEquals(messages, result.messages)
+    }
+
+    @Test
+    fun keepsOnlyTheLatestLookAtTheScreen() {
+        val messages = start +
+                assistant("1", "getUiHierarchy") + tool("1", "getUiHierarchy", "first screen") +
+                assistant("2", "globalAction", """{"action":"quick_settings"}""") +
+                tool("2", "globalAction", "Opened quick_settings. This is the shade: wifi") +
+                assistant("3", "globalAction", """{"action":"back"}""") +
+                tool("3", "globalAction", "Performed global action back") +
+                assistant("4", "getUiChanges") + tool("4", "getUiChanges", "latest changes")
+
+        val result = compact(messages).messages
+
+        assertEquals("{outdated getUiHierarchy output truncated}", firstText(result[3]))
+        assertEquals("{outdated globalAction output truncated}", firstText(result[5]))
+        assertEquals("Performed global action back", firstText(result[7]))
+        assertEquals("latest changes", firstText(result[9]))
+    }
+
+    @Test
+    fun keepsOnlyTheLatestWebPageRead() {
+        val messages = start +
+                assistant("1", "webSearch") + tool("1", "webSearch", "results") +
+                assistant("2", "openUrl") + tool("2", "openUrl", "page") +
+                assistant("3", "getUiHierarchy") + tool("3", "getUiHierarchy", "screen")
+
+        val result = compact(messages).messages
+
+        assertEquals("{outdated webSearch output truncated}", firstText(result[3]))
+        // A look at the screen doesn't replace what was read on the web
+        assertEquals("page", firstText(result[5]))
+        assertEquals("screen", firstText(result[7]))
+    }
+
+    @Test
+    fun stripsScreenshotsOlderThanTheLatestLookAtTheScreen() {
+        val screenshot = Message(
+            role = "user",
+            content = listOf(
+                Content.Text(text = "Screenshot"),
+                Content.ImageUrl(imageUrl = Image(url = "data:image/png;base64,AAAA"))
+            )
+        )
+        val messages = start + assistant("1", "takeScreenshot") +
+                tool("1", "takeScreenshot", "Took a screenshot") + screenshot +
+                assistant("2", "getUiHierarchy") + tool("2", "getUiHierarchy", "screen")
+
+        val result = compact(messages).messages
+
+        assertEquals(listOf("Screenshot"), result[4].content!!.map { (it as Content.Text).text })
+    }
+
+    @Test
+    fun dropsToolOutputsOlderThanTheMaximumAge() {
+        val messages = start +
+                assistant("1", "startApp") + tool("1", "startApp", "Started Settings") +
+                assistant("2", "clickElement") + tool("2", "clickElement", "Clicked e3") +
+                assistant("3", "clickElement") + tool("3", "clickElement", "Clicked e9")
+
+        val result = compact(messages, maxToolOutputAge = 1)
+
+        assertEquals(1, result.droppedToolOutputs)
+        assertEquals("{output of startApp dropped, 2 turns old}", firstText(result.messages[3]))
+        assertEquals("Clicked e3", firstText(result.messages[5]))
+        assertEquals("Clicked e9", firstText(result.messages[7]))
+    }
+
+    @Test
+    fun summarizesOldTurnsWhenOverBudgetWithoutSplittingToolCalls() {
+        val small = AiModel("Small", "small-model", ModelProvider.OPENAI, contextWindow = 1000)
+        val messages = start + (1..10).flatMap { step ->
+            listOf(
+                assistant("$step", "enterText"),
+                tool("$step", "enterText", "x".repeat(400))
+            )
+        }
+
+        val result = ContextCompactor(small, maxToolOutputAge = 100).compact(messages)
+
+        assertTrue(result.summarizedMessages > 0)
+        assertEquals(start, result.messages.take(2))
+        assertTrue(firstText(result.messages[2]).startsWith("[Earlier steps of this task"))
+        assertEquals("assistant", result.messages[3].role)
+        assertEquals(messages.size - result.summarizedMessages + 1, result.messages.size)
+        assertTrue(result.estimatedTokensAfter < result.estimatedTokensBefore)
+    }
+
+    @Test
+    fun reportsOnlyWhatEarlierCompactionsDidNotCover() {
+        val compaction = CompactionResult(
+            messages = emptyList(),
+            summarizedMessages = 12,
+            droppedToolOutputs = 5,
+            estimatedTokensBefore = 900,
+            estimatedTokensAfter = 600
+        )
+
+        val delta = compaction.since(summarized = 8, dropped = 5)
+
+        assertEquals(4, delta.summarizedMessages)
+        assertEquals(0, delta.droppedToolOutputs)
+        assertTrue(delta.didCompact)
+        assertFalse(compaction.since(summarized = 12, dropped = 7).didCompact)
+    }
+}
@@ -0,0 +1,58 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class ElementMatcherTest {
+    private fun similarity(query: String, label: String) =
+        ElementMatcher.similarity(ElementMatcher.normalize(query), ElementMatcher.normalize(label))
+
+    @Test
+    fun scoresAnExactMatchHighest() {
+        assertEquals(1.0, similarity("Email", "email:"), 0.0)
+    }
+
+    @Test
+    fun prefersExactOverPrefixOverWordOverSubstring() {
+        val exact = similarity("Name", "Name")
+        val prefix = similarity("Name", "Name on card")
+        val word = similarity("Name", "Company name")
+        val substring = similarity("mail", "Email")
+
+        assertTrue(exact > prefix)
+        assertTrue(prefix > word)
+        assertTrue(word > substring)
+        assertTrue(substring > 0.0)
+    }
+
+    @Test
+    fun matchesAllWordsInAnyOrder() {
+        assertEquals(0.5, similarity("name first", "First name"), 1e-9)
+    }
+
+    @Test
+    fun prefersTheLabelTheQueryCoversMost() {
+        assertTrue(similarity("Name", "Last name") > similarity("Name", "Emergency contact name"))
+    }
+
+    @Test
+    fun doesNotMatchUnrelatedLabels() {
+        assertEquals(0.0, similarity("Phone", "Email address"), 0.0)
+        assertEquals(0.0, similarity("Phone", ""), 0.0)
+    }
+
+    @Test
+    fun normalizesPunctuationAndCase() {
+        assertEquals("e mail address", ElementMatcher.normalize("  E-mail Address: "))
+    }
+
+    @Test
+    fun splitsResourceIdsIntoWords() {
+        assertEquals("first name input", ElementMatcher.idWords("com.example:id/first_name_input"))
+        assertEquals(
+            "first name input",
+            ElementMatcher.normalize(ElementMatcher.idWords("firstNameInput"))
+        )
+    }
+}
@@ -0,0 +1,121 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.json.JsonPrimitive
+import okhttp3.MediaType.Companion.toMediaType
+import okhttp3.OkHttpClient
+import okhttp3.Protocol
+import okhttp3.Request
+import okhttp3.RequestBody.Companion.toRequestBody
+import okhttp3.Response
+import okhttp3.ResponseBody.Companion.toResponseBody
+import okio.Buffer
+import org.json.JSONObject
+
+/**
+ * Answers requests without touching the network: each call gets the next enqueued response,
+ * or an empty 200 once they run out.
+ */
+class ScriptedHttp {
+    val requests = mutableListOf<Request>()
+    private val responses = ArrayDeque<Pair<Int, String>>()
+
+    fun enqueue(code: Int = 200, body: String = "{}") {
+        responses.add(code to body)
+    }
+
+    val client: OkHttpClient = OkHttpClient.Builder()
+        .addInterceptor { chain ->
+            requests.add(chain.request())
+            val (code, body) = responses.removeFirstOrNull() ?: (200 to "{}")
+            Response.Builder()
+                .request(chain.request())
+                .protocol(Protocol.HTTP_1_1)
+                .code(code)
+                .message("")
+                .body(body.toResponseBody("application/json".toMediaType()))
+                .build()
+        }
+        .build()
+}
+
+/**
+ * A provider that replies with the given turns in order, whatever it was sent, and then with
+ * a plain "Done".
+ */
+class FakeProvider(turns: List<AssistantTurn>) : LlmProvider {
+    private val turns = ArrayDeque(turns)
+    val sentMessages = mutableListOf<List<Message>>()
+
+    override fun buildRequest(
+        model: AiModel,
+        apiKey: String,
+        messages: List<Message>,
+        tools: List<ToolDefinition>,
+        stream: Boolean
+    ): Request {
+        sentMessages.add(messages)
+        return Request.Builder()
+            .url("https://llm.test/chat")
+            .post("{}".toRequestBody("application/json".toMediaType()))
+            .build()
+    }
+
+    override fun parseResponse(body: String): AssistantTurn {
+        return turns.removeFirstOrNull() ?: AssistantTurn("Done")
+    }
+}
+
+fun toolCall(id: String, name: String, arguments: String = "{}"): InternalToolCall {
+    return InternalToolCall(toolId = id, name = name, arguments = JSONObject(arguments))
+}
+
+fun bodyOf(request: Request): JSONObject {
+    val buffer = Buffer()
+    request.body!!.writeTo(buffer)
+    return JSONObject(buffer.readUtf8())
+}
+
+/**
+ * A conversation using everything the providers have to encode: a system prompt, a tool call
+ * and its result, and a screenshot attached after it.
+ */
+fun sampleConversation(): List<Message> = listOf(
+    Message(role = "system", content = contentWithText("You are an agent")),
+    Message(role = "user", content = contentWithText("Open settings")),
+    Message(
+        role = "assistant",
+        content = contentWithText("Clicking it"),
+        toolCalls = listOf(
+            ToolCall(id = "call_1", function = ToolFunction("clickElement", """{"ref":"e1"}"""))
+        )
+    ),
+    Message(
+        role = "tool",
+        toolCallId = "call_1",
+        name = "clickElement",
+        content = contentWithText("Clicked e1")
+    ),
+    Message(
+        role = "user",
+        content = listOf(Content.ImageUrl(imageUrl = Image(url = "data:image/png;base64,AAAA")))
+    )
+)
+
+val sampleTools = listOf(
+    ToolDefinition(
+        function = ToolFunctionDefinition(
+            name = "clickElement",
+            description = "Clicks an element",
+            parameters = ToolParametersObject(
+                properties = mapOf(
+                    "ref" to ToolParameter(type = "string", description = "Element ref"),
+                    "times" to ToolParameter(
+                        type = "integer",
+                        default = JsonPrimitive(1)
+                    )
+                ),
+                required = listOf("ref")
+            )
+        )
+    )
+)
@@ -0,0 +1,103 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertNull
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class GeminiProviderTest {
+    private val model = AiModel("Gemini Flash", "gemini-2.0-flash", ModelProvider.GEMINI)
+
+    @Test
+    fun encodesTheConversationAsUserAndModelTurns() {
+        val request = GeminiProvider()
+            .buildRequest(model, "g-key", sampleConversation(), sampleTools)
+        val body = bodyOf(request)
+
+        assertEquals(
+            "https://generativelanguage.googleapis.com/v1beta/models/gemini-2.0-flash:generateContent?key=g-key",
+            request.url.toString()
+        )
+        val systemParts = body.getJSONObject("systemInstruction").getJSONArray("parts")
+        assertEquals("You are an agent", systemParts.getJSONObject(0).getString("text"))
+
+        val contents = body.getJSONArray("contents")
+        assertEquals(3, contents.length())
+        assertEquals("user", contents.getJSONObject(0).getString("role"))
+
+        val modelTurn = contents.getJSONObject(1)
+        assertEquals("model", modelTurn.getString("role"))
+        val functionCall = modelTurn.getJSONArray("parts").getJSONObject(1)
+            .getJSONObject("functionCall")
+        assertEquals("clickElement", functionCall.getString("name"))
+        assertEquals("e1", functionCall.getJSONObject("args").getString("ref"))
+
+        // The tool result and the screenshot after it share one user turn
+        val resultParts = contents.getJSONObject(2).getJSONArray("parts")
+        assertEquals(2, resultParts.length())
+        val functionResponse = resultParts.getJSONObject(0).getJSONObject("functionResponse")
+        assertEquals("clickElement", functionResponse.getString("name"))
+        assertEquals("Clicked e1", functionResponse.getJSONObject("response").getString("output"))
+        val inlineData = resultParts.getJSONObject(1).getJSONObject("inlineData")
+        assertEquals("image/png", inlineData.getString("mimeType"))
+    }
+
+    @Test
+    fun narrowsToolParametersToWhatGeminiAccepts() {
+        val body = bodyOf(
+            GeminiProvider().buildRequest(model, "g-key", sampleConversation(), sampleTools)
+        )
+
+        val declaration = body.getJSONArray("tools").getJSONObject(0)
+            .getJSONArray("function_declarations").getJSONObject(0)
+        val times = declaration.getJSONObject("parameters")
+            .getJSONObject("properties").getJSONObject("times")
+        assertEquals("string", times.getString("type"))
+        assertFalse(times.has("default"))
+    }
+
+    @Test
+    fun streamsThroughServerSentEvents() {
+        val request = GeminiProvider(ProviderConfig(baseUrl = "https://proxy.test/gemini"))
+            .buildRequest(model, "", sampleConversation(), sampleTools, stream = true)
+
+        assertEquals(
+            "https://proxy.test/gemini/models/gemini-2.0-flash:streamGenerateContent?alt=sse",
+            request.url.toString()
+        )
+    }
+
+    @Test
+    fun decodesTextFunctionCallsAndUsageFromAStream() {
+        val decoder = GeminiProvider().newStreamDecoder()
+
+        assertEquals(
+            "Opening",
+            decoder.onData("""{"candidates":[{"content":{"role":"model","parts":[{"text":"Opening"}]}}]}""")
+        )
+        assertNull(
+            decoder.onData(
+                """{"candidates":[{"content":{"parts":[{"functionCall":{"name":"startApp","args":{"name":"Settings"}}}]}}],"usageMetadata":{"promptTokenCount":50,"candidatesTokenCount":4}}"""
+            )
+        )
+
+        val turn = decoder.finish()
+        assertEquals("Opening", turn.text)
+        val toolCall = turn.toolCalls!!.single()
+        assertEquals("startApp", toolCall.name)
+        assertEquals("Settings", toolCall.arguments.getString("name"))
+        assertTrue(toolCall.toolId.isNotEmpty())
+        assertEquals(TokenUsage(50, 4), turn.usage)
+    }
+
+    @Test
+    fun reportsAnInvalidKeyAsAnApiKeyError() {
+        val error = GeminiProvider().classifyError(
+            400,
+            """{"error":{"status":"INVALID_ARGUMENT","details":[{"reason":"API_KEY_INVALID"}]}}"""
+        )
+
+        assertTrue(error is ApiKeyException)
+    }
+}
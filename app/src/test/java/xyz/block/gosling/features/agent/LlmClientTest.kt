@@ -0,0 +1,64 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class LlmClientTest {
+    private val model = AiModel("GPT-4o", "gpt-4o", ModelProvider.OPENAI)
+    private val http = ScriptedHttp()
+    private val client = LlmClient(http.client)
+    private val provider = OpenAIProvider()
+
+    private fun streamRequest() =
+        provider.buildRequest(model, "sk-test", sampleConversation(), emptyList(), stream = true)
+
+    @Test
+    fun readsAServerSentEventStream() {
+        http.enqueue(
+            body = """
+                |: keep-alive
+                |data: {"choices":[{"delta":{"content":"Hel"}}]}
+                |
+                |data: {"choices":[{"delta":{"content":"lo"}}]}
+                |
+                |data: [DONE]
+                |""".trimMargin()
+        )
+        val partials = mutableListOf<String>()
+
+        val turn = client.stream(provider, streamRequest(), onPartialText = { partials.add(it) })
+
+        assertEquals("Hello", turn.text)
+        assertEquals("Hel", partials.first())
+    }
+
+    @Test
+    fun stopsReadingOnceCancelled() {
+        http.enqueue(body = """data: {"choices":[{"delta":{"content":"Hello"}}]}""")
+
+        val turn = client.stream(provider, streamRequest(), isCancelled = { true })
+
+        assertEquals("Ok", turn.text)
+    }
+
+    @Test
+    fun turnsAnUnauthorizedResponseIntoAnApiKeyError() {
+        http.enqueue(code = 401, body = "")
+        val request = provider.buildRequest(model, "sk-test", sampleConversation(), emptyList())
+
+        val error = runCatching { client.complete(provider, request) }.exceptionOrNull()
+
+        assertTrue(error is ApiKeyException)
+    }
+
+    @Test
+    fun retriesAMalformedChunkLikeAnInterruptedStream() {
+        http.enqueue(body = """data: {"choices":[{"delta":{"content":"Hel""")
+
+        val error = runCatching { client.stream(provider, streamRequest()) }.exceptionOrNull()
+
+        assertTrue(error is AgentException)
+        assertTrue(error!!.message!!.startsWith("Malformed stream chunk"))
+    }
+}
@@ -0,0 +1,59 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertNotNull
+import org.junit.Assert.assertNull
+import org.junit.Test
+
+class LoopDetectorTest {
+    private val swipeUp =
+        toolCall("call_1", "swipe", """{"startX":500,"startY":1500,"endX":500,"endY":500}""")
+
+    @Test
+    fun flagsTheSameCallsWithTheSameResults() {
+        val detector = LoopDetector()
+
+        assertNull(detector.record(listOf(swipeUp), listOf("Swiped")))
+        assertNull(detector.record(listOf(swipeUp), listOf("Swiped")))
+        assertNotNull(detector.record(listOf(swipeUp), listOf("Swiped")))
+    }
+
+    @Test
+    fun letsRepeatedScrollingThroughWhileTheOutputChanges() {
+        val detector = LoopDetector()
+        val scrollBrowse = toolCall("call_2", "scrollBrowse", """{"direction":"down"}""")
+
+        for (page in 1..10) {
+            assertNull(detector.record(listOf(swipeUp), listOf("Swiped, showing item ${page * 10}")))
+            assertNull(detector.record(listOf(scrollBrowse), listOf("Page section $page")))
+        }
+    }
+
+    @Test
+    fun flagsActionsThatLeaveTheScreenUnchanged() {
+        val detector = LoopDetector()
+
+        val results = (1..5).map { turn ->
+            detector.record(
+                listOf(
+                    toolCall("call_$turn", "clickElement", """{"ref":"e$turn"}"""),
+                    toolCall("read_$turn", "getUiHierarchy")
+                ),
+                listOf("Clicked e$turn", "[e1] Button Save")
+            )
+        }
+
+        results.take(4).forEach { assertNull(it) }
+        assertNotNull(results.last())
+    }
+
+    @Test
+    fun startsOverAfterAReset() {
+        val detector = LoopDetector()
+        repeat(2) { detector.record(listOf(swipeUp), listOf("Swiped")) }
+
+        detector.reset()
+
+        assertNull(detector.record(listOf(swipeUp), listOf("Swiped")))
+        assertNull(detector.record(listOf(swipeUp), listOf("Swiped")))
+    }
+}
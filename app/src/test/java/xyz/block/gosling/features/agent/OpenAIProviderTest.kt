@@ -0,0 +1,110 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertNull
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class OpenAIProviderTest {
+    private val model = AiModel("GPT-4o", "gpt-4o", ModelProvider.OPENAI)
+
+    @Test
+    fun encodesTheConversationAndTools() {
+        val request = OpenAIProvider()
+            .buildRequest(model, "sk-test", sampleConversation(), sampleTools)
+        val body = bodyOf(request)
+
+        assertEquals("https://api.openai.com/v1/chat/completions", request.url.toString())
+        assertEquals("Bearer sk-test", request.header("Authorization"))
+        assertEquals("gpt-4o", body.getString("model"))
+        assertFalse(body.has("stream"))
+        assertFalse(body.has("stream_options"))
+
+        val messages = body.getJSONArray("messages")
+        assertEquals(5, messages.length())
+        val toolCall = messages.getJSONObject(2).getJSONArray("tool_calls").getJSONObject(0)
+        assertEquals("call_1", toolCall.getString("id"))
+        assertEquals("clickElement", toolCall.getJSONObject("function").getString("name"))
+        assertEquals("call_1", messages.getJSONObject(3).getString("tool_call_id"))
+
+        val function = body.getJSONArray("tools").getJSONObject(0).getJSONObject("function")
+        assertEquals("clickElement", function.getString("name"))
+        assertEquals("ref", function.getJSONObject("parameters").getJSONArray("required").getString(0))
+    }
+
+    @Test
+    fun asksForUsageWhenStreaming() {
+        val request = OpenAIProvider()
+            .buildRequest(model, "sk-test", sampleConversation(), sampleTools, stream = true)
+        val body = bodyOf(request)
+
+        assertTrue(body.getBoolean("stream"))
+        assertTrue(body.getJSONObject("stream_options").getBoolean("include_usage"))
+    }
+
+    @Test
+    fun streamsWithoutUsageToAServerThatRejectedStreamOptions() {
+        val provider = OpenAIProvider(ProviderConfig(baseUrl = "https://old-vllm.test/v1"))
+
+        val error = provider.classifyError(400, "Unrecognized request argument: stream_options")
+        val body = bodyOf(
+            provider.buildRequest(model, "", sampleConversation(), emptyList(), stream = true)
+        )
+
+        assertFalse(error is ApiKeyException)
+        assertTrue(body.getBoolean("stream"))
+        assertFalse(body.has("stream_options"))
+    }
+
+    @Test
+    fun sendsNoKeyToALocalServerWithoutOne() {
+        val config = ProviderConfig(
+            baseUrl = "http://localhost:11434/v1/",
+            extraHeaders = mapOf("X-Team" to "mobile")
+        )
+        val request = OpenAIProvider(config).buildRequest(model, "", sampleConversation(), emptyList())
+
+        assertEquals("http://localhost:11434/v1/chat/completions", request.url.toString())
+        assertNull(request.header("Authorization"))
+        assertEquals("mobile", request.header("X-Team"))
+        assertFalse(bodyOf(request).has("tools"))
+    }
+
+    @Test
+    fun decodesTextToolCallsAndUsageFromAStream() {
+        val decoder = OpenAIProvider().newStreamDecoder()
+
+        assertEquals("Open", decoder.onData("""{"choices":[{"delta":{"role":"assistant","content":"Open"}}]}"""))
+        assertEquals("ing", decoder.onData("""{"choices":[{"delta":{"content":"ing"}}]}"""))
+        assertNull(
+            decoder.onData(
+                """{"choices":[{"delta":{"content":null,"tool_calls":[{"index":0,"id":"call_9","function":{"name":"clickElement","arguments":"{\"re"}}]}}]}"""
+            )
+        )
+        assertNull(
+            decoder.onData(
+                """{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"f\":\"e4\"}"}}]}}]}"""
+            )
+        )
+        assertNull(decoder.onData("""{"choices":[],"usage":{"prompt_tokens":120,"completion_tokens":8}}"""))
+
+        val turn = decoder.finish()
+        assertEquals("Opening", turn.text)
+        val toolCall = turn.toolCalls!!.single()
+        assertEquals("call_9", toolCall.toolId)
+        assertEquals("clickElement", toolCall.name)
+        assertEquals("e4", toolCall.arguments.getString("ref"))
+        assertEquals(TokenUsage(120, 8), turn.usage)
+    }
+
+    @Test(expected = AgentException::class)
+    fun failsAStreamCutOffInsideToolArguments() {
+        val decoder = OpenAIProvider().newStreamDecoder()
+        decoder.onData(
+            """{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_9","function":{"name":"clickElement","arguments":"{\"ref\":"}}]}}]}"""
+        )
+
+        decoder.finish()
+    }
+}
@@ -0,0 +1,33 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Test
+
+class PartialToolCallTest {
+    @Test
+    fun parsesCompleteArguments() {
+        val partial = PartialToolCall(id = "call_1", name = "enterText")
+        partial.arguments.append("""{"text":"hel""").append("""lo"}""")
+
+        val toolCall = partial.toInternalToolCall()
+
+        assertEquals("call_1", toolCall.toolId)
+        assertEquals("enterText", toolCall.name)
+        assertEquals("hello", toolCall.arguments.getString("text"))
+    }
+
+    @Test
+    fun treatsMissingArgumentsAsAnEmptyObject() {
+        val toolCall = PartialToolCall(id = "call_1", name = "home").toInternalToolCall()
+
+        assertEquals(0, toolCall.arguments.length())
+    }
+
+    @Test(expected = AgentException::class)
+    fun failsOnArgumentsThatNeverFinished() {
+        val partial = PartialToolCall(id = "call_1", name = "enterText")
+        partial.arguments.append("""{"text":"hel""")
+
+        partial.toInternalToolCall()
+    }
+}
@@ -0,0 +1,48 @@
+package xyz.block.gosling.features.agent
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertNull
+import org.junit.Test
+
+class RunBudgetTest {
+    @Test
+    fun stopsAtTheStepLimit() {
+        val budget = RunBudget(RunLimits(maxSteps = 2, maxTokens = 0, maxDurationSeconds = 0), 0)
+
+        budget.recordStep(null)
+        assertNull(budget.exceeded(now = 0))
+        budget.recordStep(null)
+
+        assertEquals(StopReason.MAX_STEPS, budget.exceeded(now = 0))
+        assertEquals("Stopped after 2 steps (step limit)", budget.describe(StopReason.MAX_STEPS))
+    }
+
+    @Test
+    fun countsInputAndOutputTokens() {
+        val budget = RunBudget(RunLimits(maxSteps = 0, maxTokens = 1000, maxDurationSeconds = 0), 0)
+
+        budget.recordStep(TokenUsage(inputTokens = 600, outputTokens = 300))
+        assertNull(budget.exceeded(now = 0))
+        budget.recordStep(TokenUsage(inputTokens = 90, outputTokens = 10))
+
+        assertEquals(1000, budget.tokens)
+        assertEquals(StopReason.MAX_TOKENS, budget.exceeded(now = 0))
+    }
+
+    @Test
+    fun stopsAfterTheTimeLimit() {
+        val budget = RunBudget(RunLimits(maxSteps = 0, maxTokens = 0, maxDurationSeconds = 60), 1_000)
+
+        assertNull(budget.exceeded(now = 60_999))
+        assertEquals(StopReason.MAX_DURATION, budget.exceeded(now = 61_000))
+    }
+
+    @Test
+    fun treatsZeroAsNoLimit() {
+        val budget = RunBudget(RunLimits(maxSteps = 0, maxTokens = 0, maxDurationSeconds = 0), 0)
+
+        repeat(1000) { budget.recordStep(TokenUsage(inputTokens = 10_000, outputTokens = 1_000)) }
+
+        assertNull(budget.exceeded(now = Long.MAX_VALUE))
+    }
+}
@@ -0,0 +1,117 @@
+package xyz.block.gosling.features.agent
+
+import org.json.JSONObject
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class ToolArgumentValidatorTest {
+    private val schema = ToolParametersObject(
+        properties = mapOf(
+            "x" to ToolParameter(type = "integer"),
+            "scale" to ToolParameter(type = "number"),
+            "submit" to ToolParameter(type = "boolean"),
+            "label" to ToolParameter(type = "string"),
+            "direction" to ToolParameter(type = "string", enum = listOf("up", "down")),
+            "point" to ToolParameter(
+                type = "object",
+                properties = mapOf(
+                    "x" to ToolParameter(type = "integer"),
+                    "y" to ToolParameter(type = "integer")
+                ),
+                required = listOf("x", "y")
+            ),
+            "refs" to ToolParameter(type = "array", items = ToolParameter(type = "string"))
+        ),
+        required = listOf("x")
+    )
+
+    private fun validate(json: String) = ToolArgumentValidator.validate(JSONObject(json), schema)
+
+    @Test
+    fun coercesValuesSentAsStrings() {
+        val result = validate("""{"x":"42","scale":"1.5","submit":"True","label":7}""")
+
+        assertTrue(result.isValid)
+        assertEquals(42L, result.arguments.get("x"))
+        assertEquals(1.5, result.arguments.getDouble("scale"), 0.0)
+        assertEquals(true, result.arguments.get("submit"))
+        assertEquals("7", result.arguments.get("label"))
+    }
+
+    @Test
+    fun acceptsWholeNumbersSentAsDecimals() {
+        val result = validate("""{"x":12.0}""")
+
+        assertTrue(result.isValid)
+        assertEquals(12L, result.arguments.get("x"))
+    }
+
+    @Test
+    fun rejectsFractionsForIntegers() {
+        val problem = validate("""{"x":12.5}""").problems.single()
+
+        assertEquals("x", problem.field)
+        assertEquals("integer", problem.expected)
+    }
+
+    @Test
+    fun reportsMissingRequiredArguments() {
+        val problem = validate("""{"label":"OK"}""").problems.single()
+
+        assertEquals("x", problem.field)
+        assertEquals("Missing required argument 'x'", problem.message)
+    }
+
+    @Test
+    fun treatsNullAsMissing() {
+        val problem = validate("""{"x":null}""").problems.single()
+
+        assertEquals("Missing required argument 'x'", problem.message)
+    }
+
+    @Test
+    fun namesTheAcceptedArgumentsForAnUnknownOne() {
+        val problem = validate("""{"x":1,"text":"hi"}""").problems.single()
+
+        assertEquals("text", problem.field)
+        assertTrue(problem.message.contains("label"))
+    }
+
+    @Test
+    fun enforcesEnums() {
+        val problem = validate("""{"x":1,"direction":"left"}""").problems.single()
+
+        assertEquals("direction", problem.field)
+        assertEquals("up|down", problem.expected)
+    }
+
+    @Test
+    fun validatesNestedObjectsSentAsJsonStrings() {
+        val result = validate("""{"x":1,"point":"{\"x\":\"3\",\"y\":4}"}""")
+
+        assertTrue(result.isValid)
+        val point = result.arguments.getJSONObject("point")
+        assertEquals(3L, point.get("x"))
+        assertEquals(4, point.get("y"))
+    }
+
+    @Test
+    fun reportsProblemsInsideNestedObjectsByPath() {
+        val problem = validate("""{"x":1,"point":{"x":3}}""").problems.single()
+
+        assertEquals("point.y", problem.field)
+    }
+
+    @Test
+    fun validatesArrayItems() {
+        val valid = validate("""{"x":1,"refs":["e1",2]}""")
+        val invalid = validate("""{"x":1,"refs":["e1",{"ref":"e2"}]}""")
+
+        assertTrue(valid.isValid)
+        assertEquals("2", valid.arguments.getJSONArray("refs").get(1))
+        assertFalse(invalid.isValid)
+        assertEquals("refs[1]", invalid.problems.single().field)
+    }
+}
@@ -0,0 +1,125 @@
+package xyz.block.gosling.features.agent
+
+import kotlinx.serialization.json.JsonPrimitive
+import org.json.JSONObject
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertNull
+import org.junit.Test
+
+class ToolSchemaTest {
+    private fun parse(json: String) = jsonSchemaToParameters(JSONObject(json))
+
+    @Test
+    fun readsTypesDescriptionsEnumsAndDefaults() {
+        val schema = parse(
+            """
+            {
+              "type": "object",
+              "properties": {
+                "query": {"type": "string", "description": "What to search for"},
+                "limit": {"type": "integer", "default": 5},
+                "unit": {"type": "string", "enum": ["c", "f"], "default": "c"},
+                "exact": {"type": "boolean", "default": false}
+              },
+              "required": ["query"]
+            }
+            """
+        )
+
+        assertEquals(setOf("query", "limit", "unit", "exact"), schema.properties.keys)
+        assertEquals(listOf("query"), schema.required)
+        assertEquals(
+            ToolParameter(type = "string", description = "What to search for"),
+            schema.properties["query"]
+        )
+        assertEquals(JsonPrimitive(5), schema.properties["limit"]!!.default)
+        assertEquals(listOf("c", "f"), schema.properties["unit"]!!.enum)
+        assertEquals(JsonPrimitive("c"), schema.properties["unit"]!!.default)
+        assertEquals(JsonPrimitive(false), schema.properties["exact"]!!.default)
+    }
+
+    @Test
+    fun picksTheNonNullTypeOrInfersAMissingOne() {
+        val schema = parse(
+            """
+            {
+              "properties": {
+                "note": {"type": ["null", "string"]},
+                "tags": {"items": {"type": "string"}},
+                "point": {"properties": {"x": {"type": "integer"}}},
+                "anything": {}
+              }
+            }
+            """
+        )
+
+        assertEquals("string", schema.properties["note"]!!.type)
+        assertEquals("array", schema.properties["tags"]!!.type)
+        assertEquals("object", schema.properties["point"]!!.type)
+        assertEquals("string", schema.properties["anything"]!!.type)
+    }
+
+    @Test
+    fun readsNestedObjectsAndArrayItems() {
+        val schema = parse(
+            """
+            {
+              "type": "object",
+              "properties": {
+                "stops": {
+                  "type": "array",
+                  "items": {
+                    "type": "object",
+                    "properties": {
+                      "lat": {"type": "number"},
+                      "lng": {"type": "number"}
+                    },
+                    "required": ["lat", "lng"]
+                  }
+                }
+              }
+            }
+            """
+        )
+
+        val items = schema.properties["stops"]!!.items!!
+        assertEquals("object", items.type)
+        assertEquals(setOf("lat", "lng"), items.properties!!.keys)
+        assertEquals("number", items.properties!!["lat"]!!.type)
+        assertEquals(listOf("lat", "lng"), items.required)
+    }
+
+    @Test
+    fun dropsRequiredNamesThatAreNotDeclared() {
+        val schema = parse(
+            """
+            {
+              "type": "object",
+              "properties": {
+                "id": {"type": "string"},
+                "options": {"type": "object", "properties": {}, "required": ["verbose"]}
+              },
+              "required": ["id", "missing"]
+            }
+            """
+        )
+
+        assertEquals(listOf("id"), schema.required)
+        assertNull(schema.properties["options"]!!.required)
+    }
+
+    @Test
+    fun skipsPropertiesThatAreNotSchemas() {
+        val schema = parse("""{"properties": {"id": {"type": "string"}, "broken": true}}""")
+
+        assertEquals(setOf("id"), schema.properties.keys)
+    }
+
+    @Test
+    fun acceptsASchemaWithoutProperties() {
+        val schema = parse("""{"type": "object"}""")
+
+        assertEquals(emptyMap<String, ToolParameter>(), schema.properties)
+        assertEquals(emptyList<String>(), schema.required)
+    }
+}
@@ -0,0 +1,71 @@
+package xyz.block.gosling.features.agent
+
+import org.json.JSONObject
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+
+class UiTreeTest {
+    private fun node(ref: String, text: String? = null, top: Int = 0, checked: Boolean = false) =
+        UiNode(
+            ref = ref,
+            className = "Switch",
+            text = text,
+            bounds = listOf(0, top, 100, top + 50),
+            clickable = true,
+            checked = checked
+        )
+
+    @Test
+    fun findsNothingBetweenEqualSnapshots() {
+        val states = mapOf("e1" to node("e1", "Wi-Fi"), "e2" to node("e2", "Bluetooth", top = 50))
+
+        assertTrue(UiTree.diff(states, states).isEmpty)
+    }
+
+    @Test
+    fun matchesElementsByRef() {
+        val previous = mapOf(
+            "e1" to node("e1", "Wi-Fi"),
+            "e2" to node("e2", "Bluetooth", top = 50)
+        )
+        val current = mapOf(
+            "e1" to node("e1", "Wi-Fi"),
+            "e3" to node("e3", "Airplane mode", top = 50)
+        )
+
+        val diff = UiTree.diff(previous, current)
+
+        assertEquals(listOf("e3"), diff.added.map { it.ref })
+        assertEquals(listOf("e2"), diff.removed.map { it.ref })
+        assertTrue(diff.changed.isEmpty())
+    }
+
+    @Test
+    fun listsWhichFieldsChanged() {
+        val previous = mapOf("e1" to node("e1", "Wi-Fi"))
+        val current = mapOf("e1" to node("e1", "Wi-Fi on", top = 10, checked = true))
+
+        val change = UiTree.diff(previous, current).changed.single()
+
+        assertEquals("e1", change.node.ref)
+        assertEquals(listOf("text", "bounds", "checked"), change.fields)
+        assertTrue(change.node.checked)
+    }
+
+    @Test
+    fun leavesDefaultsOutOfTheEncodedDiff() {
+        val diff = UiTree.diff(emptyMap(), mapOf("e1" to node("e1", "Wi-Fi")))
+
+        val json = JSONObject(UiTree.encode(diff))
+
+        assertFalse(json.has("removed"))
+        assertFalse(json.has("changed"))
+        val added = json.getJSONArray("added").getJSONObject(0)
+        assertEquals("Switch", added.getString("class"))
+        assertTrue(added.getBoolean("clickable"))
+        assertFalse(added.has("checked"))
+        assertFalse(added.has("desc"))
+    }
+}
@@ -0,0 +1,48 @@
+package xyz.block.gosling.mmcp
+
+import android.os.ParcelFileDescriptor
+import android.util.Log
+import java.io.IOException
+import kotlin.concurrent.thread
+
+/**
+ * Moves JSON payloads over the bound-service transport. Small ones go inline; ones that could
+ * overflow a binder transaction (about 1 MB shared by the whole process) go through a pipe.
+ */
+object McpPayloads {
+    const val INLINE_LIMIT_BYTES = 64 * 1024
+
+    private const val TAG = "McpPayloads"
+
+    /**
+     * Returns the payload to send inline, or a pipe to read it from when it is too large. Close
+     * the pipe once it has been handed over; the receiver gets its own copy. A receiver that
+     * closes the pipe without reading it, or never gets it, only costs the write.
+     */
+    fun split(payload: String): Pair<String?, ParcelFileDescriptor?> {
+        val bytes = payload.toByteArray(Charsets.UTF_8)
+        if (bytes.size <= INLINE_LIMIT_BYTES) return payload to null
+
+        val (read, write) = ParcelFileDescriptor.createPipe()
+        // The reader only starts once the other side gets the descriptor, so write on a thread
+        thread(name = "mmcp-payload") {
+            try {
+                ParcelFileDescriptor.AutoCloseOutputStream(write).use { it.write(bytes) }
+            } catch (e: IOException) {
+                // EPIPE: the other side closed the pipe or never received it
+                Log.w(TAG, "Payload of ${bytes.size} bytes was not read: ${e.message}")
+            }
+        }
+        return null to read
+    }
+
+    /**
+     * Reads back a payload sent with [split]. Closes [large].
+     */
+    fun join(inline: String?, large: ParcelFileDescriptor?): String? {
+        if (large == null) return inline
+        return ParcelFileDescriptor.AutoCloseInputStream(large).use {
+            it.readBytes().toString(Charsets.UTF_8)
+        }
+    }
+}
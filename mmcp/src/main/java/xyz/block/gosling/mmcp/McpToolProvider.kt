@@ -0,0 +1,108 @@
+package xyz.block.gosling.mmcp
+
+import android.content.Context
+import android.util.Log
+import org.json.JSONObject
+
+/**
+ * A running tool call, as seen by the tool. Long-running tools should check [isCancelled] and
+ * report progress; over the broadcast transport neither goes anywhere.
+ */
+interface McpCall {
+    val isCancelled: Boolean
+
+    /**
+     * [progress] is between 0 and 1, or null when unknown.
+     */
+    fun reportProgress(message: String, progress: Float? = null)
+}
+
+/**
+ * The tools an app exposes, shared by [MobileMcpServer] and [MobileMcpService] so an app can
+ * implement them once and offer both transports.
+ */
+interface McpToolProvider {
+    val serverInfo: McpServerInfo
+
+    fun tools(context: Context): List<McpTool>
+
+    fun callTool(context: Context, name: String, arguments: JSONObject, call: McpCall): McpResult
+
+    /** Only asked for when offering [MobileMcp.CAPABILITY_RESOURCES]. */
+    fun resources(context: Context): List<McpResource> = emptyList()
+
+    /** Returns the contents of the resource as text. */
+    fun readResource(context: Context, uri: String): McpResult =
+        McpResult.Error(McpErrorCode.UNSUPPORTED_CAPABILITY, "No resources")
+
+    /** Only asked for when offering [MobileMcp.CAPABILITY_PROMPTS]. */
+    fun prompts(context: Context): List<McpPrompt> = emptyList()
+
+    /** Returns the prompt text with [arguments] filled in. */
+    fun getPrompt(context: Context, name: String, arguments: JSONObject): McpResult =
+        McpResult.Error(McpErrorCode.UNSUPPORTED_CAPABILITY, "No prompts")
+}
+
+internal object NoProgressCall : McpCall {
+    override val isCancelled = false
+    override fun reportProgress(message: String, progress: Float?) = Unit
+}
+
+/**
+ * Checks the request and runs the tool, turning anything that goes wrong into an error result.
+ */
+internal fun McpToolProvider.execute(
+    context: Context,
+    name: String?,
+    arguments: String?,
+    call: McpCall
+): McpResult {
+    if (name == null) {
+        return McpResult.Error(McpErrorCode.INVALID_ARGUMENTS, "No tool name given")
+    }
+    val known = try {
+        tools(context).any { it.name == name }
+    } catch (e: Exception) {
+        return internalError("Listing tools", e)
+    }
+    if (!known) {
+        return McpResult.Error(McpErrorCode.UNKNOWN_TOOL, "No tool named $name")
+    }
+
+    val json = try {
+        JSONObject(arguments ?: "{}")
+    } catch (e: Exception) {
+        return McpResult.Error(McpErrorCode.INVALID_ARGUMENTS, "Arguments are not a JSON object")
+    }
+
+    return try {
+        callTool(context, name, json, call)
+    } catch (e: Exception) {
+        internalError("Tool $name", e)
+    }
+}
+
+/**
+ * Runs a resource read or prompt request, turning exceptions into error results.
+ */
+internal inline fun catchingErrors(what: String, block: () -> McpResult): McpResult {
+    return try {
+        block()
+    } catch (e: Exception) {
+        internalError(what, e)
+    }
+}
+
+/**
+ * Logs an exception thrown by the provider and turns it into an error result.
+ */
+internal fun internalError(what: String, e: Exception): McpResult.Error {
+    Log.e("McpToolProvider", "$what failed", e)
+    return McpResult.Error(McpErrorCode.INTERNAL_ERROR, e.message ?: e.javaClass.simpleName)
+}
+
+internal fun unsupportedVersion(clientMax: Int): McpResult.Error = McpResult.Error(
+    McpErrorCode.UNSUPPORTED_VERSION,
+    "Client speaks up to version $clientMax, server speaks " +
+            "${MobileMcp.MIN_PROTOCOL_VERSION} to ${MobileMcp.PROTOCOL_VERSION}"
+)
@@ -0,0 +1,229 @@
+package xyz.block.gosling.mmcp
+
+import org.json.JSONArray
+import org.json.JSONObject
+
+data class McpServerInfo(
+    val name: String,
+    val version: String
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("name", name)
+        .put("version", version)
+
+    companion object {
+        fun fromJson(json: JSONObject): McpServerInfo = McpServerInfo(
+            name = json.optString("name"),
+            version = json.optString("version")
+        )
+    }
+}
+
+/**
+ * A tool a server exposes. [inputSchema] is a JSON Schema object describing the arguments,
+ * for example:
+ *
+ * ```
+ * {"type": "object",
+ *  "properties": {"location": {"type": "string", "description": "City name"}},
+ *  "required": ["location"]}
+ * ```
+ */
+data class McpTool(
+    val name: String,
+    val description: String,
+    val inputSchema: JSONObject = emptySchema()
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("name", name)
+        .put("description", description)
+        .put("inputSchema", inputSchema)
+
+    companion object {
+        fun emptySchema(): JSONObject = JSONObject()
+            .put("type", "object")
+            .put("properties", JSONObject())
+
+        fun fromJson(json: JSONObject): McpTool = McpTool(
+            name = json.getString("name"),
+            description = json.optString("description"),
+            inputSchema = json.optJSONObject("inputSchema") ?: emptySchema()
+        )
+
+        fun listToJson(tools: List<McpTool>): String =
+            JSONArray(tools.map { it.toJson() }).toString()
+
+        fun listFromJson(json: String): List<McpTool> {
+            val array = JSONArray(json)
+            return (0 until array.length()).map { fromJson(array.getJSONObject(it)) }
+        }
+    }
+}
+
+/**
+ * Data a server lets the agent read, such as a list of orders or a note. [uri] is whatever the
+ * server uses to find it again, e.g. "orders://recent".
+ */
+data class McpResource(
+    val uri: String,
+    val name: String,
+    val description: String = "",
+    val mimeType: String = "text/plain"
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("uri", uri)
+        .put("name", name)
+        .put("description", description)
+        .put("mimeType", mimeType)
+
+    companion object {
+        fun fromJson(json: JSONObject): McpResource = McpResource(
+            uri = json.getString("uri"),
+            name = json.optString("name").ifEmpty { json.getString("uri") },
+            description = json.optString("description"),
+            mimeType = json.optString("mimeType").ifEmpty { "text/plain" }
+        )
+
+        fun listToJson(resources: List<McpResource>): String =
+            JSONArray(resources.map { it.toJson() }).toString()
+
+        fun listFromJson(json: String): List<McpResource> {
+            val array = JSONArray(json)
+            return (0 until array.length()).map { fromJson(array.getJSONObject(it)) }
+        }
+    }
+}
+
+data class McpPromptArgument(
+    val name: String,
+    val description: String = "",
+    val required: Boolean = false
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("name", name)
+        .put("description", description)
+        .put("required", required)
+
+    companion object {
+        fun fromJson(json: JSONObject): McpPromptArgument = McpPromptArgument(
+            name = json.getString("name"),
+            description = json.optString("description"),
+            required = json.optBoolean("required")
+        )
+    }
+}
+
+/**
+ * A command template a server suggests, such as "Reorder my last meal". Getting it with
+ * [MobileMcp.ACTION_GET_PROMPT] returns the text to hand to the agent.
+ */
+data class McpPrompt(
+    val name: String,
+    val description: String = "",
+    val arguments: List<McpPromptArgument> = emptyList()
+) {
+    fun toJson(): JSONObject = JSONObject()
+        .put("name", name)
+        .put("description", description)
+        .put("arguments", JSONArray(arguments.map { it.toJson() }))
+
+    companion object {
+        fun fromJson(json: JSONObject): McpPrompt {
+            val arguments = json.optJSONArray("arguments") ?: JSONArray()
+            return McpPrompt(
+                name = json.getString("name"),
+                description = json.optString("description"),
+                arguments = (0 until arguments.length())
+                    .map { McpPromptArgument.fromJson(arguments.getJSONObject(it)) }
+            )
+        }
+
+        fun listToJson(prompts: List<McpPrompt>): String =
+            JSONArray(prompts.map { it.toJson() }).toString()
+
+        fun listFromJson(json: String): List<McpPrompt> {
+            val array = JSONArray(json)
+            return (0 until array.length()).map { fromJson(array.getJSONObject(it)) }
+        }
+    }
+}
+
+enum class McpErrorCode(val wireName: String) {
+    /** The server has no tool with the requested name. */
+    UNKNOWN_TOOL("unknown_tool"),
+
+    /** The server has no resource or prompt with the requested URI or name. */
+    NOT_FOUND("not_found"),
+
+    /** The arguments don't match the tool's input schema. */
+    INVALID_ARGUMENTS("invalid_arguments"),
+
+    /** The tool ran but could not do what was asked. */
+    TOOL_FAILED("tool_failed"),
+
+    /** Client and server have no protocol version in common. */
+    UNSUPPORTED_VERSION("unsupported_version"),
+
+    /** The server does not offer what was asked for. */
+    UNSUPPORTED_CAPABILITY("unsupported_capability"),
+
+    /** The client cancelled the call. */
+    CANCELLED("cancelled"),
+
+    /** The server did not answer in time. Set by the client. */
+    TIMEOUT("timeout"),
+
+    /** The server answered without a result. Set by the client. */
+    NO_RESPONSE("no_response"),
+
+    /** Anything else, such as an exception in the server. */
+    INTERNAL_ERROR("internal_error");
+
+    companion object {
+        fun fromWireName(name: String?): McpErrorCode =
+            entries.find { it.wireName == name } ?: INTERNAL_ERROR
+    }
+}
+
+/**
+ * The outcome of a tool call. On the wire:
+ *
+ * ```
+ * {"isError": false, "content": [{"type": "text", "text": "Sunny, 25°C"}]}
+ * {"isError": true, "error": {"code": "unknown_tool", "message": "No tool named foo"}}
+ * ```
+ */
+sealed class McpResult {
+    data class Success(val text: String) : McpResult()
+
+    data class Error(val code: McpErrorCode, val message: String) : McpResult()
+
+    fun toJson(): JSONObject = when (this) {
+        is Success -> JSONObject()
+            .put("isError", false)
+            .put("content", JSONArray().put(JSONObject().put("type", "text").put("text", text)))
+
+        is Error -> JSONObject()
+            .put("isError", true)
+            .put("error", JSONObject().put("code", code.wireName).put("message", message))
+    }
+
+    companion object {
+        fun fromJson(json: JSONObject): McpResult {
+            if (json.optBoolean("isError")) {
+                val error = json.optJSONObject("error") ?: JSONObject()
+                return Error(
+                    McpErrorCode.fromWireName(error.optString("code")),
+                    error.optString("message")
+                )
+            }
+
+            val content = json.optJSONArray("content") ?: JSONArray()
+            val text = (0 until content.length())
+                .mapNotNull { content.optJSONObject(it) }
+                .filter { it.optString("type") == "text" }
+                .joinToString("\n") { it.optString("text") }
+            return Success(text)
+        }
+    }
+}
@@ -0,0 +1,98 @@
+package xyz.block.gosling.mmcp
+
+/**
+ * Wire constants of the Mobile MCP protocol, spoken over ordered broadcasts between an agent
+ * (the client, such as Gosling) and apps that expose tools (servers).
+ *
+ * Discovery: the client sends [ACTION_DISCOVER] to every receiver that handles it, with the
+ * range of protocol versions it speaks and the capabilities it understands. A server answers in
+ * its result extras with the version it picked, its own capabilities, who it is and, for the
+ * tools capability, its tool definitions.
+ *
+ * Invocation: the client sends [ACTION_INVOKE] to one server with the negotiated version, the
+ * tool name and the arguments as a JSON object. The server answers with a [McpResult] encoded
+ * as JSON in [EXTRA_RESULT].
+ *
+ * Resources (data the agent can read) and prompts (suggested commands) work the same way with
+ * [ACTION_READ_RESOURCE] and [ACTION_GET_PROMPT], for servers that offer those capabilities.
+ *
+ * Apps that also run a [MobileMcpService] get invocations over binder instead, which allows
+ * long-running calls, progress, cancellation and large payloads. Discovery always uses
+ * broadcasts.
+ *
+ * See mmcp/README.md for the full description.
+ */
+object MobileMcp {
+    /** Highest protocol version this library speaks. */
+    const val PROTOCOL_VERSION = 1
+
+    /** Lowest protocol version this library still speaks. */
+    const val MIN_PROTOCOL_VERSION = 1
+
+    const val ACTION_DISCOVER = "xyz.block.gosling.mmcp.action.DISCOVER"
+    const val ACTION_INVOKE = "xyz.block.gosling.mmcp.action.INVOKE"
+
+    const val ACTION_READ_RESOURCE = "xyz.block.gosling.mmcp.action.READ_RESOURCE"
+    const val ACTION_GET_PROMPT = "xyz.block.gosling.mmcp.action.GET_PROMPT"
+
+    /** Service action for the bound-service transport, see [MobileMcpService]. */
+    const val ACTION_BIND = "xyz.block.gosling.mmcp.action.BIND"
+
+    /** Int. Highest version the client speaks; in a response, the version the server picked. */
+    const val EXTRA_PROTOCOL_VERSION = "xyz.block.gosling.mmcp.extra.PROTOCOL_VERSION"
+
+    /** Int. Lowest version the client speaks. */
+    const val EXTRA_MIN_PROTOCOL_VERSION = "xyz.block.gosling.mmcp.extra.MIN_PROTOCOL_VERSION"
+
+    /** String array. Capabilities the client understands, or the server offers. */
+    const val EXTRA_CAPABILITIES = "xyz.block.gosling.mmcp.extra.CAPABILITIES"
+
+    /** JSON object with the server's name and version, see [McpServerInfo]. */
+    const val EXTRA_SERVER_INFO = "xyz.block.gosling.mmcp.extra.SERVER_INFO"
+
+    /** JSON array of [McpTool] definitions. */
+    const val EXTRA_TOOLS = "xyz.block.gosling.mmcp.extra.TOOLS"
+
+    /** JSON array of [McpResource] definitions. */
+    const val EXTRA_RESOURCES = "xyz.block.gosling.mmcp.extra.RESOURCES"
+
+    /** JSON array of [McpPrompt] definitions. */
+    const val EXTRA_PROMPTS = "xyz.block.gosling.mmcp.extra.PROMPTS"
+
+    /** String. URI of the resource to read. */
+    const val EXTRA_URI = "xyz.block.gosling.mmcp.extra.URI"
+
+    /** String. Name of the prompt to get; its arguments go in [EXTRA_ARGUMENTS]. */
+    const val EXTRA_PROMPT = "xyz.block.gosling.mmcp.extra.PROMPT"
+
+    /** String. Name of the tool to invoke. */
+    const val EXTRA_TOOL = "xyz.block.gosling.mmcp.extra.TOOL"
+
+    /** JSON object with the tool arguments. */
+    const val EXTRA_ARGUMENTS = "xyz.block.gosling.mmcp.extra.ARGUMENTS"
+
+    /** JSON object, see [McpResult]. Also carries the error when discovery fails. */
+    const val EXTRA_RESULT = "xyz.block.gosling.mmcp.extra.RESULT"
+
+    /** The server exposes tools through [EXTRA_TOOLS] and [ACTION_INVOKE]. */
+    const val CAPABILITY_TOOLS = "tools"
+
+    /** The server lists resources in [EXTRA_RESOURCES] and reads them on [ACTION_READ_RESOURCE]. */
+    const val CAPABILITY_RESOURCES = "resources"
+
+    /** The server lists prompts in [EXTRA_PROMPTS] and fills them in on [ACTION_GET_PROMPT]. */
+    const val CAPABILITY_PROMPTS = "prompts"
+
+    /**
+     * The version both sides speak, or null if their ranges don't overlap.
+     */
+    fun negotiateVersion(
+        clientMin: Int,
+        clientMax: Int,
+        serverMin: Int = MIN_PROTOCOL_VERSION,
+        serverMax: Int = PROTOCOL_VERSION
+    ): Int? {
+        val version = minOf(clientMax, serverMax)
+        return if (version >= maxOf(clientMin, serverMin)) version else null
+    }
+}
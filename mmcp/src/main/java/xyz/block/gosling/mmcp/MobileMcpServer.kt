@@ -0,0 +1,152 @@
+package xyz.block.gosling.mmcp
+
+import android.content.BroadcastReceiver
+import android.content.Context
+import android.content.Intent
+import android.os.Bundle
+import org.json.JSONObject
+import kotlin.concurrent.thread
+
+/**
+ * Base class for apps that expose tools to agents. Subclass it, register the receiver as
+ * exported with intent filters for [MobileMcp.ACTION_DISCOVER] and [MobileMcp.ACTION_INVOKE],
+ * and implement the [McpToolProvider] members. Version negotiation and encoding are handled
+ * here.
+ *
+ * To offer resources or prompts too, add their capabilities to [capabilities], override the
+ * matching [McpToolProvider] members and add [MobileMcp.ACTION_READ_RESOURCE] or
+ * [MobileMcp.ACTION_GET_PROMPT] to the intent filter.
+ *
+ * [callTool] runs on a background thread, so it may do I/O, but it should finish within a few
+ * seconds: the client gives up on slow broadcasts. Tools that take longer belong in a
+ * [MobileMcpService].
+ */
+abstract class MobileMcpServer : BroadcastReceiver(), McpToolProvider {
+
+    open val capabilities: Set<String> = setOf(MobileMcp.CAPABILITY_TOOLS)
+
+    override fun onReceive(context: Context, intent: Intent) {
+        when (intent.action) {
+            MobileMcp.ACTION_DISCOVER -> setResultExtras(discover(context, intent))
+            MobileMcp.ACTION_INVOKE -> answerAsync(intent) { invoke(context, intent) }
+            MobileMcp.ACTION_READ_RESOURCE -> answerAsync(intent) { handleReadResource(context, intent) }
+            MobileMcp.ACTION_GET_PROMPT -> answerAsync(intent) { handleGetPrompt(context, intent) }
+        }
+    }
+
+    /**
+     * Computes the result off the main thread and returns it as the broadcast result. Anything
+     * the block throws is answered with an error, since an uncaught exception on this thread
+     * would take the whole app down.
+     */
+    private fun answerAsync(intent: Intent, block: () -> McpResult) {
+        val pending = goAsync()
+        thread(name = "mmcp-${serverInfo.name}") {
+            try {
+                val result = catchingErrors("Request ${intent.action}") { block() }
+                pending.setResultExtras(Bundle().apply {
+                    putInt(
+                        MobileMcp.EXTRA_PROTOCOL_VERSION,
+                        negotiate(intent) ?: MobileMcp.PROTOCOL_VERSION
+                    )
+                    putString(MobileMcp.EXTRA_RESULT, result.toJson().toString())
+                })
+            } finally {
+                pending.finish()
+            }
+        }
+    }
+
+    private fun discover(context: Context, intent: Intent): Bundle {
+        val extras = Bundle()
+        val version = negotiate(intent)
+            ?: return extras.apply {
+                putString(MobileMcp.EXTRA_RESULT, unsupportedVersion(clientMax(intent)).toJson().toString())
+            }
+
+        extras.putInt(MobileMcp.EXTRA_PROTOCOL_VERSION, version)
+        try {
+            extras.putStringArray(MobileMcp.EXTRA_CAPABILITIES, capabilities.toTypedArray())
+            extras.putString(MobileMcp.EXTRA_SERVER_INFO, serverInfo.toJson().toString())
+            if (MobileMcp.CAPABILITY_TOOLS in capabilities) {
+                extras.putString(MobileMcp.EXTRA_TOOLS, McpTool.listToJson(tools(context)))
+            }
+            if (MobileMcp.CAPABILITY_RESOURCES in capabilities) {
+                extras.putString(MobileMcp.EXTRA_RESOURCES, McpResource.listToJson(resources(context)))
+            }
+            if (MobileMcp.CAPABILITY_PROMPTS in capabilities) {
+                extras.putString(MobileMcp.EXTRA_PROMPTS, McpPrompt.listToJson(prompts(context)))
+            }
+        } catch (e: Exception) {
+            val error = internalError("Discovery", e)
+            extras.putString(MobileMcp.EXTRA_RESULT, error.toJson().toString())
+        }
+        return extras
+    }
+
+    private fun handleReadResource(context: Context, intent: Intent): McpResult {
+        if (negotiate(intent) == null) return unsupportedVersion(clientMax(intent))
+        if (MobileMcp.CAPABILITY_RESOURCES !in capabilities) {
+            return McpResult.Error(McpErrorCode.UNSUPPORTED_CAPABILITY, "No resources")
+        }
+
+        val uri = intent.getStringExtra(MobileMcp.EXTRA_URI)
+            ?: return McpResult.Error(McpErrorCode.INVALID_ARGUMENTS, "No resource URI given")
+        return catchingErrors("Reading $uri") {
+            if (resources(context).none { it.uri == uri }) {
+                McpResult.Error(McpErrorCode.NOT_FOUND, "No resource $uri")
+            } else {
+                readResource(context, uri)
+            }
+        }
+    }
+
+    private fun handleGetPrompt(context: Context, intent: Intent): McpResult {
+        if (negotiate(intent) == null) return unsupportedVersion(clientMax(intent))
+        if (MobileMcp.CAPABILITY_PROMPTS !in capabilities) {
+            return McpResult.Error(McpErrorCode.UNSUPPORTED_CAPABILITY, "No prompts")
+        }
+
+        val name = intent.getStringExtra(MobileMcp.EXTRA_PROMPT)
+            ?: return McpResult.Error(McpErrorCode.INVALID_ARGUMENTS, "No prompt name given")
+        val prompt = try {
+            prompts(context).find { it.name == name }
+        } catch (e: Exception) {
+            return internalError("Listing prompts", e)
+        } ?: return McpResult.Error(McpErrorCode.NOT_FOUND, "No prompt named $name")
+
+        val arguments = try {
+            JSONObject(intent.getStringExtra(MobileMcp.EXTRA_ARGUMENTS) ?: "{}")
+        } catch (e: Exception) {
+            return McpResult.Error(McpErrorCode.INVALID_ARGUMENTS, "Arguments are not a JSON object")
+        }
+        val missing = prompt.arguments.filter { it.required && !arguments.has(it.name) }
+        if (missing.isNotEmpty()) {
+            return McpResult.Error(
+                McpErrorCode.INVALID_ARGUMENTS,
+                "Missing arguments: ${missing.joinToString { it.name }}"
+            )
+        }
+        return catchingErrors("Prompt $name") { getPrompt(context, name, arguments) }
+    }
+
+    private fun invoke(context: Context, intent: Intent): McpResult {
+        if (negotiate(intent) == null) return unsupportedVersion(clientMax(intent))
+
+        return execute(
+            context,
+            intent.getStringExtra(MobileMcp.EXTRA_TOOL),
+            intent.getStringExtra(MobileMcp.EXTRA_ARGUMENTS),
+            NoProgressCall
+        )
+    }
+
+    private fun clientMax(intent: Intent): Int =
+        intent.getIntExtra(MobileMcp.EXTRA_PROTOCOL_VERSION, 0)
+
+    private fun negotiate(intent: Intent): Int? {
+        val clientMax = clientMax(intent)
+        val clientMin = intent.getIntExtra(MobileMcp.EXTRA_MIN_PROTOCOL_VERSION, clientMax)
+        return MobileMcp.negotiateVersion(clientMin, clientMax)
+    }
+}
@@ -0,0 +1,102 @@
+package xyz.block.gosling.mmcp
+
+import android.app.Service
+import android.content.Intent
+import android.os.IBinder
+import android.os.ParcelFileDescriptor
+import android.os.RemoteException
+import android.util.Log
+import java.util.concurrent.ConcurrentHashMap
+import java.util.concurrent.ExecutorService
+import java.util.concurrent.Executors
+
+/**
+ * Bound-service transport for apps whose tools take long, report progress, or return large
+ * results. Subclass it, implement the [McpToolProvider] members and register it as exported
+ * with an intent filter for [MobileMcp.ACTION_BIND]. Discovery still goes through a
+ * [MobileMcpServer] in the same app; clients then prefer this service for calls.
+ *
+ * Calls run concurrently on a thread pool and can be cancelled by the client.
+ */
+abstract class MobileMcpService : Service(), McpToolProvider {
+
+    private val executor: ExecutorService = Executors.newCachedThreadPool()
+    private val activeCalls = ConcurrentHashMap<String, ActiveCall>()
+
+    private class ActiveCall(
+        val id: String,
+        val callback: IMobileMcpCallback
+    ) : McpCall {
+        @Volatile
+        override var isCancelled = false
+
+        override fun reportProgress(message: String, progress: Float?) {
+            try {
+                callback.onProgress(id, progress ?: -1f, message)
+            } catch (e: RemoteException) {
+                // The client is gone; nobody is waiting for this call any more
+                isCancelled = true
+            }
+        }
+    }
+
+    private val binder = object : IMobileMcpService.Stub() {
+        override fun getProtocolVersion(): Int = MobileMcp.PROTOCOL_VERSION
+
+        override fun callTool(
+            callId: String,
+            protocolVersion: Int,
+            tool: String?,
+            arguments: String?,
+            largeArguments: ParcelFileDescriptor?,
+            callback: IMobileMcpCallback
+        ) {
+            val call = ActiveCall(callId, callback)
+            activeCalls[callId] = call
+
+            executor.execute {
+                val result = try {
+                    if (MobileMcp.negotiateVersion(protocolVersion, protocolVersion) == null) {
+                        unsupportedVersion(protocolVersion)
+                    } else {
+                        val json = McpPayloads.join(arguments, largeArguments)
+                        execute(this@MobileMcpService, tool, json, call)
+                    }
+                } catch (e: Exception) {
+                    McpResult.Error(McpErrorCode.INTERNAL_ERROR, e.message ?: "Call failed")
+                } finally {
+                    // Already closed by join, unless the call was refused before reading it
+                    largeArguments?.close()
+                    activeCalls.remove(callId)
+                }
+
+                val (inline, large) = McpPayloads.split(result.toJson().toString())
+                try {
+                    callback.onResult(callId, inline, large)
+                } catch (e: RemoteException) {
+                    Log.w(TAG, "Client went away before the result of $tool")
+                } finally {
+                    large?.close()
+                }
+            }
+        }
+
+        override fun cancel(callId: String) {
+            activeCalls[callId]?.isCancelled = true
+        }
+    }
+
+    override fun onBind(intent: Intent?): IBinder? {
+        return if (intent?.action == MobileMcp.ACTION_BIND) binder else null
+    }
+
+    override fun onDestroy() {
+        activeCalls.values.forEach { it.isCancelled = true }
+        executor.shutdownNow()
+        super.onDestroy()
+    }
+
+    private companion object {
+        const val TAG = "MobileMcpService"
+    }
+}
@@ -21,3 +21,4 @@ dependencyResolutionManagement {
 
 rootProject.name = "Gosling"
 include(":app")
+include(":mmcp")